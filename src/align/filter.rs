@@ -1,13 +1,12 @@
 //! Filter alignments in a SAM/BAM/CRAM file.
 
-use crate::cli::CliOpt;
+use crate::{cli::CliOpt, error::BjtError, utils::sink::OutputOpts};
 use bam::{Record, RecordReader, RecordWriter};
 use clap::Parser;
 use regex::Regex;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use std::str::from_utf8;
 
 /// Options for filtering alignments from a SAM/BAM/CRAM file.
 #[derive(Debug, Parser)]
@@ -29,9 +28,8 @@ pub struct SamBamCramFilterOpts {
     )]
     id_list_path: Option<PathBuf>,
 
-    /// Output file name.
-    #[clap(short, long)]
-    output: Option<PathBuf>,
+    #[clap(flatten)]
+    output: OutputOpts,
 
     /// Keep the records that match, instead of discarding them.
     #[clap(short, long)]
@@ -44,38 +42,75 @@ impl CliOpt for SamBamCramFilterOpts {}
 /// Assumes a sorted SAM/BAM file and a sorted list of IDs
 /// # Arguments
 /// * reader: RecordReader for a name-sorted SAM/BAM file. Sort with `samtools sort -n`
+/// * hts: Path to the SAM/BAM file, for error messages
 /// * ids: A name-sorted file containing IDs to filter out (or keep) from the SAM/BAM file. Sort with `sort ids.in > ids.filtered.out`.
-/// * out: Output file to write filtered reads to
+/// * writer: RecordWriter to write filtered reads to
+/// * out: Path to the output file (`-` for STDOUT), for error messages
 /// * keep: Boolean to keep the reads matching IDs in `ids` (`true`) or discard them (`false`)
 pub fn filter<T: RecordReader, S: RecordWriter>(
     reader: &mut T,
+    hts: &Path,
     ids: &Path,
     writer: &mut S,
+    out: &Path,
     keep: bool,
-) {
+) -> Result<(), BjtError> {
+    let read_err = |record: u64| {
+        move |e: io::Error| BjtError::InvalidRecord {
+            path: hts.to_path_buf(),
+            record,
+            reason: e.to_string(),
+        }
+    };
+    let write_err = |source: io::Error| BjtError::Write {
+        path: out.to_path_buf(),
+        source,
+    };
+
     // open IDs to filter
     let mut id_file = match File::open(ids) {
         Ok(f) => BufReader::new(f).lines(),
-        Err(_) => panic!("IDs file {} could not be opened.", ids.display()),
+        Err(source) => {
+            return Err(BjtError::Read {
+                path: ids.to_path_buf(),
+                source,
+            })
+        }
     };
+    let mut n_ids = 1;
 
     // first ID in the ID file
     let mut prev_id = match id_file.next() {
         Some(Ok(id)) => id.to_lowercase(),
-        Some(Err(_)) => panic!("Error parsing first line in ID file {}.", ids.display()),
-        None => panic!("No IDs in ID file {}. No need to filter", ids.display()),
+        Some(Err(e)) => {
+            return Err(BjtError::InvalidLine {
+                path: ids.to_path_buf(),
+                line: n_ids,
+                reason: e.to_string(),
+            })
+        }
+        None => {
+            return Err(BjtError::EmptyFile {
+                path: ids.to_path_buf(),
+                reason: "There are no IDs to filter.",
+            })
+        }
     };
     let mut cur_id = prev_id.clone();
 
     // name of the first record in the SAM/BAM file
+    let mut n_records = 1;
     let mut prev_record = Record::new();
-    match reader.read_into(&mut prev_record) {
-        // no problem if reading the first read
-        Ok(true) => {}
-        Ok(false) => panic!("No reads in HTS file"),
-        Err(_) => panic!("Error parsing first read in HTS file"),
-    };
-    let mut prev_record_name = from_utf8(&prev_record.name()).unwrap().to_lowercase();
+    if !reader
+        .read_into(&mut prev_record)
+        .map_err(read_err(n_records))?
+    {
+        return Err(BjtError::EmptyFile {
+            path: hts.to_path_buf(),
+            reason: "There are no reads to filter.",
+        });
+    }
+    let mut prev_record_name = String::from_utf8_lossy(prev_record.name()).to_lowercase();
     let mut cur_record = prev_record.clone();
     let mut cur_record_name = prev_record_name.clone();
 
@@ -83,47 +118,62 @@ pub fn filter<T: RecordReader, S: RecordWriter>(
 
     // step through records and IDs
     loop {
-        // panic if IDs aren't sorted
-        if &cur_id < &prev_id {
-            panic!("IDs aren't sorted. Please sort with `sort ids.in > ids.filtered.out`")
+        // stop if IDs aren't sorted
+        if cur_id < prev_id {
+            return Err(BjtError::Unsorted {
+                path: ids.to_path_buf(),
+                record: n_ids,
+                hint: "Please sort with `sort ids.in > ids.filtered.out`.",
+            });
         }
-        // panic if SAM/BAM isn't name-sorted
-        if &cur_record_name < &prev_record_name {
-            panic!("HTS file isn't sorted. Please sort with `samtools sort -n`")
+        // stop if SAM/BAM isn't name-sorted
+        if cur_record_name < prev_record_name {
+            return Err(BjtError::Unsorted {
+                path: hts.to_path_buf(),
+                record: n_records,
+                hint: "Please sort with `samtools sort -n`.",
+            });
         }
 
         // decide what to do with cur_record, depending on how it relates to cur_id
         // write or discard record if the IDs are ahead of the reads
-        if &cur_record_name < &cur_id {
+        if cur_record_name < cur_id {
             if !keep {
-                writer.write(&cur_record).unwrap();
+                writer.write(&cur_record).map_err(write_err)?;
             }
             // update the records
             prev_record_name = cur_record_name;
+            n_records += 1;
             // check if there is a subsequent record in the SAM/BAM
-            match reader.read_into(&mut cur_record) {
-                Ok(true) => {}
-                // if no more records in SAM/BAM, close the writer and exit the loop
-                Ok(false) => {
-                    writer.finish().unwrap();
-                    break;
-                }
-                Err(_) => panic!("Error parsing record in HTS file"),
+            // if no more records in SAM/BAM, close the writer and exit the loop
+            if !reader
+                .read_into(&mut cur_record)
+                .map_err(read_err(n_records))?
+            {
+                writer.finish().map_err(write_err)?;
+                break;
             }
-            cur_record_name = from_utf8(&cur_record.name()).unwrap().to_lowercase();
+            cur_record_name = String::from_utf8_lossy(cur_record.name()).to_lowercase();
         // update the IDs to catch up to the records
         } else if cur_record_name > cur_id {
+            n_ids += 1;
             match id_file.next() {
                 // update the IDs
                 Some(Ok(id)) => {
                     prev_id = cur_id;
                     cur_id = id.to_lowercase();
                 }
-                Some(Err(_)) => panic!("Error parsing ID in ID file {}.", ids.display()),
+                Some(Err(e)) => {
+                    return Err(BjtError::InvalidLine {
+                        path: ids.to_path_buf(),
+                        line: n_ids,
+                        reason: e.to_string(),
+                    })
+                }
                 // if no more IDs, close this reader and deal with the remaining reads outside the loop
                 None => {
                     // write the current read, if required, then deal with all the future ones
-                    writer.write(&cur_record).unwrap();
+                    writer.write(&cur_record).map_err(write_err)?;
                     deal_with_remaining_reads = true;
                     break;
                 }
@@ -132,20 +182,20 @@ pub fn filter<T: RecordReader, S: RecordWriter>(
             // don't purge this ID yet, just move onto the next record
             // there may be other records that match this ID (e.g. mate or non-unique alignment)
             if keep {
-                writer.write(&cur_record).unwrap();
+                writer.write(&cur_record).map_err(write_err)?;
             }
             prev_record_name = cur_record_name;
-            match reader.read_into(&mut cur_record) {
-                // if there is a subsequent records in the SAM/BAM
-                Ok(true) => {
-                    cur_record_name = from_utf8(&cur_record.name()).unwrap().to_lowercase();
-                }
-                // if no more reads in SAM/BAM, close the writer and exit the loop
-                Ok(false) => {
-                    writer.finish().unwrap();
-                    break;
-                }
-                Err(_) => panic!("Error parsing record in HTS file"),
+            n_records += 1;
+            // if there is a subsequent records in the SAM/BAM
+            if reader
+                .read_into(&mut cur_record)
+                .map_err(read_err(n_records))?
+            {
+                cur_record_name = String::from_utf8_lossy(cur_record.name()).to_lowercase();
+            // if no more reads in SAM/BAM, close the writer and exit the loop
+            } else {
+                writer.finish().map_err(write_err)?;
+                break;
             }
         }
     }
@@ -154,9 +204,12 @@ pub fn filter<T: RecordReader, S: RecordWriter>(
     // write them without comparing against IDs
     if deal_with_remaining_reads && !keep {
         for read in reader {
-            let record = read.unwrap();
-            writer.write(&record).unwrap();
+            n_records += 1;
+            let record = read.map_err(read_err(n_records))?;
+            writer.write(&record).map_err(write_err)?;
         }
-        writer.finish().unwrap();
+        writer.finish().map_err(write_err)?;
     }
+
+    Ok(())
 }
@@ -3,21 +3,46 @@
 use super::reader::SamBamCramReader;
 use crate::{
     cli::CliOpt,
-    record::header::{ILLUMINA_SEPARATOR_ASCII_CODE, RNAME_SEPARATOR_ASCII_CODE},
-    record::{error::RecordError, header::RecordName, stats::RecordStats},
-    utils::{formats::OutputFormat, Align, Hts, HtsFile},
+    error::BjtError,
+    record::{
+        grammar::NameGrammar,
+        stats::{
+            is_zero, merge_counts, serialize_length_distribution, MergeStats, NameOpts,
+            RecordStats, SraRuns,
+        },
+    },
+    utils::{
+        batch::{expand_paths, join_keys, BatchReport, SummaryRow},
+        decompress::ThreadOpts,
+        formats::{serialize_sorted_map, OutputFormat},
+        progress::Progress,
+        stats_file::PartialStats,
+        Align, Hts, HtsFile,
+    },
 };
+use anyhow::{bail, Context};
 use bam::{BamReader, SamReader};
 use clap::Parser;
-use std::path::PathBuf;
-use std::{collections::HashMap, io};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    io::{self, BufReader},
+};
 
 /// CLI options for getting info from an HTS file
 #[derive(Debug, Parser)]
 pub struct SamBamCramInfoOpts {
-    /// Get info about this HTS file
-    #[clap(name = "HTS")]
-    hts_path: PathBuf,
+    /// Get info about these HTS files (glob patterns are expanded).
+    /// Multiple files are summarized in a table with one row per file.
+    /// Use `-` to read from standard input.
+    #[clap(name = "HTS", required = true)]
+    hts_paths: Vec<PathBuf>,
+
+    /// Format of the input, instead of detecting it from the file extension (required for standard input)
+    #[clap(long, value_name = "FORMAT")]
+    input_format: Option<Hts>,
 
     /// Count the total number of records
     #[clap(short, long)]
@@ -31,34 +56,82 @@ pub struct SamBamCramInfoOpts {
     #[clap(short, long)]
     instruments: bool,
 
-    /// Track flow cell IDs
+    /// Track flow cell IDs and lanes
     #[clap(short = 'F', long)]
     flow_cell_ids: bool,
 
+    /// Track SRA run accessions and spot numbers, and check the lengths in SRA read names
+    #[clap(long)]
+    sra: bool,
+
+    /// Parse read names with this regular expression before trying the built-in formats.
+    /// Named capture groups `instrument`, `flowcell`, `lane`, and `barcode` are recognized,
+    /// and the instruments or flow cells the expression captures are tracked, as if `-i` or `-F` were given.
+    /// Can be given multiple times, and the first matching expression is used.
+    #[clap(long = "name-regex", value_name = "REGEX")]
+    name_grammars: Vec<NameGrammar>,
+
     /// Output format to return statistics in
     #[clap(short = 'f', long, default_value = "human")]
     format: OutputFormat,
 
+    /// Also save the raw statistics to this file, to be combined with others by `bjt info merge`.
+    /// For multiple files, the statistics of all the files combined are saved.
+    #[clap(long, value_name = "FILE")]
+    save_stats: Option<PathBuf>,
+
     /// Keep statistics on the first N records
     #[clap(short = 'N', long = "max-records", value_name = "N")]
     n_max_records: Option<u64>,
+
+    /// Process N files in parallel [default: the number of CPUs]
+    #[clap(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    #[clap(flatten)]
+    threads: ThreadOpts,
 }
 
 impl SamBamCramInfoOpts {
-    /// Get information and statistics about a desired FASTQ file
-    fn calc_info(&self, hts: HtsFile) -> SamBamCramStats {
+    /// Which pieces of information to track from record names
+    fn name_opts(&self) -> NameOpts<'_> {
+        NameOpts {
+            instruments: self.instruments,
+            flow_cells: self.flow_cell_ids,
+            sra: self.sra,
+            grammars: &self.name_grammars,
+            ..Default::default()
+        }
+        .with_grammar_groups()
+    }
+
+    /// Get information and statistics about a desired SAM/BAM/CRAM file
+    fn calc_info(&self, path: &Path) -> anyhow::Result<SamBamCramStats> {
+        let hts = HtsFile::with_format(path, self.input_format)?;
+        debug!("Reading {} as {}", path.display(), hts.filetype());
         let mut stats = SamBamCramStats::new();
+        let name_opts = self.name_opts();
+        let progress = Progress::reading(&hts);
         let reader_wrapper = match hts.filetype() {
             Hts::Align(Align::Sam) => SamBamCramReader::Sam(
-                SamReader::from_path(hts.path()).expect("Error opening SAM file."),
+                SamReader::from_stream(BufReader::new(progress.track(hts.reader()?)))
+                    .with_context(|| format!("Error opening SAM file {}", path.display()))?,
             ),
             Hts::Align(Align::Bam) => SamBamCramReader::Bam(
-                BamReader::from_path(hts.path(), 3).expect("Error opening BAM file."),
+                BamReader::from_stream(
+                    progress.track(hts.reader()?),
+                    self.threads.n_threads().saturating_sub(1),
+                )
+                .with_context(|| format!("Error opening BAM file {}", path.display()))?,
             ),
-            Hts::Align(Align::Cram) => {
-                todo!()
-            }
-            _ => todo!(),
+            Hts::Align(Align::Cram) => bail!(BjtError::UnsupportedFormat {
+                path: path.to_path_buf(),
+                format: "CRAM",
+            }),
+            _ => bail!(BjtError::WrongFormat {
+                path: path.to_path_buf(),
+                expected: "SAM/BAM/CRAM",
+            }),
         };
 
         match reader_wrapper {
@@ -66,11 +139,13 @@ impl SamBamCramInfoOpts {
                 if let Some(n_max) = self.n_max_records {
                     // check if the max capacity has been hit
                     while let (true, Some(record)) = (stats.n_records() < n_max, reader.next()) {
-                        stats.process_record(&record, self);
+                        stats.process_record(&record, self, &name_opts);
+                        progress.record();
                     }
                 } else {
                     while let Some(record) = reader.next() {
-                        stats.process_record(&record, self);
+                        stats.process_record(&record, self, &name_opts);
+                        progress.record();
                     }
                 }
             }
@@ -78,35 +153,54 @@ impl SamBamCramInfoOpts {
                 if let Some(n_max) = self.n_max_records {
                     // check if the max capacity has been hit
                     while let (true, Some(record)) = (stats.n_records() < n_max, reader.next()) {
-                        stats.process_record(&record, self);
+                        stats.process_record(&record, self, &name_opts);
+                        progress.record();
                     }
                 } else {
                     while let Some(record) = reader.next() {
-                        stats.process_record(&record, self);
+                        stats.process_record(&record, self, &name_opts);
+                        progress.record();
                     }
                 }
             }
-            SamBamCramReader::Cram => {
-                todo!()
-            }
+            SamBamCramReader::Cram => bail!(BjtError::UnsupportedFormat {
+                path: path.to_path_buf(),
+                format: "CRAM",
+            }),
         }
+        progress.finish(&hts);
 
-        stats
+        Ok(stats)
     }
 }
 
 impl CliOpt for SamBamCramInfoOpts {
     fn exec(&self) -> anyhow::Result<()> {
-        let hts = HtsFile::new(&self.hts_path);
-        let stats = self.calc_info(hts);
-        println!("{:#?}", stats);
+        let paths = expand_paths(&self.hts_paths)?;
+
+        // a single file gets a detailed report
+        if let [path] = paths.as_slice() {
+            let stats = self.calc_info(path)?;
+            if let Some(stats_path) = &self.save_stats {
+                PartialStats::Bam(Box::new((&stats).into())).save(stats_path)?;
+            }
+            print!("{}", self.format.render(&stats)?);
 
-        Ok(())
+            return Ok(());
+        }
+
+        let batch = BatchReport::process(&paths, self.jobs, |path| self.calc_info(path))?;
+        if let (Some(stats_path), Some(total)) = (&self.save_stats, batch.total()) {
+            PartialStats::Bam(Box::new(total.into())).save(stats_path)?;
+        }
+        print!("{}", batch.render(&self.format)?);
+
+        Ok(batch.check()?)
     }
 }
 
 /// Important statistics from a SAM/BAM/CRAM file.
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct SamBamCramStats {
     /// Number of valid records.
     valid_records: u64,
@@ -118,41 +212,142 @@ pub struct SamBamCramStats {
     bases: u64,
 
     /// Length distribution of records
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_length_distribution"
+    )]
     lengths: HashMap<u64, u64>,
 
     /// Sequencing instruments
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
     instruments: HashMap<String, u64>,
 
     /// Flow cell IDs
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
     flow_cell_ids: HashMap<String, u64>,
 
+    /// Flow cell lanes, as `flowcell:lane`
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    lanes: HashMap<String, u64>,
+
+    /// Runs and spots from SRA read names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sra_runs: Option<SraRuns>,
+
+    /// Number of records whose name format couldn't be determined
+    #[serde(skip_serializing_if = "is_zero")]
+    unrecognized_names: u64,
+
     /// How deep the coverage is from these records.
+    #[serde(skip)]
     genome_depth: (),
 
     /// What amount of the genome is supported by these records.
+    #[serde(skip)]
     genome_support: (),
 }
 
-impl SamBamCramStats {
-    /// Process an Illumina (Casava >= v1.8) formatted FASTQ record
-    fn process_illumina_split_record(&mut self, rname: &[u8], opts: &SamBamCramInfoOpts) {
-        // Illumina Casava >= v1.8 format
-        let mut id_splits = rname.split(|x| *x == ILLUMINA_SEPARATOR_ASCII_CODE);
+/// Raw statistics from a SAM/BAM/CRAM file, as saved in a stats file
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SamBamCramStatsState {
+    valid_records: u64,
+    invalid_records: u64,
+    bases: u64,
+    lengths: HashMap<u64, u64>,
+    instruments: HashMap<String, u64>,
+    flow_cell_ids: HashMap<String, u64>,
+    lanes: HashMap<String, u64>,
+    sra_runs: Option<SraRuns>,
+    unrecognized_names: u64,
+}
+
+impl From<&SamBamCramStats> for SamBamCramStatsState {
+    fn from(stats: &SamBamCramStats) -> Self {
+        Self {
+            valid_records: stats.valid_records,
+            invalid_records: stats.invalid_records,
+            bases: stats.bases,
+            lengths: stats.lengths.clone(),
+            instruments: stats.instruments.clone(),
+            flow_cell_ids: stats.flow_cell_ids.clone(),
+            lanes: stats.lanes.clone(),
+            sra_runs: stats.sra_runs.clone(),
+            unrecognized_names: stats.unrecognized_names,
+        }
+    }
+}
+
+impl From<SamBamCramStatsState> for SamBamCramStats {
+    fn from(state: SamBamCramStatsState) -> Self {
+        Self {
+            valid_records: state.valid_records,
+            invalid_records: state.invalid_records,
+            bases: state.bases,
+            lengths: state.lengths,
+            instruments: state.instruments,
+            flow_cell_ids: state.flow_cell_ids,
+            lanes: state.lanes,
+            sra_runs: state.sra_runs,
+            unrecognized_names: state.unrecognized_names,
+            genome_depth: (),
+            genome_support: (),
+        }
+    }
+}
 
-        // instrument name
-        let inst = id_splits.next();
-        if opts.instruments {
-            self.process_illumina_instrument(inst);
+impl MergeStats for SamBamCramStats {
+    fn merge(&mut self, other: &Self) {
+        self.valid_records += other.valid_records;
+        self.invalid_records += other.invalid_records;
+        self.bases += other.bases;
+        merge_counts(&mut self.lengths, &other.lengths);
+        merge_counts(&mut self.instruments, &other.instruments);
+        merge_counts(&mut self.flow_cell_ids, &other.flow_cell_ids);
+        merge_counts(&mut self.lanes, &other.lanes);
+        self.unrecognized_names += other.unrecognized_names;
+        match (self.sra_runs.as_mut(), &other.sra_runs) {
+            (Some(runs), Some(other_runs)) => runs.merge(other_runs),
+            (None, other_runs) => self.sra_runs = other_runs.clone(),
+            (Some(_), None) => {}
         }
+    }
+}
 
-        // run ID
-        let _run_id = id_splits.next();
+impl SummaryRow for SamBamCramStats {
+    fn summary_row(&self) -> Vec<(&'static str, String)> {
+        let mut row = vec![
+            ("valid_records", self.valid_records.to_string()),
+            ("invalid_records", self.invalid_records.to_string()),
+            ("bases", self.bases.to_string()),
+        ];
 
-        // flow cell ID
-        let fcid = id_splits.next();
-        if opts.flow_cell_ids {
-            self.process_illumina_flowcell(fcid);
+        if let Some(summary) = self.length_summary() {
+            row.extend(summary.summary_row());
+        }
+        if !self.instruments.is_empty() {
+            row.push(("instruments", join_keys(&self.instruments)));
+        }
+        if !self.flow_cell_ids.is_empty() {
+            row.push(("flow_cell_ids", join_keys(&self.flow_cell_ids)));
         }
+        if let Some(sra) = &self.sra_runs {
+            row.push(("sra_runs", join_keys(&sra.runs)));
+            row.push(("sra_length_mismatches", sra.length_mismatches.to_string()));
+        }
+        if self.unrecognized_names > 0 {
+            row.push(("unrecognized_names", self.unrecognized_names.to_string()));
+        }
+
+        row
     }
 }
 
@@ -170,6 +365,9 @@ impl<'a> RecordStats<'a> for SamBamCramStats {
             lengths: HashMap::new(),
             instruments: HashMap::new(),
             flow_cell_ids: HashMap::new(),
+            lanes: HashMap::new(),
+            sra_runs: None,
+            unrecognized_names: 0,
             genome_depth: (),
             genome_support: (),
         }
@@ -183,6 +381,10 @@ impl<'a> RecordStats<'a> for SamBamCramStats {
         self.invalid_records
     }
 
+    fn lengths(&self) -> &HashMap<u64, u64> {
+        &self.lengths
+    }
+
     fn mut_lengths(&mut self) -> &mut HashMap<u64, u64> {
         &mut self.lengths
     }
@@ -195,8 +397,25 @@ impl<'a> RecordStats<'a> for SamBamCramStats {
         &mut self.instruments
     }
 
+    fn mut_lanes(&mut self) -> &mut HashMap<String, u64> {
+        &mut self.lanes
+    }
+
+    fn mut_sra_runs(&mut self) -> &mut SraRuns {
+        self.sra_runs.get_or_insert_with(SraRuns::new)
+    }
+
+    fn mut_unrecognized_names(&mut self) -> &mut u64 {
+        &mut self.unrecognized_names
+    }
+
     /// Process the statistics for a valid record
-    fn process_valid_record(&mut self, seq: &Self::Record, opts: &Self::InfoOpts) {
+    fn process_valid_record(
+        &mut self,
+        seq: &Self::Record,
+        opts: &Self::InfoOpts,
+        name_opts: &NameOpts,
+    ) {
         self.valid_records += 1;
 
         let seq_length: u64 = seq.query_len().try_into().unwrap();
@@ -206,18 +425,9 @@ impl<'a> RecordStats<'a> for SamBamCramStats {
             self.update_lengths(seq_length);
         }
 
-        if opts.flow_cell_ids || opts.instruments {
-            match RecordName::try_from(seq.name()) {
-                Ok(RecordName::CasavaV1_8) => {
-                    let mut splits = seq.name().split(|x| *x == RNAME_SEPARATOR_ASCII_CODE);
-                    let a = splits.next().unwrap();
-                    self.process_illumina_split_record(a, opts);
-                }
-                Ok(RecordName::SequenceReadArchive) => {
-                    self.process_sra_split_record();
-                }
-                Err(RecordError::UncertainRecordNameFormat) => todo!(),
-            }
+        if name_opts.any() {
+            // hard-clipped and secondary alignments may not hold the full sequence
+            self.process_name(seq.name(), None, name_opts);
         }
     }
 
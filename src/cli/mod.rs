@@ -2,17 +2,27 @@
 
 use crate::{
     align::info_stats::SamBamCramInfoOpts,
-    fastq::info_stats::FastqInfoOpts,
+    data::OrganizeOpts,
+    fasta::{
+        faidx::FaidxOpts, filter::FastaFilterOpts, genome::GenomeOpts, info_stats::FastaInfoOpts,
+    },
+    fastq::{filter::FastqFilterOpts, info_stats::FastqInfoOpts},
+    utils::stats_file::MergeStatsOpts,
     // align::{filter::SamBamCramFilterOpts, info_stats::SamBamCramInfoOpts},
-    // fastq::{filter::FastqFilterOpts, info_stats::FastqInfoOpts},
 };
 use clap::{Parser, Subcommand};
+use clap_verbosity_flag::{Verbosity, WarnLevel};
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
 pub struct Cli {
     #[clap(subcommand)]
     pub(crate) cmd: SubCmd,
+
+    /// Log more detail to STDERR with each `-v`, and show progress from the first `-v` onward.
+    /// Log less with each `-q`.
+    #[clap(flatten)]
+    pub(crate) verbose: Verbosity<WarnLevel>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -23,9 +33,15 @@ pub(crate) enum SubCmd {
     #[clap(subcommand)]
     Filter(FilterSubCmd),
 
+    /// Index a FASTA file and extract regions from it
+    Faidx(FaidxOpts),
+
+    /// Write the chrom.sizes file or sequence dictionary of a reference genome
+    Genome(GenomeOpts),
+
     /// Organize a batch of raw sequencing data
     #[clap(name = "org")]
-    Organize,
+    Organize(OrganizeOpts),
 }
 
 pub(crate) trait CliOpt {
@@ -39,7 +55,7 @@ pub(crate) trait CliOpt {
 pub(crate) enum InfoSubCmd {
     /// Get info about a FASTA file
     #[clap(visible_alias = "fa")]
-    Fasta,
+    Fasta(FastaInfoOpts),
 
     /// Get info about a FASTQ file
     #[clap(visible_alias = "fq")]
@@ -51,15 +67,19 @@ pub(crate) enum InfoSubCmd {
 
     /// Get info about a BED file
     Bed,
+
+    /// Combine statistics saved with `--save-stats` into a single report
+    Merge(MergeStatsOpts),
 }
 
 impl CliOpt for InfoSubCmd {
     fn exec(&self) -> anyhow::Result<()> {
         match self {
-            Self::Fasta => todo!(),
+            Self::Fasta(opts) => opts.exec(),
             Self::Fastq(opts) => opts.exec(),
             Self::Bam(opts) => opts.exec(),
             Self::Bed => todo!(),
+            Self::Merge(opts) => opts.exec(),
         }
     }
 }
@@ -69,16 +89,15 @@ impl CliOpt for InfoSubCmd {
 pub(crate) enum FilterSubCmd {
     /// Filter a FASTA file
     #[clap(visible_alias = "fa")]
-    Fasta,
+    Fasta(FastaFilterOpts),
 
-    // /// Filter a FASTQ file
-    // #[clap(visible_alias = "fq")]
-    // Fastq(FastqFilterOpts),
+    /// Filter a FASTQ file
+    #[clap(visible_alias = "fq")]
+    Fastq(FastqFilterOpts),
 
     // /// Filter a SAM/BAM/CRAM file
     // #[clap(visible_aliases = &["sam", "cram"])]
     // Bam(SamBamCramFilterOpts),
-
     /// Filter a BED file
     Bed,
 }
@@ -86,8 +105,8 @@ pub(crate) enum FilterSubCmd {
 impl CliOpt for FilterSubCmd {
     fn exec(&self) -> anyhow::Result<()> {
         match self {
-            Self::Fasta => todo!(),
-            // Self::Fastq(opts) => opts.exec(),
+            Self::Fasta(opts) => opts.exec(),
+            Self::Fastq(opts) => opts.exec(),
             // Self::Bam(opts) => opts.exec(),
             Self::Bed => todo!(),
         }
@@ -2,7 +2,9 @@
 //! Functions and methods to handle folders of raw sequencing data, right off the sequencer.
 
 use chrono::Local;
+use clap::Parser;
 use indoc::indoc;
+use log::{debug, info};
 use regex::Regex;
 use std::collections::HashMap;
 use std::fmt;
@@ -11,7 +13,11 @@ use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-use crate::utils::{detect_filetype, Fastx, Hts, HtsFile};
+use crate::{
+    cli::CliOpt,
+    error::BjtError,
+    utils::{detect_filetype, progress::Progress, Fastx, Hts, HtsFile},
+};
 
 type Date = chrono::NaiveDate;
 
@@ -26,6 +32,24 @@ const RESERVED_FILENAMES: [&'static str; 5] = [
     "setup.log",
 ];
 
+/// CLI options for organizing a batch of raw sequencing data
+#[derive(Debug, Parser)]
+pub(crate) struct OrganizeOpts {
+    /// Directory to organize
+    #[clap(name = "DIR")]
+    dir: PathBuf,
+
+    /// Only show what steps are going to be performed
+    #[clap(short = 'n', long)]
+    dryrun: bool,
+}
+
+impl CliOpt for OrganizeOpts {
+    fn exec(&self) -> anyhow::Result<()> {
+        Ok(organize(&self.dir, self.dryrun)?)
+    }
+}
+
 #[derive(Debug)]
 struct SeqDir {
     path: PathBuf,
@@ -39,52 +63,48 @@ struct SeqDir {
 
 impl SeqDir {
     /// Construct a new SeqDir object from a path
-    pub fn new(path: &Path) -> SeqDir {
+    pub fn new(path: &Path) -> Result<SeqDir, BjtError> {
         let dir_regex: Regex = Regex::new(
             r"^([0-9]{2})(0?[1-9]|1[012])(0[1-9]|[12]\d|3[01])_(\w{3,})_(\d{4})_(A|B)(\w{9})(.*)?",
         )
         .unwrap();
         // if given a relative path, force it to an absolute path
-        let dir_stem = match path.is_relative() {
-            true => String::from(
-                path.canonicalize()
-                    .unwrap()
-                    .file_stem()
-                    .unwrap()
-                    .to_str()
-                    .unwrap(),
-            ),
-            false => String::from(path.file_stem().unwrap().to_str().unwrap()),
-        };
+        let abs_path = path.canonicalize().map_err(|source| BjtError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let dir_stem = abs_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
         // try extracting flowcell information from directory name
-        let dir_stem_capture_attempt = dir_regex.captures(&dir_stem);
-        match dir_stem_capture_attempt {
-            Some(cap) => {
-                // extract sequencing date
-                let date = Date::parse_from_str(
-                    &format!(
-                        "{}{}{}",
-                        cap.get(1).unwrap().as_str(),
-                        cap.get(2).unwrap().as_str(),
-                        cap.get(3).unwrap().as_str()
-                    ),
-                    "%y%m%d",
-                )
-                .unwrap();
-                let instrument = cap.get(4).unwrap().as_str();
-                let run = cap.get(5).unwrap().as_str().parse::<u16>().unwrap();
-                let pos = cap.get(6).unwrap().as_str().parse::<char>().unwrap();
-                let flowcell = cap.get(7).unwrap().as_str();
-                let description = cap.get(8).unwrap().as_str();
+        let group =
+            |cap: &regex::Captures, i: usize| cap.get(i).map_or("", |m| m.as_str()).to_string();
+        let dir_stem_capture_attempt = dir_regex.captures(&dir_stem).and_then(|cap| {
+            // extract sequencing date, which may not exist even if it matches the pattern
+            let date = Date::parse_from_str(
+                &format!("{}{}{}", group(&cap, 1), group(&cap, 2), group(&cap, 3)),
+                "%y%m%d",
+            )
+            .ok()?;
+            Some((date, cap))
+        });
+        let seq_dir = match dir_stem_capture_attempt {
+            Some((date, cap)) => {
+                let instrument = group(&cap, 4);
+                let run = group(&cap, 5).parse::<u16>().unwrap_or_default();
+                let pos = group(&cap, 6).parse::<char>().unwrap_or('?');
+                let flowcell = group(&cap, 7);
+                let description = group(&cap, 8);
 
                 SeqDir {
                     path: path.to_path_buf(),
                     date: date,
-                    instrument: String::from(instrument),
-                    run: run,
+                    instrument,
+                    run,
                     position: pos,
-                    flowcell: String::from(flowcell),
-                    description: String::from(description),
+                    flowcell,
+                    description,
                 }
             }
             // if no regex match, return the default SeqDir initialization
@@ -97,7 +117,9 @@ impl SeqDir {
                 flowcell: String::from(""),
                 description: String::from(""),
             },
-        }
+        };
+
+        Ok(seq_dir)
     }
 
     /// Path to SeqDir
@@ -136,59 +158,52 @@ impl SeqDir {
     }
 
     /// Create the reserved files, if they are missing from the SeqDir
-    pub fn create_reserved_files(&self, verbose: bool, dryrun: bool) {
+    pub fn create_reserved_files(&self, dryrun: bool) -> Result<(), BjtError> {
         // create non-existent reserved files
-        if verbose {
-            println!("Creating files...");
-        }
         for f in &RESERVED_FILENAMES {
             let p = self.path().join(Path::new(&f));
             if !p.as_path().exists() {
-                if verbose {
-                    println!("  {}", f);
-                }
+                report(dryrun, format_args!("Creating file {}", p.display()));
                 if !dryrun {
-                    create_reserved_file(self, f);
+                    create_reserved_file(self, f)?;
                 }
             }
         }
+
+        Ok(())
     }
 
     /// Create the reserved directories, if they are missing from the SeqDir
-    pub fn create_reserved_dirs(&self, verbose: bool, dryrun: bool) {
+    pub fn create_reserved_dirs(&self, dryrun: bool) -> Result<(), BjtError> {
         // create non-existent reserved directories
-        if verbose {
-            println!("Creating directories...");
-        }
         for d in &RESERVED_DIRNAMES {
             let p = self.path().join(Path::new(&d));
             if !p.as_path().exists() {
-                if verbose {
-                    println!("  {}", d);
-                }
+                report(dryrun, format_args!("Creating directory {}", p.display()));
                 if !dryrun {
-                    create_reserved_dir(p);
+                    create_reserved_dir(p)?;
                 }
             }
         }
+
+        Ok(())
     }
 
     /// Relocate HTS files into the appropriate reserved directories
-    pub fn relocate_hts_files(&self, verbose: bool, dryrun: bool) {
+    pub fn relocate_hts_files(&self, dryrun: bool) -> Result<(), BjtError> {
         // find and relocate FASTQs, if necessary
-        if verbose {
-            println!("Moving sequencing files...");
-        }
-
         // walk over all HTS files in the folder
-        for hts in WalkDir::new(self.path())
+        let hts_files: Vec<HtsFile> = WalkDir::new(self.path())
             .into_iter()
             .filter_map(|e| e.ok()) // only consider correct entries
             .filter(|e| e.path().is_file()) // only consider files
             .filter(|e| detect_filetype(e.path()).is_some()) // only consider HtsFiles
-            .map(|e| HtsFile::new(e.path()))
-        // convert to HtsFile object
-        {
+            .filter_map(|e| HtsFile::new(e.path()).ok()) // convert to HtsFile object
+            .collect();
+        debug!("Found {} sequencing files to move", hts_files.len());
+
+        let progress = Progress::steps("Moving files", hts_files.len() as u64);
+        for hts in hts_files {
             let destdir: PathBuf;
             // find out where the file needs to go
             match hts.filetype() {
@@ -208,20 +223,24 @@ impl SeqDir {
                     destdir = self.path().join(Path::new("Reports"));
                 }
             }
-            if !dryrun {
-                mv_to_dir(hts.path(), destdir.as_path());
-            }
-            if verbose {
-                println!(
-                    "  {} -> {}",
+            report(
+                dryrun,
+                format_args!(
+                    "Moving {} -> {}",
                     hts.path().display(),
                     destdir
                         .as_path()
-                        .join(hts.path().file_name().unwrap())
+                        .join(hts.path().file_name().unwrap_or_default())
                         .display()
-                );
+                ),
+            );
+            if !dryrun {
+                mv_to_dir(hts.path(), destdir.as_path())?;
             }
+            progress.step();
         }
+
+        Ok(())
     }
 }
 
@@ -245,67 +264,55 @@ impl fmt::Display for SeqSample {
     }
 }
 
-fn create_reserved_file(seq: &SeqDir, file: &str) {
+fn create_reserved_file(seq: &SeqDir, file: &str) -> Result<(), BjtError> {
     match file {
         "README.md" => create_readme(seq),
         "cluster.yaml" => create_cluster_yaml(seq),
         "Snakefile" => create_snakefile(seq),
         // exclude config.tsv, make that file separately when you reorganize the FASTQs
-        _ => return,
+        _ => Ok(()),
     }
 }
 
-fn create_reserved_dir(p: PathBuf) {
-    create_dir(p).expect("Error creating directory.");
-    // OpenOptions::new().write(true).create_new(true).open(outfile).expect("Error creating file.");
+fn create_reserved_dir(p: PathBuf) -> Result<(), BjtError> {
+    create_dir(&p).map_err(|source| BjtError::Write { path: p, source })
+}
+
+/// Create a file and write text to it
+fn write_text(p: &Path, text: &str) -> Result<(), BjtError> {
+    File::create(p)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|source| BjtError::Write {
+            path: p.to_path_buf(),
+            source,
+        })
 }
 
-fn create_readme(seq: &SeqDir) {
+fn create_readme(seq: &SeqDir) -> Result<(), BjtError> {
     let p = seq.path.join(Path::new("README.md"));
-    let mut file = match File::create(&p) {
-        // The `description` method of `io::Error` returns a string that
-        Err(why) => panic!("couldn't open {}: {}", p.display(), why.to_string()),
-        Ok(file) => file,
-    };
     let text = format!(
         "# {}\n\nFlowcell: {}\nDate Submitted: {}\nDate Received: {}\n\n## Description\n\n{}",
-        seq.path.file_stem().unwrap().to_str().unwrap(),
+        seq.path.file_stem().unwrap_or_default().to_string_lossy(),
         seq.flowcell,
         "",
         seq.date.format("%Y-%m-%d"),
         seq.description
     );
-    match file.write_all(text.as_bytes()) {
-        Err(why) => panic!("couldn't write to {}: {}", p.display(), why.to_string()),
-        Ok(_) => return,
-    }
+    write_text(&p, &text)
 }
 
-fn create_cluster_yaml(seq: &SeqDir) {
+fn create_cluster_yaml(seq: &SeqDir) -> Result<(), BjtError> {
     let p = seq.path.join(Path::new("cluster.yaml"));
-    let mut file = match File::create(&p) {
-        // The `description` method of `io::Error` returns a string that
-        Err(why) => panic!("couldn't open {}: {}", p.display(), why.to_string()),
-        Ok(file) => file,
-    };
     let text = indoc!(
         "__default__:
             params: '-p all --export=ALL -t 1-00:00:00 --mem 6G'
         "
     );
-    match file.write_all(text.as_bytes()) {
-        Err(why) => panic!("couldn't write to {}: {}", p.display(), why.to_string()),
-        Ok(_) => return,
-    }
+    write_text(&p, text)
 }
 
-fn create_snakefile(seq: &SeqDir) {
+fn create_snakefile(seq: &SeqDir) -> Result<(), BjtError> {
     let p = seq.path.join(Path::new("Snakefile"));
-    let mut file = match File::create(&p) {
-        // The `description` method of `io::Error` returns a string that
-        Err(why) => panic!("couldn't open {}: {}", p.display(), why.to_string()),
-        Ok(file) => file,
-    };
     let text = indoc!(
         "
         # =============================================================================
@@ -398,10 +405,7 @@ fn create_snakefile(seq: &SeqDir) {
                 'sambamba sort -t 8 --tmpdir . -p {input}'
         "
     );
-    match file.write_all(text.as_bytes()) {
-        Err(why) => panic!("couldn't write to {}: {}", p.display(), why.to_string()),
-        Ok(_) => return,
-    }
+    write_text(&p, text)
 }
 
 fn update_sample(s: &mut SeqSample, mate: String, lane: String) {
@@ -413,10 +417,10 @@ fn update_sample(s: &mut SeqSample, mate: String, lane: String) {
     }
 }
 
-fn create_config(sd: &SeqDir, dryrun: bool) {
+fn create_config(sd: &SeqDir, dryrun: bool) -> Result<(), BjtError> {
     // return if the config already exists
     if sd.path().join(Path::new("config.tsv")).exists() {
-        return;
+        return Ok(());
     }
     // sample name + optional sample index + optional lane + optional read mate/index number + optional _001 suffix
     // this produces the following captures:
@@ -436,22 +440,22 @@ fn create_config(sd: &SeqDir, dryrun: bool) {
         .filter_map(|e| e.ok()) // only consider correct entries
         .filter(|e| e.path().is_file()) // only consider files
         .filter(|e| detect_filetype(e.path()).is_some()) // only consider HtsFiles
-        .map(|e| HtsFile::new(e.path()))
+        .filter_map(|e| HtsFile::new(e.path()).ok())
     // convert to HtsFile object
     {
         // don't move directories, only assess FASTQs
         match hts.filetype() {
             Hts::Fastx(Fastx::Fastq) => {
-                let fname = hts.path().file_name().unwrap().to_str().unwrap();
-                let cap = fq_regex.captures(fname);
+                let fname = hts.path().file_name().unwrap_or_default().to_string_lossy();
+                let cap = fq_regex.captures(&fname);
                 // deal with the capture
                 match cap {
                     Some(c) => {
-                        let sample = c.get(1).unwrap().as_str().to_owned();
-                        let index = match c.get(2) {
-                            Some(i) => i.as_str().parse::<u8>().unwrap(),
-                            None => 0,
-                        };
+                        let sample = c.get(1).map_or("", |m| m.as_str()).to_owned();
+                        let index = c
+                            .get(2)
+                            .and_then(|i| i.as_str().parse::<u8>().ok())
+                            .unwrap_or(0);
                         let lane = match c.get(3) {
                             Some(l) => l.as_str().to_string(),
                             None => "".to_string(),
@@ -485,40 +489,56 @@ fn create_config(sd: &SeqDir, dryrun: bool) {
     // write sample information to config.tsv
     if !dryrun {
         let p = sd.path().join(Path::new("config.tsv"));
-        let mut file = match File::create(&p) {
-            // The `description` method of `io::Error` returns a string that
-            Err(why) => panic!("couldn't open {}: {}", p.display(), why.to_string()),
-            Ok(file) => file,
-        };
         let mut text = "Sample_ID\tSample_Index\tMates\tLanes\tDescription\n".to_string();
         // append new row for each sample
         for (_, s) in &samples {
             text.push_str(&format!("{}", s));
         }
-        match file.write_all(text.as_bytes()) {
-            Err(why) => panic!("couldn't write to {}: {}", p.display(), why.to_string()),
-            Ok(_) => return,
-        }
+        write_text(&p, &text)?;
     }
+
+    Ok(())
 }
 
-fn mv_to_dir(file: &Path, dir: &Path) {
-    rename(file, dir.join(file.file_name().unwrap())).expect("Failed to move file.");
+fn mv_to_dir(file: &Path, dir: &Path) -> Result<(), BjtError> {
+    let dest = dir.join(file.file_name().unwrap_or_default());
+    rename(file, &dest).map_err(|source| BjtError::Write { path: dest, source })
+}
+
+/// Report a step of organizing a directory
+///
+/// A dry run prints each step to STDOUT, since that is its only output.
+/// Otherwise, steps are logged, and only shown with `--verbose`.
+fn report(dryrun: bool, step: fmt::Arguments) {
+    if dryrun {
+        println!("{}", step);
+    } else {
+        info!("{}", step);
+    }
 }
 
 /// Organize a directory containing HTS data
-pub fn organize(indir: &Path, dryrun: bool, verbose: bool) {
-    let sd = SeqDir::new(indir);
-    sd.create_reserved_files(verbose, dryrun);
-    sd.create_reserved_dirs(verbose, dryrun);
-    sd.relocate_hts_files(verbose, dryrun);
+pub fn organize(indir: &Path, dryrun: bool) -> Result<(), BjtError> {
+    if !indir.exists() {
+        return Err(BjtError::MissingPath {
+            path: indir.to_path_buf(),
+        });
+    } else if !indir.is_dir() {
+        return Err(BjtError::NotADirectory {
+            path: indir.to_path_buf(),
+        });
+    }
+
+    let sd = SeqDir::new(indir)?;
+    info!("Organizing {}", sd.path().display());
+    sd.create_reserved_files(dryrun)?;
+    sd.create_reserved_dirs(dryrun)?;
+    sd.relocate_hts_files(dryrun)?;
 
     // extract sample information from FASTQs, reorganize
-    if verbose {
-        println!("Extracting sample information...");
-    }
-    create_config(&sd, dryrun);
-    if verbose {
-        println!("Done.");
-    }
+    debug!("Extracting sample information");
+    create_config(&sd, dryrun)?;
+    info!("Finished organizing {}", sd.path().display());
+
+    Ok(())
 }
@@ -0,0 +1,252 @@
+//! # Errors
+//!
+//! Errors that can stop any `bjt` command, and the exit code for each kind of error.
+//!
+//! | Exit code | Category                                                |
+//! | --------- | ------------------------------------------------------- |
+//! | 1         | Anything else                                           |
+//! | 2         | Invalid command line arguments (reported by `clap`)     |
+//! | 3         | Missing or unusable input paths                         |
+//! | 4         | Unknown, unsupported, or mismatched file formats        |
+//! | 5         | Malformed, empty, or unsorted file contents             |
+//! | 6         | Errors reading or writing files                         |
+//! | 7         | Some of the files in a batch could not be processed     |
+
+use crate::{
+    fasta::index::FaidxError,
+    fastq::filter::FastqFilterError,
+    record::{barcodes::SampleSheetError, error::RecordError},
+    utils::{
+        sniff::{Compression, SniffError},
+        stats_file::StatsFileError,
+    },
+};
+use std::{io, path::PathBuf, process::ExitCode};
+use thiserror::Error;
+
+/// Exit code for errors without a more specific category
+const EXIT_OTHER: u8 = 1;
+
+/// Exit code for missing or unusable input paths
+const EXIT_INPUT: u8 = 3;
+
+/// Exit code for unknown, unsupported, or mismatched file formats
+const EXIT_FORMAT: u8 = 4;
+
+/// Exit code for malformed, empty, or unsorted file contents
+const EXIT_CONTENT: u8 = 5;
+
+/// Exit code for errors reading or writing files
+const EXIT_IO: u8 = 6;
+
+/// Exit code for batches where some files could not be processed
+const EXIT_BATCH: u8 = 7;
+
+#[derive(Debug, Error)]
+pub enum BjtError {
+    #[error("File {path} does not exist. Please provide a path that exists.")]
+    MissingPath { path: PathBuf },
+
+    #[error("Path {path} is a directory. Please provide the path to a file.")]
+    NotAFile { path: PathBuf },
+
+    #[error("Path {path} is not a directory. Please provide the path to a directory.")]
+    NotADirectory { path: PathBuf },
+
+    #[error("No files match the pattern {pattern}.")]
+    NoGlobMatches { pattern: String },
+
+    #[error("Standard input can only be read once. Please provide `-` a single time.")]
+    StdinRepeated,
+
+    #[error(
+        "Cannot detect the format of standard input. Please provide it with `--input-format`."
+    )]
+    StdinFormatRequired,
+
+    #[error("Could not parse HTS file type from path {path}. Supported file extensions are (excluding compression): {extensions:?}. Otherwise, please provide the format with `--input-format`.")]
+    UnknownFormat {
+        path: PathBuf,
+        extensions: &'static [&'static str],
+    },
+
+    #[error("{path} is not a {expected} file.")]
+    WrongFormat {
+        path: PathBuf,
+        expected: &'static str,
+    },
+
+    #[error("{format} files are not yet supported ({path}).")]
+    UnsupportedFormat { path: PathBuf, format: &'static str },
+
+    #[error(
+        "Cannot write {compression} output to {path}. Please use gzip, bgzf, or zstd compression."
+    )]
+    UnsupportedCompression {
+        path: PathBuf,
+        compression: Compression,
+    },
+
+    #[error("Compression level {level} is too high for {compression} output to {path}. The highest level is {max}.")]
+    InvalidCompressionLevel {
+        path: PathBuf,
+        compression: Compression,
+        level: u32,
+        max: u32,
+    },
+
+    #[error(transparent)]
+    Sniff(#[from] SniffError),
+
+    #[error("{path} is empty. {reason}")]
+    EmptyFile { path: PathBuf, reason: &'static str },
+
+    #[error("Cannot parse record {record} of {path}. {reason}")]
+    InvalidRecord {
+        path: PathBuf,
+        record: u64,
+        reason: String,
+    },
+
+    #[error("Cannot parse line {line} of {path}. {reason}")]
+    InvalidLine {
+        path: PathBuf,
+        line: u64,
+        reason: String,
+    },
+
+    #[error("{path} is not sorted at record {record}. {hint}")]
+    Unsorted {
+        path: PathBuf,
+        record: u64,
+        hint: &'static str,
+    },
+
+    #[error("Cannot read {path}. {source}")]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("Cannot write {path}. {source}")]
+    Write { path: PathBuf, source: io::Error },
+
+    #[error("{failed} of {total} files could not be processed.")]
+    BatchFailed { failed: usize, total: usize },
+}
+
+impl BjtError {
+    /// Exit code for the category of this error
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::MissingPath { .. }
+            | Self::NotAFile { .. }
+            | Self::NotADirectory { .. }
+            | Self::NoGlobMatches { .. }
+            | Self::StdinRepeated => EXIT_INPUT,
+            Self::StdinFormatRequired
+            | Self::UnknownFormat { .. }
+            | Self::WrongFormat { .. }
+            | Self::UnsupportedFormat { .. }
+            | Self::UnsupportedCompression { .. }
+            | Self::InvalidCompressionLevel { .. } => EXIT_FORMAT,
+            Self::Sniff(e) => sniff_exit_code(e),
+            Self::EmptyFile { .. }
+            | Self::InvalidRecord { .. }
+            | Self::InvalidLine { .. }
+            | Self::Unsorted { .. } => EXIT_CONTENT,
+            Self::Read { .. } | Self::Write { .. } => EXIT_IO,
+            Self::BatchFailed { .. } => EXIT_BATCH,
+        }
+    }
+}
+
+/// Exit code for an error detecting a file's format
+fn sniff_exit_code(err: &SniffError) -> u8 {
+    match err {
+        SniffError::Io { .. } => EXIT_IO,
+        _ => EXIT_FORMAT,
+    }
+}
+
+/// Exit code for any error returned from a command, from the first error in its chain with a known category
+pub fn exit_code(err: &anyhow::Error) -> ExitCode {
+    let code = err
+        .chain()
+        .find_map(|e| {
+            if let Some(e) = e.downcast_ref::<BjtError>() {
+                Some(e.exit_code())
+            } else if let Some(e) = e.downcast_ref::<SniffError>() {
+                Some(sniff_exit_code(e))
+            } else if let Some(e) = e.downcast_ref::<StatsFileError>() {
+                match e {
+                    StatsFileError::Io { .. } => Some(EXIT_IO),
+                    StatsFileError::UnsupportedVersion { .. }
+                    | StatsFileError::Incompatible { .. } => Some(EXIT_FORMAT),
+                    StatsFileError::Parse { .. } | StatsFileError::Inconsistent { .. } => {
+                        Some(EXIT_CONTENT)
+                    }
+                }
+            } else if let Some(e) = e.downcast_ref::<FaidxError>() {
+                match e {
+                    FaidxError::Io { .. } => Some(EXIT_IO),
+                    FaidxError::Compressed { .. } => Some(EXIT_FORMAT),
+                    FaidxError::InvalidIndex { .. }
+                    | FaidxError::DifferentLineLengths { .. }
+                    | FaidxError::DuplicateName { .. }
+                    | FaidxError::MissingHeader { .. } => Some(EXIT_CONTENT),
+                    FaidxError::UnknownSequence { .. } | FaidxError::InvalidRegion { .. } => {
+                        Some(EXIT_OTHER)
+                    }
+                }
+            } else if e.is::<FastqFilterError>() {
+                Some(EXIT_OTHER)
+            } else if let Some(e) = e.downcast_ref::<SampleSheetError>() {
+                match e {
+                    SampleSheetError::Io(_) => Some(EXIT_IO),
+                    _ => Some(EXIT_CONTENT),
+                }
+            } else if e.is::<RecordError>() {
+                Some(EXIT_CONTENT)
+            } else if e.is::<io::Error>() {
+                Some(EXIT_IO)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(EXIT_OTHER);
+
+    ExitCode::from(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn categories_have_distinct_codes() {
+        let missing = BjtError::MissingPath {
+            path: PathBuf::from("a.fastq"),
+        };
+        let unsorted = BjtError::Unsorted {
+            path: PathBuf::from("a.fastq"),
+            record: 3,
+            hint: "",
+        };
+
+        assert_eq!(missing.exit_code(), EXIT_INPUT);
+        assert_eq!(unsorted.exit_code(), EXIT_CONTENT);
+        assert_eq!(BjtError::StdinFormatRequired.exit_code(), EXIT_FORMAT);
+    }
+
+    #[test]
+    fn exit_code_is_found_through_context() {
+        let err = Err::<(), _>(BjtError::StdinRepeated)
+            .context("Cannot process files")
+            .unwrap_err();
+
+        assert_eq!(exit_code(&err), ExitCode::from(EXIT_INPUT));
+        assert_eq!(
+            exit_code(&anyhow::anyhow!("other")),
+            ExitCode::from(EXIT_OTHER)
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Extract regions from an indexed FASTA file, like `samtools faidx`.
+
+use super::index::{build_and_save, FaiIndex, FaidxError, IndexedFasta};
+use crate::{
+    cli::CliOpt,
+    error::BjtError,
+    utils::{is_stdin, sink::OutputOpts},
+};
+use clap::Parser;
+use log::info;
+use std::{
+    fs::read_to_string,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Default number of bases on each line of extracted sequences
+const DEFAULT_LINE_LENGTH: usize = 60;
+
+/// Suffix added to the names of reverse-complemented sequences
+const REVERSE_COMPLEMENT_SUFFIX: &str = "/rc";
+
+/// CLI options for indexing a FASTA file and extracting regions from it
+#[derive(Debug, Parser)]
+pub(crate) struct FaidxOpts {
+    /// FASTA file to index and extract regions from (uncompressed or BGZF-compressed).
+    /// Its `.fai` index (and `.gzi` index, if compressed) is built if it doesn't exist yet.
+    #[clap(name = "FASTA")]
+    fasta_path: PathBuf,
+
+    /// Regions to extract, as `name`, `name:start`, or `name:start-end`, with 1-based, inclusive positions.
+    /// Without any regions, the indexes are (re)built and nothing is extracted.
+    #[clap(name = "REGIONS")]
+    regions: Vec<String>,
+
+    /// Also extract the regions listed in this file, one per line
+    #[clap(short, long, value_name = "FILE")]
+    region_file: Option<PathBuf>,
+
+    /// Reverse complement the extracted sequences, and add `/rc` to their names
+    #[clap(short = 'i', long)]
+    reverse_complement: bool,
+
+    /// Number of bases on each line of the extracted sequences (0 for a single line)
+    #[clap(short = 'n', long, value_name = "N", default_value_t = DEFAULT_LINE_LENGTH)]
+    line_length: usize,
+
+    #[clap(flatten)]
+    output: OutputOpts,
+}
+
+impl FaidxOpts {
+    /// All regions to extract, from the command line and the region file
+    fn regions(&self) -> anyhow::Result<Vec<String>> {
+        let mut regions = self.regions.clone();
+        if let Some(path) = &self.region_file {
+            let content = read_to_string(path).map_err(|source| BjtError::Read {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            regions.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        Ok(regions)
+    }
+}
+
+impl CliOpt for FaidxOpts {
+    fn exec(&self) -> anyhow::Result<()> {
+        let path = self.fasta_path.as_path();
+        if is_stdin(path) {
+            anyhow::bail!(
+                "Standard input can't be indexed. Please provide the path to a FASTA file."
+            );
+        } else if !path.exists() {
+            return Err(BjtError::MissingPath {
+                path: path.to_path_buf(),
+            }
+            .into());
+        }
+
+        let regions = self.regions()?;
+        if regions.is_empty() {
+            let (fai, _) = build_and_save(path)?;
+            info!(
+                "Indexed {} sequences in {}",
+                fai.records().len(),
+                path.display()
+            );
+            return Ok(());
+        }
+
+        let mut fasta = IndexedFasta::open(path)?;
+        // check every region before writing anything
+        let regions: Vec<Region> = regions
+            .iter()
+            .map(|r| Region::parse(r, fasta.index(), path))
+            .collect::<Result<_, _>>()?;
+
+        let mut sink = self.output.sink()?;
+        let out_path = self.output.path().unwrap_or(Path::new("-")).to_path_buf();
+        for region in &regions {
+            let mut seq = fasta.fetch(&region.name, region.start, region.end)?;
+            let mut label = region.label.clone();
+            if self.reverse_complement {
+                reverse_complement(&mut seq);
+                label.push_str(REVERSE_COMPLEMENT_SUFFIX);
+            }
+            write_record(&mut sink, &label, &seq, self.line_length).map_err(|source| {
+                BjtError::Write {
+                    path: out_path.clone(),
+                    source,
+                }
+            })?;
+        }
+        sink.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Part of a sequence to extract
+#[derive(Debug, PartialEq, Eq)]
+pub struct Region {
+    /// Region as it was given, used to name the extracted sequence
+    pub label: String,
+
+    /// Name of the sequence
+    pub name: String,
+
+    /// 0-based start position
+    pub start: u64,
+
+    /// 0-based end position, not included in the region
+    pub end: u64,
+}
+
+impl Region {
+    /// Parse a region given as `name`, `name:start`, or `name:start-end`, with 1-based, inclusive positions
+    ///
+    /// Sequence names can contain `:`, so a region that exactly matches a sequence name is always the whole sequence.
+    /// Regions may end past the end of their sequence, but not start past it.
+    pub fn parse(region: &str, index: &FaiIndex, path: &Path) -> Result<Self, FaidxError> {
+        let invalid = |reason| FaidxError::InvalidRegion {
+            region: region.to_string(),
+            reason,
+        };
+
+        if let Some(record) = index.get(region) {
+            return Ok(Self {
+                label: region.to_string(),
+                name: record.name.clone(),
+                start: 0,
+                end: record.length,
+            });
+        }
+
+        let Some((name, record, range)) = region
+            .rsplit_once(':')
+            .and_then(|(name, range)| index.get(name).map(|record| (name, record, range)))
+        else {
+            let name = region.rsplit_once(':').map_or(region, |(name, _)| name);
+            return Err(FaidxError::UnknownSequence {
+                path: path.to_path_buf(),
+                name: name.to_string(),
+            });
+        };
+
+        let position = |pos: &str| {
+            pos.replace(',', "")
+                .parse::<u64>()
+                .ok()
+                .filter(|p| *p > 0)
+                .ok_or_else(|| invalid("Positions must be whole numbers, starting from 1."))
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, "")) => (position(start)?, record.length),
+            Some((start, end)) => (position(start)?, position(end)?),
+            None => (position(range)?, record.length),
+        };
+        if end < start {
+            return Err(invalid("The end of a region can't come before its start."));
+        }
+        if start > record.length {
+            return Err(invalid("The region starts past the end of its sequence."));
+        }
+
+        Ok(Self {
+            label: region.to_string(),
+            name: name.to_string(),
+            start: start - 1,
+            end,
+        })
+    }
+}
+
+/// Reverse complement a sequence in place, keeping the case of each base
+///
+/// IUPAC ambiguity codes are complemented too, and anything else is left as-is.
+pub fn reverse_complement(seq: &mut [u8]) {
+    seq.reverse();
+    for base in seq.iter_mut() {
+        let complement = match base.to_ascii_uppercase() {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'T' | b'U' => b'A',
+            b'R' => b'Y',
+            b'Y' => b'R',
+            b'K' => b'M',
+            b'M' => b'K',
+            b'B' => b'V',
+            b'V' => b'B',
+            b'D' => b'H',
+            b'H' => b'D',
+            other => other,
+        };
+        *base = match base.is_ascii_lowercase() {
+            true => complement.to_ascii_lowercase(),
+            false => complement,
+        };
+    }
+}
+
+/// Write a FASTA record, wrapping its sequence into lines of `line_length` bases (or a single line, if 0)
+fn write_record<W: Write>(
+    writer: &mut W,
+    name: &str,
+    seq: &[u8],
+    line_length: usize,
+) -> std::io::Result<()> {
+    writeln!(writer, ">{}", name)?;
+    let line_length = match line_length {
+        0 => seq.len().max(1),
+        n => n,
+    };
+    for line in seq.chunks(line_length) {
+        writer.write_all(line)?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> FaiIndex {
+        FaiIndex::build(
+            &b">chr1\nACGTACGTAC\n>HLA-A*01:01\nACGT\n"[..],
+            Path::new("test.fa"),
+        )
+        .unwrap()
+    }
+
+    fn parse(region: &str) -> Result<(String, u64, u64), FaidxError> {
+        Region::parse(region, &index(), Path::new("test.fa")).map(|r| (r.name, r.start, r.end))
+    }
+
+    #[test]
+    fn regions_are_1_based_and_inclusive() {
+        assert_eq!(parse("chr1").unwrap(), ("chr1".to_string(), 0, 10));
+        assert_eq!(parse("chr1:2-4").unwrap(), ("chr1".to_string(), 1, 4));
+        assert_eq!(parse("chr1:5").unwrap(), ("chr1".to_string(), 4, 10));
+        assert_eq!(parse("chr1:5-").unwrap(), ("chr1".to_string(), 4, 10));
+        assert_eq!(
+            parse("chr1:1,0-2,000").unwrap(),
+            ("chr1".to_string(), 9, 2000)
+        );
+    }
+
+    #[test]
+    fn names_can_contain_colons() {
+        assert_eq!(
+            parse("HLA-A*01:01").unwrap(),
+            ("HLA-A*01:01".to_string(), 0, 4)
+        );
+        assert_eq!(
+            parse("HLA-A*01:01:2-3").unwrap(),
+            ("HLA-A*01:01".to_string(), 1, 3)
+        );
+    }
+
+    #[test]
+    fn invalid_regions_are_err() {
+        assert!(
+            matches!(parse("chrX:1-2"), Err(FaidxError::UnknownSequence { name, .. }) if name == "chrX")
+        );
+        assert!(matches!(
+            parse("chr1:0-2"),
+            Err(FaidxError::InvalidRegion { .. })
+        ));
+        assert!(matches!(
+            parse("chr1:5-2"),
+            Err(FaidxError::InvalidRegion { .. })
+        ));
+        assert!(matches!(
+            parse("chr1:20-30"),
+            Err(FaidxError::InvalidRegion { .. })
+        ));
+        assert!(matches!(
+            parse("chr1:11"),
+            Err(FaidxError::InvalidRegion { .. })
+        ));
+        assert!(matches!(
+            parse("chr1:a-b"),
+            Err(FaidxError::InvalidRegion { .. })
+        ));
+    }
+
+    #[test]
+    fn reverse_complement_keeps_case() {
+        let mut seq = b"ACGTNacgtnRYK".to_vec();
+        reverse_complement(&mut seq);
+
+        assert_eq!(seq, b"MRYnacgtNACGT");
+    }
+
+    #[test]
+    fn records_are_wrapped() {
+        let mut wrapped = vec![];
+        write_record(&mut wrapped, "chr1:1-7", b"ACGTACG", 3).unwrap();
+        let mut single = vec![];
+        write_record(&mut single, "chr1", b"ACGTACG", 0).unwrap();
+
+        assert_eq!(wrapped, b">chr1:1-7\nACG\nTAC\nG\n");
+        assert_eq!(single, b">chr1\nACGTACG\n");
+    }
+}
@@ -0,0 +1,287 @@
+//! Filter sequences from a FASTA file.
+
+use crate::{
+    cli::CliOpt,
+    error::BjtError,
+    record::filter::RecordFilter,
+    utils::{decompress::ThreadOpts, progress::Progress, sink::OutputOpts, Fastx, Hts, HtsFile},
+};
+use anyhow::{bail, Context};
+use clap::{ArgGroup, Parser};
+use log::info;
+use needletail::{parse_fastx_reader, parser::SequenceRecord};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+/// Options for filtering sequences from a FASTA file.
+#[derive(Debug, Parser)]
+#[clap(group(
+    ArgGroup::new("criteria")
+        .required(true)
+        .multiple(true)
+        .args(["regex", "id_list_path", "min_length", "max_length", "max_n_fraction"])
+))]
+pub struct FastaFilterOpts {
+    /// Filter this FASTA file (use `-` to read from standard input).
+    #[clap(name = "HTS")]
+    hts_path: PathBuf,
+
+    /// Format of the input, instead of detecting it from the file extension (required for standard input).
+    #[clap(long, value_name = "FORMAT")]
+    input_format: Option<Hts>,
+
+    /// Regular expression to match against the sequence names (up to the first whitespace in each header).
+    #[clap(short, long, conflicts_with = "id_list_path")]
+    regex: Option<Regex>,
+
+    /// Text file containing all sequence names to filter, one per line.
+    /// Only the first word of each line is used, so a `.fai` index or `chrom.sizes` file also works.
+    #[clap(
+        short = 'f',
+        long = "id-file",
+        value_name = "FILE",
+        conflicts_with = "regex"
+    )]
+    id_list_path: Option<PathBuf>,
+
+    #[clap(flatten)]
+    output: OutputOpts,
+
+    /// Keep the sequences whose names match, instead of discarding them.
+    #[clap(short, long)]
+    keep: bool,
+
+    /// Discard sequences shorter than this.
+    #[clap(long, value_name = "N")]
+    min_length: Option<u64>,
+
+    /// Discard sequences longer than this.
+    #[clap(long, value_name = "N")]
+    max_length: Option<u64>,
+
+    /// Discard sequences where more than this fraction of the bases are `N`.
+    #[clap(long, value_name = "FRACTION", value_parser = parse_fraction)]
+    max_n_fraction: Option<f64>,
+
+    #[clap(flatten)]
+    threads: ThreadOpts,
+}
+
+/// Parse a fraction between 0 and 1
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(frac) if (0.0..=1.0).contains(&frac) => Ok(frac),
+        _ => Err(format!("{} is not a fraction between 0 and 1", s)),
+    }
+}
+
+impl CliOpt for FastaFilterOpts {
+    fn exec(&self) -> anyhow::Result<()> {
+        let ids = match &self.id_list_path {
+            Some(path) => Some(read_ids(path)?),
+            None => None,
+        };
+
+        let hts = HtsFile::with_format(&self.hts_path, self.input_format)?;
+        if hts.filetype() != Hts::Fastx(Fastx::Fasta) {
+            bail!(BjtError::WrongFormat {
+                path: hts.path().to_path_buf(),
+                expected: "FASTA",
+            });
+        }
+        let progress = Progress::reading(&hts);
+        let mut reader =
+            parse_fastx_reader(hts.decompressed_reader(self.threads.n_threads(), &progress)?)
+                .with_context(|| format!("Error opening HTS file {}", hts.path().display()))?;
+        let mut writer = self.writer_output()?;
+
+        let mut n_kept = 0;
+        while let Some(record) = reader.next() {
+            let rec = record
+                .with_context(|| format!("Error reading HTS file {}", hts.path().display()))?;
+            if self.passes(&rec, ids.as_ref()) {
+                rec.write(&mut writer, None)?;
+                n_kept += 1;
+            }
+            progress.record();
+        }
+        writer.finish()?;
+        info!(
+            "Kept {} of {} sequences from {}",
+            n_kept,
+            progress.n_records(),
+            hts.path().display()
+        );
+        progress.finish(&hts);
+
+        Ok(())
+    }
+}
+
+impl RecordFilter for FastaFilterOpts {
+    fn output(&self) -> &OutputOpts {
+        &self.output
+    }
+}
+
+impl FastaFilterOpts {
+    /// Whether a sequence should be written out
+    fn passes(&self, rec: &SequenceRecord, ids: Option<&HashSet<String>>) -> bool {
+        let name = sequence_name(rec.id());
+        let matched = match (&self.regex, ids) {
+            (Some(re), _) => Some(re.is_match(&name)),
+            (None, Some(ids)) => Some(ids.contains(name.as_ref())),
+            (None, None) => None,
+        };
+        // sequences are only discarded by name if a regex or ID file was given
+        if matched.is_some_and(|m| m != self.keep) {
+            return false;
+        }
+
+        let length = rec.num_bases() as u64;
+        if self.min_length.is_some_and(|min| length < min)
+            || self.max_length.is_some_and(|max| length > max)
+        {
+            return false;
+        }
+
+        match (self.max_n_fraction, length) {
+            (Some(max), 1..) => {
+                let n = rec
+                    .seq()
+                    .iter()
+                    .filter(|b| matches!(b, b'N' | b'n'))
+                    .count();
+                n as f64 / length as f64 <= max
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Name of a sequence, from its header up to the first whitespace
+fn sequence_name(id: &[u8]) -> std::borrow::Cow<'_, str> {
+    let name = id
+        .split(|b| b.is_ascii_whitespace())
+        .next()
+        .unwrap_or_default();
+
+    String::from_utf8_lossy(name)
+}
+
+/// Read the sequence names from the first word of each line of a file
+fn read_ids(path: &Path) -> Result<HashSet<String>, BjtError> {
+    let content = read_to_string(path).map_err(|source| BjtError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let ids: HashSet<String> = content
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect();
+
+    if ids.is_empty() {
+        return Err(BjtError::EmptyFile {
+            path: path.to_path_buf(),
+            reason: "There are no IDs to filter.",
+        });
+    }
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    const REFERENCE: &str = ">chr1 primary\nACGTACGTAC\nGT\n>chr1_KI270706v1_random\nACGT\n>chrUn_gap\nNNNNNNAC\n>chrM\nACGTACGTNN\n";
+
+    /// Filter the reference with some arguments, and return the names of the sequences that were kept
+    fn filter(args: &[&str]) -> Vec<String> {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("ref.fa");
+        let output = dir.path().join("ref.out.fa");
+        write(&input, REFERENCE).unwrap();
+        let mut cli = vec![
+            "fasta",
+            input.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ];
+        cli.extend(args);
+        FastaFilterOpts::parse_from(cli).exec().unwrap();
+
+        read_to_string(&output)
+            .unwrap()
+            .lines()
+            .filter_map(|l| l.strip_prefix('>'))
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn regex_discards_matches() {
+        let obs = filter(&["-r", "_random$|^chrUn"]);
+
+        assert_eq!(obs, ["chr1 primary", "chrM"]);
+    }
+
+    #[test]
+    fn id_file_keeps_matches() {
+        let dir = tempdir().unwrap();
+        let ids = dir.path().join("ids.txt");
+        write(&ids, "chrM\t16569\nchr1\t248956422\n").unwrap();
+        let obs = filter(&["-f", ids.to_str().unwrap(), "--keep"]);
+
+        assert_eq!(obs, ["chr1 primary", "chrM"]);
+    }
+
+    #[test]
+    fn length_and_n_fraction_bounds() {
+        let obs = filter(&["--min-length", "5", "--max-n-fraction", "0.5"]);
+
+        assert_eq!(obs, ["chr1 primary", "chrM"]);
+    }
+
+    #[test]
+    fn kept_names_must_also_pass_bounds() {
+        let obs = filter(&["-r", "^chr1", "-k", "--max-length", "5"]);
+
+        assert_eq!(obs, ["chr1_KI270706v1_random"]);
+    }
+
+    #[test]
+    fn wrapping_is_preserved() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("wrapped.fa");
+        let output = dir.path().join("wrapped.out.fa");
+        write(&input, REFERENCE).unwrap();
+        FastaFilterOpts::parse_from([
+            "fasta",
+            input.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+            "--min-length",
+            "10",
+        ])
+        .exec()
+        .unwrap();
+        let obs = read_to_string(&output).unwrap();
+
+        assert_eq!(obs, ">chr1 primary\nACGTACGTAC\nGT\n>chrM\nACGTACGTNN\n");
+    }
+
+    #[test]
+    fn some_filter_is_required() {
+        let obs = FastaFilterOpts::try_parse_from(["fasta", "ref.fa"]);
+
+        assert!(obs.is_err());
+    }
+}
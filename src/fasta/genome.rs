@@ -0,0 +1,460 @@
+//! # Genomes
+//!
+//! The names and lengths of the sequences in a reference genome, as used by interval tools.
+//!
+//! A genome can be read from a FASTA file, its `.fai` index, a `chrom.sizes` file (or bedtools `.genome` file), or a sequence dictionary (`.dict`).
+//! It can be written out as a `chrom.sizes` file, or as a sequence dictionary of SAM `@SQ` header lines, with the MD5 checksum of each sequence.
+//!
+//! MD5 checksums follow the [SAM specification](https://samtools.github.io/hts-specs/SAMv1.pdf): they are calculated over the uppercase bases, without any whitespace.
+
+use super::index::{FaiIndex, IndexedFasta};
+use crate::{
+    cli::CliOpt,
+    error::BjtError,
+    utils::{
+        decompress::ThreadOpts, is_stdin, progress::Progress, sink::OutputOpts, Fastx, Hts, HtsFile,
+    },
+};
+use anyhow::{bail, Context};
+use clap::Parser;
+use log::{info, warn};
+use md5::{Digest, Md5};
+use needletail::parse_fastx_reader;
+use std::{
+    collections::HashMap,
+    fs::{canonicalize, read_to_string},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Version of the SAM format written in the `@HD` line of sequence dictionaries
+const SAM_VERSION: &str = "1.6";
+
+/// A single sequence in a genome
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenomeSequence {
+    /// Name of the sequence
+    pub name: String,
+
+    /// Number of bases in the sequence
+    pub length: u64,
+
+    /// MD5 checksum of the sequence, if it's known
+    pub md5: Option<String>,
+
+    /// URI of the file the sequence comes from, if it's known
+    pub uri: Option<String>,
+}
+
+/// Names and lengths of the sequences in a reference genome, in their original order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Genome {
+    sequences: Vec<GenomeSequence>,
+
+    /// Position of each sequence in `sequences`, by name
+    names: HashMap<String, usize>,
+}
+
+/// Kinds of files a genome can be read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GenomeSource {
+    Fasta,
+    Fai,
+    ChromSizes,
+    Dict,
+}
+
+impl GenomeSource {
+    /// Detect the kind of file from its extension, assuming anything unrecognized is a FASTA file
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("fai") => Self::Fai,
+            Some("sizes" | "genome") => Self::ChromSizes,
+            Some("dict") => Self::Dict,
+            _ => Self::Fasta,
+        }
+    }
+}
+
+impl Genome {
+    /// All sequences, in their original order
+    pub fn sequences(&self) -> &[GenomeSequence] {
+        &self.sequences
+    }
+
+    /// Look up a sequence by its name
+    pub fn get(&self, name: &str) -> Option<&GenomeSequence> {
+        self.names.get(name).map(|&i| &self.sequences[i])
+    }
+
+    /// Add the next sequence, unless there's already one with the same name
+    fn push(&mut self, seq: GenomeSequence) -> Result<(), String> {
+        if self.get(&seq.name).is_some() {
+            return Err(format!("Sequence {} appears more than once.", seq.name));
+        }
+
+        self.names.insert(seq.name.clone(), self.sequences.len());
+        self.sequences.push(seq);
+
+        Ok(())
+    }
+
+    /// Read a genome from a FASTA file, its `.fai` index, a `chrom.sizes` file, or a sequence dictionary, detected from its extension
+    ///
+    /// MD5 checksums are only calculated if `md5` is set, and only if the sequences are available.
+    pub fn from_path(path: &Path, md5: bool, threads: u16) -> anyhow::Result<Self> {
+        match GenomeSource::from_path(path) {
+            GenomeSource::Fasta => Self::from_fasta(path, md5, threads),
+            GenomeSource::Fai => Ok(Self::from_fai(path, md5)?),
+            GenomeSource::ChromSizes => Ok(Self::from_chrom_sizes(path)?),
+            GenomeSource::Dict => Ok(Self::from_dict(path)?),
+        }
+    }
+
+    /// Read a genome by streaming through a FASTA file
+    pub fn from_fasta(path: &Path, md5: bool, threads: u16) -> anyhow::Result<Self> {
+        let format = is_stdin(path).then_some(Hts::Fastx(Fastx::Fasta));
+        let hts = HtsFile::with_format(path, format)?;
+        if hts.filetype() != Hts::Fastx(Fastx::Fasta) {
+            bail!(BjtError::WrongFormat {
+                path: path.to_path_buf(),
+                expected: "FASTA",
+            });
+        }
+        let uri = file_uri(path);
+
+        let progress = Progress::reading(&hts);
+        let mut reader = parse_fastx_reader(hts.decompressed_reader(threads, &progress)?)
+            .with_context(|| format!("Error opening HTS file {}", path.display()))?;
+        let mut genome = Self::default();
+        while let Some(record) = reader.next() {
+            let rec =
+                record.with_context(|| format!("Error reading HTS file {}", path.display()))?;
+            let name = rec
+                .id()
+                .split(|b| b.is_ascii_whitespace())
+                .next()
+                .unwrap_or_default();
+            let seq = GenomeSequence {
+                name: String::from_utf8_lossy(name).into_owned(),
+                length: rec.num_bases() as u64,
+                md5: md5.then(|| sequence_md5(&rec.seq())),
+                uri: uri.clone(),
+            };
+            genome.push(seq).map_err(|reason| BjtError::InvalidRecord {
+                path: path.to_path_buf(),
+                record: progress.n_records() + 1,
+                reason,
+            })?;
+            progress.record();
+        }
+        progress.finish(&hts);
+
+        Ok(genome)
+    }
+
+    /// Read a genome from the `.fai` index of a FASTA file
+    ///
+    /// MD5 checksums need the sequences themselves, so they're only calculated if the FASTA file is next to its index.
+    pub fn from_fai(path: &Path, md5: bool) -> anyhow::Result<Self> {
+        let index = FaiIndex::read(path)?;
+        let fasta_path = path.with_extension("");
+        let mut fasta = match (md5, fasta_path.is_file()) {
+            (true, true) => Some(IndexedFasta::open(&fasta_path)?),
+            (true, false) => {
+                warn!(
+                    "Cannot find the FASTA file {} for {}, so its sequence dictionary won't have MD5 checksums",
+                    fasta_path.display(),
+                    path.display()
+                );
+                None
+            }
+            (false, _) => None,
+        };
+        let uri = fasta.as_ref().and_then(|_| file_uri(&fasta_path));
+
+        let mut genome = Self::default();
+        for record in index.records() {
+            let md5 = match fasta.as_mut() {
+                Some(fasta) => Some(sequence_md5(&fasta.fetch(
+                    &record.name,
+                    0,
+                    record.length,
+                )?)),
+                None => None,
+            };
+            // names are already unique in an index
+            let _ = genome.push(GenomeSequence {
+                name: record.name.clone(),
+                length: record.length,
+                md5,
+                uri: uri.clone(),
+            });
+        }
+
+        Ok(genome)
+    }
+
+    /// Read a genome from a `chrom.sizes` file, with a name and length on each line
+    pub fn from_chrom_sizes(path: &Path) -> Result<Self, BjtError> {
+        let content = read_file(path)?;
+        let mut genome = Self::default();
+
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| BjtError::InvalidLine {
+                path: path.to_path_buf(),
+                line: i as u64 + 1,
+                reason: reason.to_string(),
+            };
+
+            let mut fields = line.split_whitespace();
+            let (Some(name), Some(length)) = (fields.next(), fields.next()) else {
+                return Err(invalid("Each line must have a sequence name and length."));
+            };
+            let length = length
+                .parse()
+                .map_err(|_| invalid("Sequence lengths must be whole numbers."))?;
+            genome
+                .push(GenomeSequence {
+                    name: name.to_string(),
+                    length,
+                    md5: None,
+                    uri: None,
+                })
+                .map_err(|reason| invalid(&reason))?;
+        }
+
+        Ok(genome)
+    }
+
+    /// Read a genome from the `@SQ` lines of a sequence dictionary (or any SAM header)
+    pub fn from_dict(path: &Path) -> Result<Self, BjtError> {
+        let content = read_file(path)?;
+        let mut genome = Self::default();
+
+        for (i, line) in content.lines().enumerate() {
+            let Some(tags) = line.strip_prefix("@SQ\t") else {
+                continue;
+            };
+            let invalid = |reason: &str| BjtError::InvalidLine {
+                path: path.to_path_buf(),
+                line: i as u64 + 1,
+                reason: reason.to_string(),
+            };
+
+            let tags: HashMap<&str, &str> =
+                tags.split('\t').filter_map(|t| t.split_once(':')).collect();
+            let (Some(name), Some(length)) = (tags.get("SN"), tags.get("LN")) else {
+                return Err(invalid("`@SQ` lines must have `SN` and `LN` tags."));
+            };
+            let length = length
+                .parse()
+                .map_err(|_| invalid("Sequence lengths must be whole numbers."))?;
+            genome
+                .push(GenomeSequence {
+                    name: name.to_string(),
+                    length,
+                    md5: tags.get("M5").map(|m| m.to_string()),
+                    uri: tags.get("UR").map(|u| u.to_string()),
+                })
+                .map_err(|reason| invalid(&reason))?;
+        }
+
+        Ok(genome)
+    }
+
+    /// Write a `chrom.sizes` file, with the name and length of each sequence
+    pub fn write_chrom_sizes<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for seq in &self.sequences {
+            writeln!(writer, "{}\t{}", seq.name, seq.length)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a sequence dictionary, with an `@SQ` line for each sequence
+    pub fn write_dict<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "@HD\tVN:{}", SAM_VERSION)?;
+        for seq in &self.sequences {
+            write!(writer, "@SQ\tSN:{}\tLN:{}", seq.name, seq.length)?;
+            if let Some(md5) = &seq.md5 {
+                write!(writer, "\tM5:{}", md5)?;
+            }
+            if let Some(uri) = &seq.uri {
+                write!(writer, "\tUR:{}", uri)?;
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// MD5 checksum of a sequence, calculated over its uppercase bases
+fn sequence_md5(seq: &[u8]) -> String {
+    let mut hasher = Md5::new();
+    let bases: Vec<u8> = seq
+        .iter()
+        .filter(|b| b.is_ascii_graphic())
+        .map(u8::to_ascii_uppercase)
+        .collect();
+    hasher.update(&bases);
+
+    format!("{:x}", hasher.finalize())
+}
+
+/// `file:` URI for the absolute path of a file, if it has one
+fn file_uri(path: &Path) -> Option<String> {
+    if is_stdin(path) {
+        return None;
+    }
+
+    canonicalize(path)
+        .ok()
+        .map(|p| format!("file:{}", p.display()))
+}
+
+/// Read a whole text file, reporting which file couldn't be read
+fn read_file(path: &Path) -> Result<String, BjtError> {
+    read_to_string(path).map_err(|source| BjtError::Read {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// CLI options for writing the `chrom.sizes` file or sequence dictionary of a genome
+#[derive(Debug, Parser)]
+pub(crate) struct GenomeOpts {
+    /// Reference genome to describe: a FASTA file (use `-` for standard input), its `.fai` index, a `chrom.sizes` (or `.genome`) file, or a sequence dictionary (`.dict`).
+    #[clap(name = "GENOME")]
+    genome_path: PathBuf,
+
+    /// Write a sequence dictionary (`@SQ` lines with `SN`, `LN`, `M5`, and `UR` tags) instead of a `chrom.sizes` file
+    #[clap(short, long)]
+    dict: bool,
+
+    /// URI to list as the source of every sequence in the dictionary [default: the absolute path of the FASTA file]
+    #[clap(long, value_name = "URI", requires = "dict")]
+    uri: Option<String>,
+
+    #[clap(flatten)]
+    output: OutputOpts,
+
+    #[clap(flatten)]
+    threads: ThreadOpts,
+}
+
+impl CliOpt for GenomeOpts {
+    fn exec(&self) -> anyhow::Result<()> {
+        let mut genome = Genome::from_path(&self.genome_path, self.dict, self.threads.n_threads())?;
+        if let Some(uri) = &self.uri {
+            for seq in genome.sequences.iter_mut() {
+                seq.uri = Some(uri.clone());
+            }
+        }
+
+        let mut sink = self.output.sink()?;
+        let written = match self.dict {
+            true => genome.write_dict(&mut sink),
+            false => genome.write_chrom_sizes(&mut sink),
+        };
+        written.map_err(|source| BjtError::Write {
+            path: self.output.path().unwrap_or(Path::new("-")).to_path_buf(),
+            source,
+        })?;
+        sink.finish()?;
+        info!(
+            "Wrote {} sequences from {}",
+            genome.sequences().len(),
+            self.genome_path.display()
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::index::build_and_save;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    const FASTA: &str = ">chr1 primary\nACGTacgt\nNN\n>chrM\nGATC\n";
+
+    fn render(genome: &Genome, dict: bool) -> String {
+        let mut out = vec![];
+        match dict {
+            true => genome.write_dict(&mut out).unwrap(),
+            false => genome.write_chrom_sizes(&mut out).unwrap(),
+        }
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn md5_ignores_case_and_whitespace() {
+        // `echo -n ACGTACGTNN | md5sum`
+        assert_eq!(
+            sequence_md5(b"ACGTacgtNN"),
+            "a0e5e77e1f26e1b3d8a27baa3db84c40"
+        );
+        assert_eq!(sequence_md5(b"ACGT\nACGT NN"), sequence_md5(b"acgtacgtnn"));
+    }
+
+    #[test]
+    fn fasta_and_fai_give_same_dict() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ref.fa");
+        write(&path, FASTA).unwrap();
+        let from_fasta = Genome::from_fasta(&path, true, 1).unwrap();
+        build_and_save(&path).unwrap();
+        let from_fai = Genome::from_fai(&dir.path().join("ref.fa.fai"), true).unwrap();
+
+        assert_eq!(from_fasta, from_fai);
+        assert_eq!(render(&from_fasta, false), "chr1\t10\nchrM\t4\n");
+        assert_eq!(
+            from_fasta.get("chrM").unwrap().md5.as_deref(),
+            Some(sequence_md5(b"GATC").as_str())
+        );
+    }
+
+    #[test]
+    fn dict_round_trips() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ref.fa");
+        write(&path, FASTA).unwrap();
+        let exp = Genome::from_fasta(&path, true, 1).unwrap();
+
+        let dict_path = dir.path().join("ref.dict");
+        write(&dict_path, render(&exp, true)).unwrap();
+        let obs = Genome::from_path(&dict_path, true, 1).unwrap();
+
+        assert_eq!(obs, exp);
+        assert!(render(&obs, true).starts_with("@HD\tVN:1.6\n@SQ\tSN:chr1\tLN:10\tM5:"));
+    }
+
+    #[test]
+    fn chrom_sizes_are_read() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("hg38.chrom.sizes");
+        write(&path, "chr1\t248956422\n\nchrM\t16569\n").unwrap();
+        let obs = Genome::from_path(&path, false, 1).unwrap();
+
+        assert_eq!(obs.get("chr1").map(|s| s.length), Some(248956422));
+        assert_eq!(obs.sequences().len(), 2);
+    }
+
+    #[test]
+    fn duplicate_sizes_are_err() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("dup.sizes");
+        write(&path, "chr1\t10\nchr1\t20\n").unwrap();
+        let obs = Genome::from_chrom_sizes(&path);
+
+        assert!(matches!(obs, Err(BjtError::InvalidLine { line: 2, .. })));
+    }
+}
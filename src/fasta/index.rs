@@ -0,0 +1,734 @@
+//! # FASTA indexes
+//!
+//! Build and read [`.fai` indexes](https://www.htslib.org/doc/faidx.html), to fetch any part of a FASTA file without reading the whole file.
+//!
+//! Each line of a `.fai` index describes one sequence: its name, its length, the byte offset of its first base, and the number of bases and bytes on each of its lines.
+//! Every line of a sequence, except its last, must hold the same number of bases.
+//!
+//! BGZF-compressed FASTA files can be indexed too.
+//! Their `.fai` offsets are in the decompressed content, so they also get a `.gzi` index that maps decompressed offsets back to the compressed blocks that contain them.
+//! Files compressed with plain gzip (or anything else) can't be read from the middle, and need to be recompressed with `bgzip` first.
+
+use crate::utils::{
+    decompress::{decompress, DEFAULT_THREADS},
+    sink::OutputSink,
+    sniff::Compression,
+};
+use flate2::read::MultiGzDecoder;
+use log::{debug, warn};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Extension added to a FASTA file's name for its index
+const FAI_EXTENSION: &str = "fai";
+
+/// Extension added to a BGZF-compressed FASTA file's name for its block index
+const GZI_EXTENSION: &str = "gzi";
+
+/// Number of bytes at the start of a gzip block header, up to and including the length of its extra fields
+const GZIP_HEADER_BYTES: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum FaidxError {
+    #[error("Cannot read {path}. {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Cannot index {path}, because it is compressed with {compression}. Only uncompressed and BGZF-compressed FASTA files can be indexed, so please recompress it with `bgzip`.")]
+    Compressed {
+        path: PathBuf,
+        compression: Compression,
+    },
+
+    #[error("Cannot parse line {line} of the index {path}. {reason}")]
+    InvalidIndex {
+        path: PathBuf,
+        line: usize,
+        reason: &'static str,
+    },
+
+    #[error("Cannot index {path}, because the lines of sequence {name} have different lengths. Every line except the last must have the same length.")]
+    DifferentLineLengths { path: PathBuf, name: String },
+
+    #[error("Cannot index {path}, because sequence {name} appears more than once.")]
+    DuplicateName { path: PathBuf, name: String },
+
+    #[error("Cannot index {path}, because it has sequence before its first `>` header.")]
+    MissingHeader { path: PathBuf },
+
+    #[error("Sequence {name} is not in {path}.")]
+    UnknownSequence { path: PathBuf, name: String },
+
+    #[error("Cannot parse region {region}. {reason}")]
+    InvalidRegion {
+        region: String,
+        reason: &'static str,
+    },
+}
+
+/// Location of a single sequence in a FASTA file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FaiRecord {
+    /// Name of the sequence, up to the first whitespace in its header
+    pub name: String,
+
+    /// Number of bases in the sequence
+    pub length: u64,
+
+    /// Byte offset of the first base of the sequence
+    pub offset: u64,
+
+    /// Number of bases on each line
+    pub line_bases: u64,
+
+    /// Number of bytes on each line, including the line ending
+    pub line_width: u64,
+}
+
+impl FaiRecord {
+    /// Byte offset of the base at (0-based) position `pos`
+    pub fn offset_of(&self, pos: u64) -> u64 {
+        match self.line_bases {
+            // a sequence without any bases has nowhere to point to
+            0 => self.offset,
+            bases => self.offset + (pos / bases) * self.line_width + pos % bases,
+        }
+    }
+}
+
+/// Index of the sequences in a FASTA file, in the order they appear
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FaiIndex {
+    records: Vec<FaiRecord>,
+
+    /// Position of each sequence in `records`, by name
+    names: HashMap<String, usize>,
+}
+
+impl FaiIndex {
+    /// All indexed sequences, in the order they appear in the FASTA file
+    pub fn records(&self) -> &[FaiRecord] {
+        &self.records
+    }
+
+    /// Look up a sequence by its name
+    pub fn get(&self, name: &str) -> Option<&FaiRecord> {
+        self.names.get(name).map(|&i| &self.records[i])
+    }
+
+    /// Add the next sequence to the index
+    fn push(&mut self, record: FaiRecord, path: &Path) -> Result<(), FaidxError> {
+        if self.names.contains_key(&record.name) {
+            return Err(FaidxError::DuplicateName {
+                path: path.to_path_buf(),
+                name: record.name,
+            });
+        }
+
+        self.names.insert(record.name.clone(), self.records.len());
+        self.records.push(record);
+
+        Ok(())
+    }
+
+    /// Index the (decompressed) content of a FASTA file
+    pub fn build<R: BufRead>(mut reader: R, path: &Path) -> Result<Self, FaidxError> {
+        let io_err = |source| FaidxError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let mut index = Self::default();
+        let mut current: Option<FaiRecord> = None;
+        // whether the current sequence has had a short line, which must be its last
+        let mut short_line = false;
+        let mut pos = 0;
+        let mut line = vec![];
+
+        loop {
+            line.clear();
+            let n = reader.read_until(b'\n', &mut line).map_err(io_err)? as u64;
+            if n == 0 {
+                break;
+            }
+            pos += n;
+
+            if let Some(header) = line.strip_prefix(b">") {
+                if let Some(record) = current.take() {
+                    index.push(record, path)?;
+                }
+                let name = header
+                    .split(|b| b.is_ascii_whitespace())
+                    .next()
+                    .unwrap_or_default();
+                current = Some(FaiRecord {
+                    name: String::from_utf8_lossy(name).into_owned(),
+                    length: 0,
+                    offset: pos,
+                    line_bases: 0,
+                    line_width: 0,
+                });
+                short_line = false;
+                continue;
+            }
+
+            let n_bases = line
+                .iter()
+                .rposition(|b| !matches!(b, b'\n' | b'\r'))
+                .map_or(0, |i| i as u64 + 1);
+            let Some(record) = current.as_mut() else {
+                if n_bases == 0 {
+                    continue;
+                }
+                return Err(FaidxError::MissingHeader {
+                    path: path.to_path_buf(),
+                });
+            };
+            let inconsistent = |name: &str| FaidxError::DifferentLineLengths {
+                path: path.to_path_buf(),
+                name: name.to_string(),
+            };
+
+            // blank lines are only allowed at the end of a sequence
+            if n_bases == 0 {
+                short_line = true;
+                continue;
+            }
+            if short_line {
+                return Err(inconsistent(&record.name));
+            }
+
+            // the last line of a file may not have a line ending
+            let has_ending = line.ends_with(b"\n");
+            if record.line_bases == 0 {
+                record.line_bases = n_bases;
+                record.line_width = if has_ending { n } else { n + 1 };
+            } else if n_bases > record.line_bases
+                || (has_ending && n - n_bases != record.line_width - record.line_bases)
+            {
+                return Err(inconsistent(&record.name));
+            } else if n_bases < record.line_bases {
+                short_line = true;
+            }
+            record.length += n_bases;
+        }
+        if let Some(record) = current {
+            index.push(record, path)?;
+        }
+
+        Ok(index)
+    }
+
+    /// Read an index from a `.fai` file
+    pub fn read(path: &Path) -> Result<Self, FaidxError> {
+        let file = File::open(path).map_err(|source| FaidxError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut index = Self::default();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|source| FaidxError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |reason| FaidxError::InvalidIndex {
+                path: path.to_path_buf(),
+                line: i + 1,
+                reason,
+            };
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [name, length, offset, line_bases, line_width] = fields[..] else {
+                return Err(invalid("Each line must have 5 tab-separated fields."));
+            };
+            let number = |field: &str| {
+                field
+                    .parse::<u64>()
+                    .map_err(|_| invalid("The length, offset, and line lengths must be numbers."))
+            };
+            let record = FaiRecord {
+                name: name.to_string(),
+                length: number(length)?,
+                offset: number(offset)?,
+                line_bases: number(line_bases)?,
+                line_width: number(line_width)?,
+            };
+            if record.line_width < record.line_bases {
+                return Err(invalid("Lines can't have more bases than they have bytes."));
+            }
+            index.push(record, path)?;
+        }
+
+        Ok(index)
+    }
+
+    /// Write the index to a `.fai` file
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for r in &self.records {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                r.name, r.length, r.offset, r.line_bases, r.line_width
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compressed and decompressed offsets of the blocks in a BGZF file, as stored in a `.gzi` index
+///
+/// The first block, which always starts at the beginning of both, is left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GziIndex {
+    blocks: Vec<(u64, u64)>,
+}
+
+impl GziIndex {
+    /// Find the blocks of a BGZF file from their headers, without decompressing them
+    pub fn build<R: Read>(mut reader: R, path: &Path) -> Result<Self, FaidxError> {
+        let io_err = |source| FaidxError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        let not_bgzf = || FaidxError::Compressed {
+            path: path.to_path_buf(),
+            compression: Compression::Gzip,
+        };
+
+        let mut blocks = vec![];
+        let (mut compressed, mut decompressed) = (0, 0);
+        let mut header = [0; GZIP_HEADER_BYTES];
+        let mut rest = vec![];
+
+        loop {
+            match reader.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(io_err(e)),
+            }
+            let extra_len = u16::from_le_bytes([header[10], header[11]]) as usize;
+            let mut extra = vec![0; extra_len];
+            reader.read_exact(&mut extra).map_err(io_err)?;
+
+            // the `BC` subfield holds the size of the whole block, minus 1
+            let block_size = bgzf_block_size(&extra).ok_or_else(not_bgzf)?;
+            let rest_len = block_size
+                .checked_sub((GZIP_HEADER_BYTES + extra_len) as u64)
+                .filter(|n| *n >= 4)
+                .ok_or_else(not_bgzf)?;
+            rest.resize(rest_len as usize, 0);
+            reader.read_exact(&mut rest).map_err(io_err)?;
+
+            // the decompressed size is stored in the last 4 bytes of the block
+            let tail = &rest[rest.len() - 4..];
+            let block_content = u32::from_le_bytes([tail[0], tail[1], tail[2], tail[3]]) as u64;
+            if compressed > 0 && block_content > 0 {
+                blocks.push((compressed, decompressed));
+            }
+            compressed += block_size;
+            decompressed += block_content;
+        }
+
+        Ok(Self { blocks })
+    }
+
+    /// Read a `.gzi` index
+    pub fn read(path: &Path) -> Result<Self, FaidxError> {
+        let invalid = |reason| FaidxError::InvalidIndex {
+            path: path.to_path_buf(),
+            line: 1,
+            reason,
+        };
+        let mut content = vec![];
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut content))
+            .map_err(|source| FaidxError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        let numbers: Vec<u64> = content
+            .chunks(8)
+            .map(|b| b.try_into().map(u64::from_le_bytes))
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("It must be made of 64-bit numbers."))?;
+        let Some((n, offsets)) = numbers.split_first() else {
+            return Err(invalid("It must start with the number of blocks."));
+        };
+        if offsets.len() as u64 != 2 * n {
+            return Err(invalid(
+                "It must have a pair of offsets for each of its blocks.",
+            ));
+        }
+
+        let blocks = offsets.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+        Ok(Self { blocks })
+    }
+
+    /// Write the index to a `.gzi` file
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&(self.blocks.len() as u64).to_le_bytes())?;
+        for (compressed, decompressed) in &self.blocks {
+            writer.write_all(&compressed.to_le_bytes())?;
+            writer.write_all(&decompressed.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Compressed and decompressed offsets of the block that contains a decompressed offset
+    fn block_of(&self, offset: u64) -> (u64, u64) {
+        match self.blocks.partition_point(|(_, d)| *d <= offset) {
+            0 => (0, 0),
+            i => self.blocks[i - 1],
+        }
+    }
+}
+
+/// Size of a BGZF block, from the extra fields of its header
+fn bgzf_block_size(mut extra: &[u8]) -> Option<u64> {
+    while extra.len() >= 4 {
+        let field_len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let field = extra.get(4..4 + field_len)?;
+        if &extra[..2] == b"BC" && field_len == 2 {
+            return Some(u16::from_le_bytes([field[0], field[1]]) as u64 + 1);
+        }
+        extra = &extra[4 + field_len..];
+    }
+
+    None
+}
+
+/// Path to an index, made by adding an extension to the full name of the file it indexes
+fn index_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(".");
+    name.push(extension);
+
+    PathBuf::from(name)
+}
+
+/// Open a file for reading, reporting which file couldn't be opened
+fn open(path: &Path) -> Result<File, FaidxError> {
+    File::open(path).map_err(|source| FaidxError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Detect the compression of a FASTA file, and check that it can be indexed
+fn indexable_compression(path: &Path) -> Result<Compression, FaidxError> {
+    let mut head = vec![];
+    open(path)?
+        .take(18)
+        .read_to_end(&mut head)
+        .map_err(|source| FaidxError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+    match Compression::from_magic(&head) {
+        compression @ (Compression::None | Compression::Bgzf) => Ok(compression),
+        compression => Err(FaidxError::Compressed {
+            path: path.to_path_buf(),
+            compression,
+        }),
+    }
+}
+
+/// Build the indexes of a FASTA file, and save them next to it
+///
+/// BGZF-compressed files also get a `.gzi` index.
+pub fn build_and_save(path: &Path) -> anyhow::Result<(FaiIndex, Option<GziIndex>)> {
+    let compression = indexable_compression(path)?;
+    debug!("Indexing {}", path.display());
+
+    let reader =
+        decompress(Box::new(open(path)?), DEFAULT_THREADS).map_err(|source| FaidxError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let fai = FaiIndex::build(BufReader::new(reader), path)?;
+    save(&fai, &index_path(path, FAI_EXTENSION), |sink, index| {
+        index.write(sink)
+    })?;
+
+    let gzi = match compression {
+        Compression::Bgzf => {
+            let gzi = GziIndex::build(BufReader::new(open(path)?), path)?;
+            save(&gzi, &index_path(path, GZI_EXTENSION), |sink, index| {
+                index.write(sink)
+            })?;
+            Some(gzi)
+        }
+        _ => None,
+    };
+
+    Ok((fai, gzi))
+}
+
+/// Write an index to a file, only putting it in place once it's complete
+fn save<T, F>(index: &T, path: &Path, write: F) -> anyhow::Result<()>
+where
+    F: Fn(&mut OutputSink, &T) -> io::Result<()>,
+{
+    let mut sink = OutputSink::create(Some(path), Some(Compression::None), None)?;
+    write(&mut sink, index).map_err(|source| FaidxError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    sink.finish()?;
+
+    Ok(())
+}
+
+/// Where the bytes of an indexed FASTA file are read from
+enum FastaSource {
+    Plain(BufReader<File>),
+    Bgzf {
+        reader: BufReader<File>,
+        gzi: GziIndex,
+    },
+}
+
+/// A FASTA file that any sequence, or part of a sequence, can be fetched from
+pub(crate) struct IndexedFasta {
+    path: PathBuf,
+    index: FaiIndex,
+    source: FastaSource,
+}
+
+impl IndexedFasta {
+    /// Open an indexed FASTA file, building and saving its indexes if they don't exist yet
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let compression = indexable_compression(path)?;
+        let fai_path = index_path(path, FAI_EXTENSION);
+        let gzi_path = index_path(path, GZI_EXTENSION);
+        let needs_gzi = compression == Compression::Bgzf;
+
+        let (index, gzi) = if fai_path.exists() && (!needs_gzi || gzi_path.exists()) {
+            if is_older(&fai_path, path) {
+                warn!(
+                    "The index {} is older than {}, and may be out of date",
+                    fai_path.display(),
+                    path.display()
+                );
+            }
+            let gzi = match needs_gzi {
+                true => Some(GziIndex::read(&gzi_path)?),
+                false => None,
+            };
+            (FaiIndex::read(&fai_path)?, gzi)
+        } else {
+            build_and_save(path)?
+        };
+
+        let file = open(path)?;
+        let source = match gzi {
+            Some(gzi) => FastaSource::Bgzf {
+                reader: BufReader::new(file),
+                gzi,
+            },
+            None => FastaSource::Plain(BufReader::new(file)),
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            index,
+            source,
+        })
+    }
+
+    /// Index of the sequences in the file
+    pub fn index(&self) -> &FaiIndex {
+        &self.index
+    }
+
+    /// Fetch the bases from (0-based) positions `start` up to, but not including, `end` of a sequence
+    ///
+    /// Positions past the end of the sequence are left out.
+    pub fn fetch(&mut self, name: &str, start: u64, end: u64) -> Result<Vec<u8>, FaidxError> {
+        let record = self
+            .index
+            .get(name)
+            .ok_or_else(|| FaidxError::UnknownSequence {
+                path: self.path.clone(),
+                name: name.to_string(),
+            })?;
+        let end = end.min(record.length);
+        if start >= end {
+            return Ok(vec![]);
+        }
+
+        let from = record.offset_of(start);
+        let to = record.offset_of(end - 1) + 1;
+        let mut raw = vec![0; (to - from) as usize];
+        self.read_at(from, &mut raw)
+            .map_err(|source| FaidxError::Io {
+                path: self.path.clone(),
+                source,
+            })?;
+        raw.retain(|b| !matches!(b, b'\n' | b'\r'));
+
+        Ok(raw)
+    }
+
+    /// Fill `buf` with the (decompressed) bytes starting at `offset`
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        match &mut self.source {
+            FastaSource::Plain(reader) => {
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.read_exact(buf)
+            }
+            FastaSource::Bgzf { reader, gzi } => {
+                // each block is a complete gzip member, so decompression can start from any of them
+                let (compressed, decompressed) = gzi.block_of(offset);
+                reader.seek(SeekFrom::Start(compressed))?;
+                let mut decoder = MultiGzDecoder::new(reader);
+                io::copy(
+                    &mut (&mut decoder).take(offset - decompressed),
+                    &mut io::sink(),
+                )?;
+                decoder.read_exact(buf)
+            }
+        }
+    }
+}
+
+/// Whether the file at `path` was last modified before the file at `other`
+fn is_older(path: &Path, other: &Path) -> bool {
+    let modified = |p: &Path| p.metadata().and_then(|m| m.modified()).ok();
+    matches!((modified(path), modified(other)), (Some(a), Some(b)) if a < b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, write};
+    use tempfile::tempdir;
+
+    const FASTA: &str =
+        ">chr1 first\nACGTA\nCGTAC\nGT\n>chr2\nNNNN\nAC\n>empty\n>chr3\r\nAAAA\r\nCC\r\n";
+
+    #[test]
+    fn index_matches_samtools() {
+        let obs = FaiIndex::build(FASTA.as_bytes(), Path::new("test.fa")).unwrap();
+        let mut written = vec![];
+        obs.write(&mut written).unwrap();
+        let exp = "chr1\t12\t12\t5\t6\nchr2\t6\t33\t4\t5\nempty\t0\t48\t0\t0\nchr3\t6\t55\t4\t6\n";
+
+        assert_eq!(String::from_utf8(written).unwrap(), exp);
+    }
+
+    #[test]
+    fn different_line_lengths_are_err() {
+        let obs = FaiIndex::build(&b">chr1\nACG\nACGT\n"[..], Path::new("test.fa"));
+
+        assert!(matches!(
+            obs,
+            Err(FaidxError::DifferentLineLengths { name, .. }) if name == "chr1"
+        ));
+    }
+
+    #[test]
+    fn duplicate_names_are_err() {
+        let obs = FaiIndex::build(&b">chr1\nACGT\n>chr1 again\nAC\n"[..], Path::new("test.fa"));
+
+        assert!(matches!(obs, Err(FaidxError::DuplicateName { .. })));
+    }
+
+    #[test]
+    fn saved_index_is_read() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("saved.fa");
+        write(&path, FASTA).unwrap();
+        let (exp, gzi) = build_and_save(&path).unwrap();
+        let obs = FaiIndex::read(&index_path(&path, FAI_EXTENSION)).unwrap();
+
+        assert_eq!(obs, exp);
+        assert_eq!(gzi, None);
+    }
+
+    #[test]
+    fn regions_are_fetched_across_lines() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("fetch.fa");
+        write(&path, FASTA).unwrap();
+        let mut fasta = IndexedFasta::open(&path).unwrap();
+
+        assert_eq!(fasta.fetch("chr1", 3, 8).unwrap(), b"TACGT");
+        assert_eq!(fasta.fetch("chr1", 10, 100).unwrap(), b"GT");
+        assert_eq!(fasta.fetch("chr3", 0, 6).unwrap(), b"AAAACC");
+        assert_eq!(fasta.fetch("empty", 0, 10).unwrap(), b"");
+        assert!(fasta.fetch("chrX", 0, 1).is_err());
+    }
+
+    #[test]
+    fn bgzf_regions_match_plain_regions() {
+        // long enough to span several BGZF blocks
+        let seq: String = (0..300_000)
+            .map(|i| ['A', 'C', 'G', 'T', 'N'][i % 7 % 5])
+            .collect();
+        let lines: Vec<&str> = seq
+            .as_bytes()
+            .chunks(70)
+            .map(|l| std::str::from_utf8(l).unwrap())
+            .collect();
+        let content = format!(">chr1\n{}\n>chr2\nACGT\n", lines.join("\n"));
+
+        let dir = tempdir().unwrap();
+        let plain = dir.path().join("plain.fa");
+        let bgzf = dir.path().join("compressed.fa.bgz");
+        write(&plain, &content).unwrap();
+        let mut sink = OutputSink::create(Some(&bgzf), None, None).unwrap();
+        sink.write_all(content.as_bytes()).unwrap();
+        sink.finish().unwrap();
+
+        let mut exp = IndexedFasta::open(&plain).unwrap();
+        let mut obs = IndexedFasta::open(&bgzf).unwrap();
+        let gzi = GziIndex::read(&index_path(&bgzf, GZI_EXTENSION)).unwrap();
+        let fai = read_to_string(index_path(&bgzf, FAI_EXTENSION)).unwrap();
+
+        assert!(gzi.blocks.len() > 1);
+        assert_eq!(obs.index(), exp.index());
+        assert!(fai.starts_with("chr1\t300000\t6\t70\t71\n"));
+        for (name, start, end) in [("chr1", 0, 10), ("chr1", 65_000, 135_000), ("chr2", 1, 3)] {
+            assert_eq!(
+                obs.fetch(name, start, end).unwrap(),
+                exp.fetch(name, start, end).unwrap()
+            );
+        }
+        assert_eq!(
+            obs.fetch("chr1", 65_000, 135_000).unwrap(),
+            &seq.as_bytes()[65_000..135_000]
+        );
+    }
+
+    #[test]
+    fn gzip_cannot_be_indexed() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plain-gzip.fa.gz");
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(FASTA.as_bytes()).unwrap();
+        write(&path, encoder.finish().unwrap()).unwrap();
+        let obs = indexable_compression(&path);
+
+        assert!(matches!(
+            obs,
+            Err(FaidxError::Compressed {
+                compression: Compression::Gzip,
+                ..
+            })
+        ));
+    }
+}
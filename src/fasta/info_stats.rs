@@ -0,0 +1,473 @@
+//! Assembly statistics for a FASTA file.
+
+use crate::{
+    cli::CliOpt,
+    error::BjtError,
+    record::stats::{
+        is_zero, merge_counts, serialize_length_distribution, LengthSummary, MergeStats,
+    },
+    utils::{
+        batch::{expand_paths, BatchReport, SummaryRow},
+        decompress::ThreadOpts,
+        formats::{OutputFormat, OutputFormatError},
+        progress::Progress,
+        Fastx, Hts, HtsFile,
+    },
+};
+use anyhow::{bail, Context};
+use clap::Parser;
+use log::debug;
+use needletail::parse_fastx_reader;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    ops::AddAssign,
+    path::{Path, PathBuf},
+};
+
+/// Default shortest run of `N`s that counts as a gap
+const DEFAULT_MIN_GAP: u64 = 1;
+
+/// Columns of the per-sequence table
+const SEQUENCE_COLUMNS: [&str; 4] = ["name", "length", "gc_fraction", "gaps"];
+
+/// Column with the file path in per-sequence tables of multiple files
+const FILE_COLUMN: &str = "file";
+
+/// CLI options for getting info from a FASTA file
+#[derive(Debug, Parser)]
+pub(crate) struct FastaInfoOpts {
+    /// Get info about these FASTA files (glob patterns are expanded).
+    /// Multiple files are summarized in a table with one row per file.
+    /// Use `-` to read from standard input.
+    #[clap(name = "HTS", required = true)]
+    hts_paths: Vec<PathBuf>,
+
+    /// Format of the input, instead of detecting it from the file extension (required for standard input)
+    #[clap(long, value_name = "FORMAT")]
+    input_format: Option<Hts>,
+
+    /// List the name, length, GC content, and number of gaps of every sequence.
+    /// Tabular formats show only this list, with one row per sequence.
+    #[clap(short = 's', long)]
+    per_sequence: bool,
+
+    /// Shortest run of `N`s to count as a gap
+    #[clap(long, value_name = "N", default_value_t = DEFAULT_MIN_GAP)]
+    min_gap: u64,
+
+    /// Output format to return statistics in
+    #[clap(short = 'f', long, default_value = "human")]
+    format: OutputFormat,
+
+    /// Process N files in parallel [default: the number of CPUs]
+    #[clap(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    #[clap(flatten)]
+    threads: ThreadOpts,
+}
+
+impl FastaInfoOpts {
+    /// Get information and statistics about a single file
+    fn calc_file_info(&self, path: &Path) -> anyhow::Result<FastaStats> {
+        let hts = HtsFile::with_format(path, self.input_format)?;
+        debug!("Reading {} as {}", path.display(), hts.filetype());
+        match hts.filetype() {
+            Hts::Fastx(Fastx::Fasta) => self.calc_fasta_info(hts),
+            _ => bail!(BjtError::WrongFormat {
+                path: path.to_path_buf(),
+                expected: "FASTA",
+            }),
+        }
+    }
+
+    /// Get information and statistics about a desired FASTA file
+    fn calc_fasta_info(&self, hts: HtsFile) -> anyhow::Result<FastaStats> {
+        let mut stats = FastaStats::new();
+        let progress = Progress::reading(&hts);
+        let mut reader =
+            parse_fastx_reader(hts.decompressed_reader(self.threads.n_threads(), &progress)?)
+                .with_context(|| format!("Error opening HTS file {}", hts.path().display()))?;
+
+        while let Some(record) = reader.next() {
+            match record {
+                Ok(seq) => stats.process_sequence(seq.id(), &seq.seq(), self),
+                Err(_) => stats.invalid_records += 1,
+            }
+            progress.record();
+        }
+        progress.finish(&hts);
+
+        Ok(stats)
+    }
+}
+
+impl CliOpt for FastaInfoOpts {
+    fn exec(&self) -> anyhow::Result<()> {
+        let paths = expand_paths(&self.hts_paths)?;
+        let per_sequence_table = self.per_sequence && self.format.is_tabular();
+
+        // a single file gets a detailed report
+        if let [path] = paths.as_slice() {
+            let stats = self.calc_file_info(path)?;
+            let rendered = if per_sequence_table {
+                render_sequence_table(&self.format, &[(path, &stats)], false)?
+            } else {
+                self.format.render(&stats)?
+            };
+            print!("{}", rendered);
+
+            return Ok(());
+        }
+
+        let batch = BatchReport::process(&paths, self.jobs, |path| self.calc_file_info(path))?;
+        let rendered = if per_sequence_table {
+            let files: Vec<(&Path, &FastaStats)> = batch.file_stats().collect();
+            render_sequence_table(&self.format, &files, true)?
+        } else {
+            batch.render(&self.format)?
+        };
+        print!("{}", rendered);
+
+        Ok(batch.check()?)
+    }
+}
+
+/// Render one row per sequence, optionally labelled with the file it came from
+fn render_sequence_table(
+    format: &OutputFormat,
+    files: &[(&Path, &FastaStats)],
+    with_file: bool,
+) -> Result<String, OutputFormatError> {
+    let mut header: Vec<&str> = vec![];
+    if with_file {
+        header.push(FILE_COLUMN);
+    }
+    header.extend(SEQUENCE_COLUMNS);
+
+    let mut rows: Vec<Vec<String>> = vec![];
+    for (path, stats) in files {
+        for seq in &stats.per_sequence {
+            let mut row = vec![];
+            if with_file {
+                row.push(path.display().to_string());
+            }
+            row.extend([
+                seq.name.clone(),
+                seq.length.to_string(),
+                seq.gc_fraction.map(|gc| gc.to_string()).unwrap_or_default(),
+                seq.gaps.to_string(),
+            ]);
+            rows.push(row);
+        }
+    }
+
+    format.render_table(&header, &rows)
+}
+
+/// Base content and gaps of one or more sequences
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SequenceContent {
+    /// Number of G or C bases
+    gc: u64,
+
+    /// Number of A or T bases
+    at: u64,
+
+    /// Number of lowercase (soft-masked) bases
+    soft_masked: u64,
+
+    /// Total number of bases
+    bases: u64,
+
+    /// Number of runs of `N`s at least as long as the minimum gap length
+    gaps: u64,
+
+    /// Total length of all gaps
+    gap_bases: u64,
+}
+
+impl SequenceContent {
+    /// Tally the content of a single sequence, counting runs of at least `min_gap` `N`s as gaps
+    pub fn from_seq(seq: &[u8], min_gap: u64) -> Self {
+        let mut content = Self {
+            bases: seq.len() as u64,
+            ..Self::default()
+        };
+
+        let mut run = 0;
+        for base in seq {
+            match base {
+                b'G' | b'C' | b'g' | b'c' => content.gc += 1,
+                b'A' | b'T' | b'a' | b't' => content.at += 1,
+                _ => {}
+            }
+            if base.is_ascii_lowercase() {
+                content.soft_masked += 1;
+            }
+            if matches!(base, b'N' | b'n') {
+                run += 1;
+            } else {
+                content.end_run(run, min_gap);
+                run = 0;
+            }
+        }
+        content.end_run(run, min_gap);
+
+        content
+    }
+
+    /// Count a finished run of `N`s as a gap if it's long enough
+    fn end_run(&mut self, run: u64, min_gap: u64) {
+        if run > 0 && run >= min_gap {
+            self.gaps += 1;
+            self.gap_bases += run;
+        }
+    }
+
+    /// Fraction of unambiguous bases that are G or C
+    pub fn gc_fraction(&self) -> Option<f64> {
+        match self.gc + self.at {
+            0 => None,
+            acgt => Some(self.gc as f64 / acgt as f64),
+        }
+    }
+
+    /// Fraction of all bases that are soft-masked
+    pub fn soft_masked_fraction(&self) -> Option<f64> {
+        match self.bases {
+            0 => None,
+            bases => Some(self.soft_masked as f64 / bases as f64),
+        }
+    }
+}
+
+impl AddAssign for SequenceContent {
+    fn add_assign(&mut self, rhs: Self) {
+        self.gc += rhs.gc;
+        self.at += rhs.at;
+        self.soft_masked += rhs.soft_masked;
+        self.bases += rhs.bases;
+        self.gaps += rhs.gaps;
+        self.gap_bases += rhs.gap_bases;
+    }
+}
+
+impl Serialize for SequenceContent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(gc) = self.gc_fraction() {
+            map.serialize_entry("gc_fraction", &gc)?;
+        }
+        if let Some(masked) = self.soft_masked_fraction() {
+            map.serialize_entry("soft_masked_fraction", &masked)?;
+        }
+        map.serialize_entry("gaps", &self.gaps)?;
+        map.serialize_entry("gap_bases", &self.gap_bases)?;
+        map.end()
+    }
+}
+
+/// Summary of a single sequence in a FASTA file
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SequenceRow {
+    /// Sequence name, up to the first whitespace
+    name: String,
+
+    /// Length of the sequence
+    length: u64,
+
+    /// Fraction of unambiguous bases that are G or C
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gc_fraction: Option<f64>,
+
+    /// Number of gaps in the sequence
+    gaps: u64,
+}
+
+/// Statistics from a FASTA file
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct FastaStats {
+    /// Total number of sequences
+    sequences: u64,
+
+    /// Total number of invalid records
+    #[serde(skip_serializing_if = "is_zero")]
+    invalid_records: u64,
+
+    /// Total number of bases in a file
+    bases: u64,
+
+    /// Length distribution of sequences
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_length_distribution"
+    )]
+    lengths: HashMap<u64, u64>,
+
+    /// GC content, soft-masking, and gaps over all sequences
+    #[serde(flatten)]
+    content: SequenceContent,
+
+    /// Summary of each sequence, in the order they appear
+    ///
+    /// These stay with the statistics of the file they came from, and aren't merged into combined totals.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    per_sequence: Vec<SequenceRow>,
+}
+
+impl FastaStats {
+    /// Create a new set of statistics for a FASTA file
+    fn new() -> Self {
+        Self {
+            sequences: 0,
+            invalid_records: 0,
+            bases: 0,
+            lengths: HashMap::new(),
+            content: SequenceContent::default(),
+            per_sequence: vec![],
+        }
+    }
+
+    /// Process the statistics for a single sequence
+    fn process_sequence(&mut self, id: &[u8], seq: &[u8], opts: &FastaInfoOpts) {
+        let length = seq.len() as u64;
+        let content = SequenceContent::from_seq(seq, opts.min_gap);
+
+        self.sequences += 1;
+        self.bases += length;
+        *self.lengths.entry(length).or_insert(0) += 1;
+        self.content += content;
+
+        if opts.per_sequence {
+            let name = id
+                .split(|b| b.is_ascii_whitespace())
+                .next()
+                .unwrap_or_default();
+            self.per_sequence.push(SequenceRow {
+                name: String::from_utf8_lossy(name).into_owned(),
+                length,
+                gc_fraction: content.gc_fraction(),
+                gaps: content.gaps,
+            });
+        }
+    }
+
+    /// Summarize the distribution of sequence lengths
+    fn length_summary(&self) -> Option<LengthSummary> {
+        LengthSummary::from_histogram(&self.lengths)
+    }
+}
+
+impl MergeStats for FastaStats {
+    fn merge(&mut self, other: &Self) {
+        self.sequences += other.sequences;
+        self.invalid_records += other.invalid_records;
+        self.bases += other.bases;
+        merge_counts(&mut self.lengths, &other.lengths);
+        self.content += other.content;
+    }
+}
+
+impl SummaryRow for FastaStats {
+    fn summary_row(&self) -> Vec<(&'static str, String)> {
+        let mut row = vec![
+            ("sequences", self.sequences.to_string()),
+            ("bases", self.bases.to_string()),
+        ];
+
+        if let Some(summary) = self.length_summary() {
+            row.extend(summary.summary_row());
+            row.push(("n90", summary.n90.to_string()));
+            row.push(("l50", summary.l50.to_string()));
+        }
+        if let Some(gc) = self.content.gc_fraction() {
+            row.push(("gc_fraction", gc.to_string()));
+        }
+        if let Some(masked) = self.content.soft_masked_fraction() {
+            row.push(("soft_masked_fraction", masked.to_string()));
+        }
+        row.push(("gaps", self.content.gaps.to_string()));
+        row.push(("gap_bases", self.content.gap_bases.to_string()));
+        if self.invalid_records > 0 {
+            row.push(("invalid_records", self.invalid_records.to_string()));
+        }
+
+        row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    const ASSEMBLY: &str = ">chr1 first contig\nACGTacgtNN\nNNGGCC\n>chr2\nAAAAnAAAA\n>chr3\nGC\n";
+
+    /// Calculate statistics for a FASTA file with the given content
+    fn calc(content: &str, args: &[&str]) -> FastaStats {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("assembly.fa");
+        write(&path, content).unwrap();
+        let opts = FastaInfoOpts::parse_from(["fasta", "-"].iter().chain(args));
+
+        opts.calc_file_info(&path).unwrap()
+    }
+
+    #[test]
+    fn assembly_statistics() {
+        let obs = calc(ASSEMBLY, &[]);
+        let summary = obs.length_summary().unwrap();
+
+        assert_eq!(obs.sequences, 3);
+        assert_eq!(obs.bases, 27);
+        assert_eq!((summary.n50, summary.n90, summary.l50), (16, 9, 1));
+        assert_eq!(obs.content.gc_fraction(), Some(10.0 / 22.0));
+        assert_eq!(obs.content.soft_masked_fraction(), Some(5.0 / 27.0));
+        assert_eq!((obs.content.gaps, obs.content.gap_bases), (2, 5));
+        assert!(obs.per_sequence.is_empty());
+    }
+
+    #[test]
+    fn short_runs_are_not_gaps() {
+        let obs = calc(ASSEMBLY, &["--min-gap", "2"]);
+
+        assert_eq!((obs.content.gaps, obs.content.gap_bases), (1, 4));
+    }
+
+    #[test]
+    fn per_sequence_rows() {
+        let obs = calc(ASSEMBLY, &["--per-sequence"]);
+        let names: Vec<&str> = obs.per_sequence.iter().map(|s| s.name.as_str()).collect();
+
+        assert_eq!(names, ["chr1", "chr2", "chr3"]);
+        assert_eq!(obs.per_sequence[0].length, 16);
+        assert_eq!(obs.per_sequence[0].gc_fraction, Some(8.0 / 12.0));
+        assert_eq!(obs.per_sequence[1].gaps, 1);
+        assert_eq!(obs.per_sequence[2].gaps, 0);
+    }
+
+    #[test]
+    fn merged_files_match_combined_file() {
+        let (head, tail) = ASSEMBLY.split_at(ASSEMBLY.find(">chr3").unwrap());
+        let mut obs = FastaStats::default();
+        obs.merge(&calc(head, &["-s"]));
+        obs.merge(&calc(tail, &["-s"]));
+        let exp = calc(ASSEMBLY, &[]);
+
+        assert_eq!(
+            serde_json::to_value(&obs).unwrap(),
+            serde_json::to_value(&exp).unwrap()
+        );
+    }
+
+    #[test]
+    fn fastq_is_wrong_format() {
+        let opts = FastaInfoOpts::parse_from(["fasta", "-"]);
+        let obs = opts.calc_file_info(Path::new("examples/SRR0000001.fastq"));
+
+        assert!(obs.is_err());
+    }
+}
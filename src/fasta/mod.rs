@@ -0,0 +1,7 @@
+//! Process reference genome and assembly [FASTA](https://en.wikipedia.org/wiki/FASTA_format) files.
+
+pub mod faidx;
+pub mod filter;
+pub mod genome;
+pub mod index;
+pub mod info_stats;
@@ -0,0 +1,335 @@
+//! Nucleotide composition and GC content of FASTQ records.
+
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use std::ops::AddAssign;
+
+/// Number of bins in the per-read GC content histogram (0% to 100%, inclusive)
+const N_GC_BINS: usize = 101;
+
+/// Counts of each nucleotide at a single cycle
+///
+/// Lowercase bases are counted with their uppercase equivalents, and any ambiguous base is counted as `N`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BaseCounts {
+    pub a: u64,
+    pub c: u64,
+    pub g: u64,
+    pub t: u64,
+    pub n: u64,
+}
+
+impl BaseCounts {
+    /// Tally a single base
+    fn update(&mut self, base: u8) {
+        match base {
+            b'A' | b'a' => self.a += 1,
+            b'C' | b'c' => self.c += 1,
+            b'G' | b'g' => self.g += 1,
+            b'T' | b't' => self.t += 1,
+            _ => self.n += 1,
+        }
+    }
+
+    /// Number of unambiguous bases
+    fn acgt(&self) -> u64 {
+        self.a + self.c + self.g + self.t
+    }
+
+    /// Number of G or C bases
+    fn gc(&self) -> u64 {
+        self.g + self.c
+    }
+
+    /// Total number of bases
+    fn total(&self) -> u64 {
+        self.acgt() + self.n
+    }
+}
+
+impl AddAssign for BaseCounts {
+    fn add_assign(&mut self, rhs: Self) {
+        self.a += rhs.a;
+        self.c += rhs.c;
+        self.g += rhs.g;
+        self.t += rhs.t;
+        self.n += rhs.n;
+    }
+}
+
+/// Nucleotide composition at each cycle and GC content of each read
+#[derive(Debug, Clone)]
+pub struct BaseComposition {
+    /// Nucleotide counts for each cycle
+    cycles: Vec<BaseCounts>,
+
+    /// Nucleotide counts over every base of every read, including cycles past the ones tracked
+    totals: BaseCounts,
+
+    /// Number of reads in each GC percentage bin
+    gc_histogram: [u64; N_GC_BINS],
+}
+
+/// Raw tallies of a [`BaseComposition`], as saved in a stats file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BaseCompositionState {
+    cycles: Vec<BaseCounts>,
+    totals: BaseCounts,
+
+    /// Number of reads with each GC percentage, from 0% to 100%
+    gc_histogram: Vec<u64>,
+}
+
+impl From<&BaseComposition> for BaseCompositionState {
+    fn from(comp: &BaseComposition) -> Self {
+        Self {
+            cycles: comp.cycles.clone(),
+            totals: comp.totals,
+            gc_histogram: comp.gc_histogram.to_vec(),
+        }
+    }
+}
+
+impl TryFrom<BaseCompositionState> for BaseComposition {
+    type Error = &'static str;
+
+    fn try_from(state: BaseCompositionState) -> Result<Self, Self::Error> {
+        let gc_histogram = state.gc_histogram.try_into().map_err(|_| {
+            "The GC content histogram must have a count for every percentage from 0% to 100%."
+        })?;
+
+        Ok(Self {
+            cycles: state.cycles,
+            totals: state.totals,
+            gc_histogram,
+        })
+    }
+}
+
+impl Default for BaseComposition {
+    fn default() -> Self {
+        Self {
+            cycles: vec![],
+            totals: BaseCounts::default(),
+            gc_histogram: [0; N_GC_BINS],
+        }
+    }
+}
+
+impl BaseComposition {
+    /// Create a new, empty nucleotide composition
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tally the bases of a single record, at up to the first `max_cycles` cycles
+    ///
+    /// The overall and per-read GC content are calculated from all of its bases.
+    pub fn update(&mut self, seq: &[u8], max_cycles: usize) {
+        let n_cycles = seq.len().min(max_cycles);
+        if self.cycles.len() < n_cycles {
+            self.cycles.resize(n_cycles, BaseCounts::default());
+        }
+
+        let mut read_counts = BaseCounts::default();
+        for (counts, base) in self.cycles.iter_mut().zip(seq) {
+            counts.update(*base);
+        }
+        for base in seq {
+            read_counts.update(*base);
+        }
+        self.totals += read_counts;
+
+        // reads made entirely of ambiguous bases have no meaningful GC content
+        if let Some(bin) = gc_bin(&read_counts) {
+            self.gc_histogram[bin] += 1;
+        }
+    }
+
+    /// Add the tallies from another composition into this one
+    pub fn merge(&mut self, other: &Self) {
+        if self.cycles.len() < other.cycles.len() {
+            self.cycles
+                .resize(other.cycles.len(), BaseCounts::default());
+        }
+
+        for (counts, other_counts) in self.cycles.iter_mut().zip(&other.cycles) {
+            *counts += *other_counts;
+        }
+        self.totals += other.totals;
+        for (n, other_n) in self.gc_histogram.iter_mut().zip(&other.gc_histogram) {
+            *n += other_n;
+        }
+    }
+
+    /// Nucleotide counts over every base of every read
+    pub fn totals(&self) -> BaseCounts {
+        self.totals
+    }
+
+    /// Fraction of unambiguous bases that are G or C
+    pub fn gc_fraction(&self) -> Option<f64> {
+        let totals = self.totals();
+        match totals.acgt() {
+            0 => None,
+            acgt => Some(totals.gc() as f64 / acgt as f64),
+        }
+    }
+
+    /// Fraction of all bases that are ambiguous
+    pub fn n_rate(&self) -> Option<f64> {
+        let totals = self.totals();
+        match totals.total() {
+            0 => None,
+            total => Some(totals.n as f64 / total as f64),
+        }
+    }
+}
+
+/// GC percentage bin for a read, rounded to the nearest percent
+fn gc_bin(counts: &BaseCounts) -> Option<usize> {
+    match counts.acgt() {
+        0 => None,
+        acgt => Some((100.0 * counts.gc() as f64 / acgt as f64).round() as usize),
+    }
+}
+
+/// Serialize only the non-empty bins of the GC histogram, keyed by GC percentage
+struct GcHistogram<'a>(&'a [u64; N_GC_BINS]);
+
+impl Serialize for GcHistogram<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        for (pct, n) in self.0.iter().enumerate().filter(|(_, n)| **n > 0) {
+            map.serialize_entry(&pct.to_string(), n)?;
+        }
+        map.end()
+    }
+}
+
+/// Serialize the nucleotide counts of each cycle, keyed by the 1-based cycle number
+struct Cycles<'a>(&'a [BaseCounts]);
+
+impl Serialize for Cycles<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (cycle, counts) in self.0.iter().enumerate() {
+            map.serialize_entry(&(cycle + 1).to_string(), counts)?;
+        }
+        map.end()
+    }
+}
+
+impl Serialize for BaseComposition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(gc) = self.gc_fraction() {
+            map.serialize_entry("gc_fraction", &gc)?;
+        }
+        if let Some(n_rate) = self.n_rate() {
+            map.serialize_entry("n_rate", &n_rate)?;
+        }
+        map.serialize_entry("gc_histogram", &GcHistogram(&self.gc_histogram))?;
+        map.serialize_entry("cycles", &Cycles(&self.cycles))?;
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fastq::quality::DEFAULT_MAX_CYCLES;
+
+    fn composition(seqs: &[&str]) -> BaseComposition {
+        let mut comp = BaseComposition::new();
+        for s in seqs {
+            comp.update(s.as_bytes(), DEFAULT_MAX_CYCLES);
+        }
+
+        comp
+    }
+
+    #[test]
+    fn empty_composition_has_no_fractions() {
+        let obs = BaseComposition::new();
+
+        assert_eq!(obs.gc_fraction(), None);
+        assert_eq!(obs.n_rate(), None);
+    }
+
+    #[test]
+    fn cycles_stop_at_limit() {
+        let mut obs = BaseComposition::new();
+        obs.update(b"AAGG", 2);
+
+        assert_eq!(obs.cycles.len(), 2);
+        assert_eq!(obs.gc_histogram[50], 1);
+    }
+
+    #[test]
+    fn totals_include_bases_past_limit() {
+        let seq = format!("{}{}", "A".repeat(1000), "G".repeat(1000));
+        let mut obs = BaseComposition::new();
+        obs.update(seq.as_bytes(), DEFAULT_MAX_CYCLES);
+
+        assert_eq!(obs.cycles.len(), DEFAULT_MAX_CYCLES);
+        assert_eq!(obs.totals().total(), 2000);
+        assert_eq!(obs.gc_fraction(), Some(0.5));
+        assert_eq!(obs.gc_histogram[50], 1);
+    }
+
+    #[test]
+    fn per_cycle_counts() {
+        let obs = composition(&["ACGT", "AcgN", "T"]);
+
+        let exp_first = BaseCounts {
+            a: 2,
+            t: 1,
+            ..Default::default()
+        };
+        let exp_last = BaseCounts {
+            t: 1,
+            n: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(obs.cycles.first(), Some(&exp_first));
+        assert_eq!(obs.cycles.get(3), Some(&exp_last));
+        assert_eq!(obs.cycles.get(4), None);
+    }
+
+    #[test]
+    fn merged_compositions_equal_combined_composition() {
+        let mut obs = composition(&["ACGT", "GG"]);
+        obs.merge(&composition(&["NNNNN", "AT"]));
+        let exp = composition(&["ACGT", "GG", "NNNNN", "AT"]);
+
+        assert_eq!(obs.cycles, exp.cycles);
+        assert_eq!(obs.totals, exp.totals);
+        assert_eq!(obs.gc_histogram, exp.gc_histogram);
+    }
+
+    #[test]
+    fn ambiguous_bases_count_as_n() {
+        let obs = composition(&["RYKM"]).totals();
+
+        assert_eq!(obs.n, 4);
+    }
+
+    #[test]
+    fn gc_fraction_excludes_n() {
+        let obs = composition(&["GCAN"]);
+
+        assert_eq!(obs.gc_fraction(), Some(2.0 / 3.0));
+        assert_eq!(obs.n_rate(), Some(0.25));
+    }
+
+    #[test]
+    fn gc_histogram_bins_reads() {
+        let obs = composition(&["GGCC", "GCAT", "ATAT", "NNNN"]);
+
+        assert_eq!(obs.gc_histogram[100], 1);
+        assert_eq!(obs.gc_histogram[50], 1);
+        assert_eq!(obs.gc_histogram[0], 1);
+        assert_eq!(obs.gc_histogram.iter().sum::<u64>(), 3);
+    }
+}
@@ -0,0 +1,251 @@
+//! Detect how quality scores are encoded in a FASTQ file.
+
+use super::quality::PHRED33_OFFSET;
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use std::fmt::Display;
+
+/// ASCII offset for Phred+64 and Solexa+64 encoded quality scores
+pub const PHRED64_OFFSET: u8 = 64;
+
+/// Lowest quality character of Solexa+64 encoding (`;`, a Solexa score of -5)
+const SOLEXA_MIN_ASCII: u8 = 59;
+
+/// Highest quality character of Illumina >= v1.8 Phred+33 encoding (`K`, a score of 42)
+const ILLUMINA_PHRED33_MAX_ASCII: u8 = 75;
+
+/// Highest quality character of Illumina v1.3 to v1.7 Phred+64 encoding (`i`, a score of 41)
+const ILLUMINA_PHRED64_MAX_ASCII: u8 = 105;
+
+/// Encoding schemes for quality scores in FASTQ files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PhredEncoding {
+    /// Sanger and Illumina >= v1.8
+    #[serde(rename = "phred+33")]
+    Phred33,
+
+    /// Illumina v1.3 to v1.7
+    #[serde(rename = "phred+64")]
+    Phred64,
+
+    /// Solexa and Illumina < v1.3, whose scores are not Phred scores
+    #[serde(rename = "solexa+64")]
+    Solexa64,
+
+    /// Quality characters that fit both Phred+33 and Phred+64, read as Phred+33
+    #[serde(rename = "ambiguous")]
+    Ambiguous,
+}
+
+impl PhredEncoding {
+    /// ASCII offset to subtract from a quality character to get its score
+    pub fn offset(&self) -> u8 {
+        match self {
+            Self::Phred33 | Self::Ambiguous => PHRED33_OFFSET,
+            Self::Phred64 | Self::Solexa64 => PHRED64_OFFSET,
+        }
+    }
+
+    /// Determine the encoding from the range of quality characters observed
+    ///
+    /// Only Phred+33 can contain characters below `;`, and a highest character of `K` or below means Phred+33,
+    /// since Phred+64 files would otherwise never exceed a score of 11.
+    /// Above that, characters between `;` and `?` only occur in Solexa+64, and characters past `i` only occur in
+    /// long-read Phred+33 files.
+    /// The remaining ranges are called as Phred+64 when the lowest score would be 10 or below in Phred+64, and
+    /// ambiguous when every score would be above 41 in Phred+33 and above 10 in Phred+64.
+    pub fn from_qual_range(min_qual: u8, max_qual: u8) -> Self {
+        if min_qual < SOLEXA_MIN_ASCII || max_qual <= ILLUMINA_PHRED33_MAX_ASCII {
+            Self::Phred33
+        } else if min_qual < PHRED64_OFFSET {
+            Self::Solexa64
+        } else if max_qual > ILLUMINA_PHRED64_MAX_ASCII {
+            Self::Phred33
+        } else if min_qual < ILLUMINA_PHRED33_MAX_ASCII {
+            Self::Phred64
+        } else {
+            Self::Ambiguous
+        }
+    }
+}
+
+impl Display for PhredEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Phred33 => "phred+33",
+            Self::Phred64 => "phred+64",
+            Self::Solexa64 => "solexa+64",
+            Self::Ambiguous => "ambiguous",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Range of quality characters observed in every record of a file
+///
+/// Every record is scanned, so the scans of separate chunks of a file merge into the scan of the whole file.
+#[derive(Debug, Clone, Default)]
+pub struct EncodingScan {
+    /// Number of records scanned
+    n_scanned: u64,
+
+    /// Lowest quality character observed
+    min_qual: Option<u8>,
+
+    /// Highest quality character observed
+    max_qual: Option<u8>,
+}
+
+/// Raw range of an [`EncodingScan`], as saved in a stats file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EncodingScanState {
+    n_scanned: u64,
+    min_qual: Option<u8>,
+    max_qual: Option<u8>,
+}
+
+impl From<&EncodingScan> for EncodingScanState {
+    fn from(scan: &EncodingScan) -> Self {
+        Self {
+            n_scanned: scan.n_scanned,
+            min_qual: scan.min_qual,
+            max_qual: scan.max_qual,
+        }
+    }
+}
+
+impl From<EncodingScanState> for EncodingScan {
+    fn from(state: EncodingScanState) -> Self {
+        Self {
+            n_scanned: state.n_scanned,
+            min_qual: state.min_qual,
+            max_qual: state.max_qual,
+        }
+    }
+}
+
+impl EncodingScan {
+    /// Create a new, empty scan
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include the quality characters of a record
+    pub fn update(&mut self, qual: &[u8]) {
+        self.n_scanned += 1;
+
+        if let Some(min) = qual.iter().min() {
+            self.min_qual = Some(self.min_qual.map_or(*min, |m| m.min(*min)));
+        }
+        if let Some(max) = qual.iter().max() {
+            self.max_qual = Some(self.max_qual.map_or(*max, |m| m.max(*max)));
+        }
+    }
+
+    /// Combine the range of quality characters observed in another scan with this one
+    pub fn merge(&mut self, other: &Self) {
+        self.n_scanned += other.n_scanned;
+        self.min_qual = match (self.min_qual, other.min_qual) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max_qual = match (self.max_qual, other.max_qual) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// Whether any quality characters have been observed
+    pub fn is_empty(&self) -> bool {
+        self.min_qual.is_none()
+    }
+
+    /// The detected encoding, if any quality characters have been observed
+    pub fn encoding(&self) -> Option<PhredEncoding> {
+        self.min_qual
+            .zip(self.max_qual)
+            .map(|(min, max)| PhredEncoding::from_qual_range(min, max))
+    }
+}
+
+impl Serialize for EncodingScan {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        if let Some(encoding) = self.encoding() {
+            map.serialize_entry("encoding", &encoding)?;
+        }
+        if let Some(min) = self.min_qual {
+            map.serialize_entry("min_quality_char", &(min as char))?;
+        }
+        if let Some(max) = self.max_qual {
+            map.serialize_entry("max_quality_char", &(max as char))?;
+        }
+        map.serialize_entry("records_scanned", &self.n_scanned)?;
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[track_caller]
+    fn check_encoding(quals: &[&str], exp: Option<PhredEncoding>) {
+        let mut scan = EncodingScan::new();
+        for q in quals {
+            scan.update(q.as_bytes());
+        }
+
+        assert_eq!(scan.encoding(), exp);
+    }
+
+    #[test]
+    fn empty_scan_has_no_encoding() {
+        check_encoding(&[], None);
+    }
+
+    #[test]
+    fn sanger_is_phred33() {
+        check_encoding(&["IIIII", "#AAAJ"], Some(PhredEncoding::Phred33));
+    }
+
+    #[test]
+    fn illumina_1_5_is_phred64() {
+        check_encoding(&["hhhhh", "BBBhh"], Some(PhredEncoding::Phred64));
+    }
+
+    #[test]
+    fn solexa_is_solexa64() {
+        check_encoding(&["hhhhh", ";;@hh"], Some(PhredEncoding::Solexa64));
+    }
+
+    #[test]
+    fn high_quality_phred33_is_phred33() {
+        check_encoding(&["IIIII", "IIIII"], Some(PhredEncoding::Phred33));
+        check_encoding(&["?@IJK", "JJJJJ"], Some(PhredEncoding::Phred33));
+    }
+
+    #[test]
+    fn long_read_phred33_is_phred33() {
+        check_encoding(&["@@~~~", "hhhhh"], Some(PhredEncoding::Phred33));
+    }
+
+    #[test]
+    fn overlapping_range_is_ambiguous() {
+        check_encoding(&["LLLLL", "hhhhh"], Some(PhredEncoding::Ambiguous));
+        assert_eq!(PhredEncoding::Ambiguous.offset(), PHRED33_OFFSET);
+    }
+
+    #[test]
+    fn merged_scans_equal_combined_scan() {
+        let mut obs = EncodingScan::new();
+        obs.update(b"BBhh");
+        let mut other = EncodingScan::new();
+        other.update(b"!!II");
+        obs.merge(&other);
+
+        assert_eq!(obs.n_scanned, 2);
+        assert_eq!(obs.min_qual, Some(b'!'));
+        assert_eq!(obs.max_qual, Some(b'h'));
+    }
+}
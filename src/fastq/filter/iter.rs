@@ -1,115 +1,259 @@
 //! Helper structs and methods for iterating through a FASTQ file and IDs.
+//!
+//! Read names and IDs are compared in natural order, where runs of digits are compared by their numeric value.
+//! This is the order from `sort -V` or `samtools sort -n`, so that `SRR0000001.9` comes before `SRR0000001.10`.
+//!
+//! Mate suffixes (`/1` and `/2`) are ignored on both sides, so an ID matches both reads of a pair, whether or not it has a suffix itself.
 
-use super::FastqFilterError;
-use needletail::{errors::ParseError, parser::SequenceRecord};
+use crate::error::BjtError;
 use std::{
+    cmp::Ordering,
     fs::File,
-    io::{BufReader, Lines},
+    io::{BufRead, BufReader, Lines},
+    path::{Path, PathBuf},
 };
 
-/// Helper struct for iterating through a FASTQ file and IDs.
+/// Hint for sorting an ID file
+const ID_SORT_HINT: &str = "Please sort with `sort -V ids.txt > ids.sorted.txt`.";
+
+/// Hint for sorting a FASTQ file by read name
+const FASTQ_SORT_HINT: &str =
+    "Please sort with `(z)cat | paste - - - - | sort -V -k1,1 | tr \"\\t\" \"\\n\"`.";
+
+/// Helper struct for iterating through a sorted ID file, alongside a sorted FASTQ file.
 pub struct FastqFilterIter {
+    /// Path to the ID file, for error messages
+    path: PathBuf,
+
+    /// Lines of the ID file
+    ids: Lines<BufReader<File>>,
+
+    /// Number of lines read from the ID file so far
+    n_lines: u64,
+
     /// The previous ID that was handled
     prev_id: Option<String>,
 
     /// The current ID being handled
     curr_id: Option<String>,
 
-    /// The ID of the previous record that was recently dealt with
-    prev_record: Option<String>,
+    /// The name of the previous record that was recently dealt with
+    prev_record: Option<Vec<u8>>,
 }
 
-impl Default for FastqFilterIter {
-    fn default() -> Self {
-        Self {
+impl FastqFilterIter {
+    /// Open a sorted ID file, and move to its first ID
+    pub fn new(path: &Path) -> Result<Self, BjtError> {
+        let file = File::open(path).map_err(|source| BjtError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut iter = Self {
+            path: path.to_path_buf(),
+            ids: BufReader::new(file).lines(),
+            n_lines: 0,
             prev_id: None,
             curr_id: None,
             prev_record: None,
+        };
+
+        iter.get_next_id()?;
+        if iter.curr_id.is_none() {
+            return Err(BjtError::EmptyFile {
+                path: path.to_path_buf(),
+                reason: "There are no IDs to filter.",
+            });
         }
-    }
-}
 
-impl FastqFilterIter {
-    /// Create a new iterator object
-    pub fn new() -> Self {
-        Self::default()
+        Ok(iter)
     }
 
     /// Retrieve the previous ID in the filter file
     pub fn prev_filter_id(&self) -> Option<&[u8]> {
-        match &self.prev_id {
-            Some(id) => Some(id.as_bytes()),
-            None => None,
-        }
+        self.prev_id.as_ref().map(|id| id.as_bytes())
     }
 
     /// Retrieve the current ID in the filter file
     pub fn curr_filter_id(&self) -> Option<&[u8]> {
-        match &self.curr_id {
-            Some(id) => Some(id.as_bytes()),
-            None => None,
-        }
+        self.curr_id.as_ref().map(|id| id.as_bytes())
     }
 
-    /// Retrieve the ID of the previous record
+    /// Retrieve the name of the previous record
     pub fn prev_record_id(&self) -> Option<&[u8]> {
-        match &self.prev_record {
-            Some(r) => Some(r.as_bytes()),
-            None => None,
-        }
+        self.prev_record.as_deref()
     }
 
-    /// Update the previous record ID
+    /// Update the previous record name, after checking that it doesn't come before the one before it
+    ///
+    /// Only the name is stored, instead of the whole record, so that the FASTQ reader can move on to the next record.
     pub fn set_prev_record_id(
         &mut self,
-        curr_record: &Option<Result<SequenceRecord, ParseError>>,
-    ) -> Result<(), FastqFilterError> {
-        // Instead of storing the entire record, we are going to only store the ID from the record that was just processed.
-        // To do this, we create a string of the ID from the byte slice that make up the record's ID.
-        // (FASTQ files are ASCII encoded, so from_utf8 should always work).
-        // The ID byte slice is copied to a new `Vec`, but this is less expensive than copying the entire contents of the FASTQ record.
-        // This is coerced into a String, which is owned by `self` and not `fq_reader`.
-        // This avoids having multiple simultaneous mutable references to `fq_reader`.
-        match curr_record {
-            Some(Ok(rec)) => {
-                self.prev_record = match String::from_utf8(rec.id().to_vec()) {
-                    Ok(val) => Some(val),
-                    Err(e) => return Err(FastqFilterError::CannotParseFastqRecordId(e)),
-                };
-
-                Ok(())
+        name: &[u8],
+        path: &Path,
+        record: u64,
+    ) -> Result<(), BjtError> {
+        if let Some(prev) = self.prev_record_id() {
+            if compare_names(name, prev) == Ordering::Less {
+                return Err(BjtError::Unsorted {
+                    path: path.to_path_buf(),
+                    record,
+                    hint: FASTQ_SORT_HINT,
+                });
             }
-            Some(Err(e)) => Err(FastqFilterError::CannotParseFastqRecord(e.clone())),
-            None => Err(FastqFilterError::NoRecordIdFromNone),
         }
+
+        // reuse the buffer from the previous name
+        let prev = self.prev_record.get_or_insert_with(Vec::new);
+        prev.clear();
+        prev.extend_from_slice(name);
+
+        Ok(())
     }
 
-    /// Retrieve the next ID form the ID file
-    pub fn get_next_id(
-        &mut self,
-        id_reader: &mut Lines<BufReader<File>>,
-    ) -> Result<(), FastqFilterError> {
-        self.prev_id.clone_from(&self.curr_id);
-        self.curr_id = match id_reader.next() {
-            Some(Ok(s)) => Some(s),
-            Some(Err(_)) => return Err(FastqFilterError::CannotParseIdFileLine),
-            None => None,
-        };
+    /// Retrieve the next ID from the ID file, skipping blank lines
+    pub fn get_next_id(&mut self) -> Result<(), BjtError> {
+        self.prev_id = self.curr_id.take();
+        for line in self.ids.by_ref() {
+            self.n_lines += 1;
+            let line = line.map_err(|e| BjtError::InvalidLine {
+                path: self.path.clone(),
+                line: self.n_lines,
+                reason: e.to_string(),
+            })?;
+            // only the first word is used, like the read names
+            if let Some(id) = line.split_whitespace().next() {
+                self.curr_id = Some(id.to_string());
+                break;
+            }
+        }
 
         self.assert_ids_are_sorted()
     }
 
+    /// Move through the IDs until reaching a read name, and check if the current ID matches it
+    ///
+    /// The current ID isn't passed if it matches, since later records may match it too (e.g. mates of a pair).
+    pub fn matches(&mut self, name: &[u8]) -> Result<bool, BjtError> {
+        while let Some(id) = self.curr_filter_id() {
+            match compare_names(id, name) {
+                Ordering::Less => self.get_next_id()?,
+                Ordering::Equal => return Ok(true),
+                Ordering::Greater => return Ok(false),
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Whether all the IDs have been passed
+    pub fn is_done(&self) -> bool {
+        self.curr_id.is_none()
+    }
+
     /// Check that the the file IDs are in sorted order
-    fn assert_ids_are_sorted(&self) -> Result<(), FastqFilterError> {
+    ///
+    /// Repeated IDs are allowed, since they don't change which reads are matched.
+    fn assert_ids_are_sorted(&self) -> Result<(), BjtError> {
         match (self.curr_filter_id(), self.prev_filter_id()) {
-            (Some(curr), Some(prev)) => {
-                if curr < prev {
-                    Ok(())
-                } else {
-                    Err(FastqFilterError::IdFileNotSorted)
-                }
+            (Some(curr), Some(prev)) if compare_names(curr, prev) == Ordering::Less => {
+                Err(BjtError::Unsorted {
+                    path: self.path.clone(),
+                    record: self.n_lines,
+                    hint: ID_SORT_HINT,
+                })
             }
             (_, _) => Ok(()),
         }
     }
 }
+
+/// Name of a read, from its header up to the first whitespace, without a `/1` or `/2` mate suffix
+pub fn read_name(id: &[u8]) -> &[u8] {
+    let name = id
+        .split(|b| b.is_ascii_whitespace())
+        .next()
+        .unwrap_or_default();
+
+    name.strip_suffix(b"/1")
+        .or_else(|| name.strip_suffix(b"/2"))
+        .unwrap_or(name)
+}
+
+/// Compare two read names (or IDs) in natural order, ignoring any mate suffixes
+///
+/// Runs of digits are compared by their numeric value, and everything else byte by byte.
+/// Names that only differ by leading zeros are ordered by their bytes, so that different names are never equal.
+pub fn compare_names(a: &[u8], b: &[u8]) -> Ordering {
+    let (a, b) = (read_name(a), read_name(b));
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let a_run = digit_run(&a[i..]);
+            let b_run = digit_run(&b[j..]);
+            let a_num = trim_leading_zeros(a_run);
+            let b_num = trim_leading_zeros(b_run);
+            // a longer number (without leading zeros) is always larger
+            let ord = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            i += a_run.len();
+            j += b_run.len();
+        } else {
+            match a[i].cmp(&b[j]) {
+                Ordering::Equal => {
+                    i += 1;
+                    j += 1;
+                }
+                ord => return ord,
+            }
+        }
+    }
+
+    (a.len() - i).cmp(&(b.len() - j)).then_with(|| a.cmp(b))
+}
+
+/// Leading run of ASCII digits in a name
+fn digit_run(s: &[u8]) -> &[u8] {
+    let end = s
+        .iter()
+        .position(|b| !b.is_ascii_digit())
+        .unwrap_or(s.len());
+
+    &s[..end]
+}
+
+/// Digits without their leading zeros
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let start = digits
+        .iter()
+        .position(|&b| b != b'0')
+        .unwrap_or(digits.len());
+
+    &digits[start..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_in_natural_order() {
+        assert_eq!(
+            compare_names(b"SRR0000001.9", b"SRR0000001.10"),
+            Ordering::Less
+        );
+        assert_eq!(compare_names(b"read10", b"read9a"), Ordering::Greater);
+        assert_eq!(compare_names(b"r1", b"r1.1"), Ordering::Less);
+        assert_eq!(compare_names(b"r01", b"r1"), Ordering::Less);
+    }
+
+    #[test]
+    fn mate_suffixes_and_comments_are_ignored() {
+        assert_eq!(read_name(b"SRR0000001.1 1 length=152"), b"SRR0000001.1");
+        assert_eq!(compare_names(b"r1/1", b"r1/2"), Ordering::Equal);
+        assert_eq!(compare_names(b"r1/2 comment", b"r1"), Ordering::Equal);
+        assert_eq!(compare_names(b"r1/3", b"r1"), Ordering::Greater);
+    }
+}
@@ -2,7 +2,6 @@
 
 pub mod error;
 pub mod iter;
-pub mod old_filter;
 pub mod opts;
 
 pub use error::FastqFilterError;
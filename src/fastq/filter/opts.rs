@@ -1,34 +1,38 @@
 //! Options for filtering records from a FASTQ file.
 
-use crate::{cli::CliOpt, fastq::filter::FastqFilterError, record::filter::RecordFilter};
-use anyhow::bail;
-use clap::Parser;
-use needletail::{
-    errors::ParseError,
-    parser::{FastqReader, SequenceRecord},
-    FastxReader,
+use crate::{
+    cli::CliOpt,
+    error::BjtError,
+    fastq::filter::FastqFilterError,
+    record::{filter::RecordFilter, header::CasavaV1_8Name},
+    utils::{decompress::ThreadOpts, progress::Progress, sink::OutputOpts, Fastx, Hts, HtsFile},
 };
+use anyhow::{bail, Context};
+use clap::Parser;
+use log::info;
+use needletail::{parse_fastx_reader, parser::SequenceRecord, FastxReader};
 use regex::Regex;
-use std::{
-    fs::File,
-    io::{self, BufRead, BufReader},
-    path::PathBuf,
-};
+use std::path::{Path, PathBuf};
 
-use super::iter::FastqFilterIter;
+use super::iter::{read_name, FastqFilterIter};
 
 /// Options for filtering reads from a FASTQ file.
 #[derive(Debug, Parser)]
 pub struct FastqFilterOpts {
-    /// Get info about this HTS file.
+    /// Filter this FASTQ file (use `-` to read from standard input).
     #[clap(name = "HTS")]
     hts_path: PathBuf,
 
+    /// Format of the input, instead of detecting it from the file extension (required for standard input).
+    #[clap(long, value_name = "FORMAT")]
+    input_format: Option<Hts>,
+
     /// Regular expression to match against the read names.
     #[clap(short, long, conflicts_with = "id_list_path")]
     regex: Option<Regex>,
 
-    /// Text file containing all read names to filter.
+    /// Text file containing all read names to filter, one per line.
+    /// Both files must be sorted by read name in natural order (e.g. with `sort -V`), and `/1` and `/2` mate suffixes are ignored.
     #[clap(
         short = 'f',
         long = "id-file",
@@ -37,167 +41,302 @@ pub struct FastqFilterOpts {
     )]
     id_list_path: Option<PathBuf>,
 
-    /// Output file name.
-    #[clap(short, long)]
-    output: Option<PathBuf>,
+    #[clap(flatten)]
+    output: OutputOpts,
 
     /// Keep the records that match, instead of discarding them.
     #[clap(short, long)]
     keep: bool,
+
+    /// Discard reads that failed the chastity filter (`Y` in Casava 1.8 read names).
+    #[clap(long)]
+    drop_filtered: bool,
+
+    #[clap(flatten)]
+    threads: ThreadOpts,
 }
 
 impl CliOpt for FastqFilterOpts {
     fn exec(&self) -> anyhow::Result<()> {
-        match (self.regex.is_some(), self.id_list_path.is_some()) {
-            (true, true) => {
+        match (&self.regex, &self.id_list_path) {
+            (Some(_), Some(_)) => {
                 // this should be excluded by the CLI
                 bail!(FastqFilterError::CannotSpecifyRegexAndIdFile)
             }
-            (true, false) => self.filter_with_id_regex(),
-            (false, true) => self.filter_with_id_file(),
-            (false, false) => bail!(FastqFilterError::FilterCannotBeEmpty),
+            (Some(re), None) => {
+                self.filter_names(|name| re.is_match(&String::from_utf8_lossy(name)) == self.keep)
+            }
+            (None, Some(ids)) => self.filter_with_id_file(ids),
+            (None, None) if self.drop_filtered => self.filter_names(|_| true),
+            (None, None) => bail!(FastqFilterError::FilterCannotBeEmpty),
         }
     }
 }
 
 impl RecordFilter for FastqFilterOpts {
-    fn output(&self) -> Option<&PathBuf> {
-        self.output.as_ref()
+    fn output(&self) -> &OutputOpts {
+        &self.output
     }
 }
 
 impl FastqFilterOpts {
-    /// Filter out records using a sorted ID file to match against.
-    fn filter_with_id_file(&self) -> anyhow::Result<()> {
-        let n_records_processed = 0u32;
-        let mut id_reader = self.get_id_file_lines()?;
-        let mut fq_reader = self.get_hts_reader();
-        let mut filt_iter = FastqFilterIter::new();
+    /// Filter records using a sorted ID file to match against.
+    ///
+    /// The FASTQ file and the ID file are both read once, in step with each other, so neither has to fit in memory.
+    fn filter_with_id_file(&self, ids: &Path) -> anyhow::Result<()> {
+        let mut filt_iter = FastqFilterIter::new(ids)?;
+        let (hts, mut fq_reader, progress) = self.get_hts_reader()?;
         // writer for the output file (or STDOUT)
         let mut writer = self.writer_output()?;
-        let mut deal_with_remaining_reads = false;
-
-        // The current record being processed.
-        // This belongs in this scope instead of inside `filt_iter` because of
-        // mutable references to data owned by `fq_reader`.
-        let mut curr_record = fq_reader.next();
-
-        // this loop iterates over both files simultaneously
-        loop {
-            let mut update_record = false;
-            let mut update_id = false;
-
-            assert_records_are_sorted(&curr_record, filt_iter.prev_record_id())?;
-
-            if let (Some(Ok(rec)), Some(filter_id)) = (curr_record, filt_iter.curr_filter_id()) {
-                if rec.id() < filter_id {
-                    // if the record is before the filtering ID and we are not keeping the filtering IDs
-                    // then we should write it to the output
-                    if !self.keep {
-                        rec.write(&mut writer, None)?;
-                    }
-
-                    // update the records
-                    update_record = true;
-                } else if rec.id() > filter_id {
-                    // update the IDs
-                    update_id = true;
-                } else {
-                    if self.keep {
-                        // unwrap is guaranteed because of the while loop condition
-                        rec.write(&mut writer, None)?;
-                    }
-
-                    // update the records
-                    update_record = true;
-                }
-            }
 
-            // if update_record {
-            //     filt_iter.set_prev_record_id(&curr_record)?;
-            //     curr_record = fq_reader.next();
-            // } else if update_id {
-            //     // if the record is after the filtering ID, don't do anything with the current record right away
-            //     filt_iter.get_next_id(&mut id_reader)?;
-
-            //     // if there are no more IDs to process, be sure to deal with the remaining records
-            //     // outside of the present loop
-            //     if filt_iter.curr_filter_id().is_none() {
-            //         // unwrap is guaranteed because of the while loop condition
-            //         curr_record.unwrap().unwrap().write(&mut writer, None)?;
-            //         deal_with_remaining_reads = true;
-            //     }
-            // }
-
-            // if the updated current record or current filtering ID is `None`, then our comparisons are done
-            // and we can break out of the look
-            if curr_record.is_none() || filt_iter.curr_filter_id().is_none() {
+        let mut n_kept = 0;
+        let mut stopped_early = false;
+        while let Some(record) = fq_reader.next() {
+            let rec = record
+                .with_context(|| format!("Error reading HTS file {}", hts.path().display()))?;
+            let name = read_name(rec.id());
+            filt_iter.set_prev_record_id(name, hts.path(), progress.n_records() + 1)?;
+            progress.record();
+
+            // once all IDs have been passed, no more records can be kept
+            if self.keep && filt_iter.is_done() {
+                stopped_early = true;
                 break;
             }
+            if filt_iter.matches(name)? == self.keep && self.passes_chastity(&rec) {
+                rec.write(&mut writer, None)?;
+                n_kept += 1;
+            }
+        }
+        writer.finish()?;
+        self.log_kept(n_kept, stopped_early, &hts, progress);
+
+        Ok(())
+    }
+
+    /// Open the FASTQ file, along with the progress of reading it
+    pub fn get_hts_reader(&self) -> anyhow::Result<(HtsFile, Box<dyn FastxReader>, Progress)> {
+        let hts = HtsFile::with_format(&self.hts_path, self.input_format)?;
+        if hts.filetype() != Hts::Fastx(Fastx::Fastq) {
+            bail!(BjtError::WrongFormat {
+                path: hts.path().to_path_buf(),
+                expected: "FASTQ",
+            });
         }
 
-        // If all of the IDs have been exhausted but there are still records to write,,
-        // write them without comparing against the IDs.
-        // We can assume this because the FASTQ and ID file are both sorted.
-        if deal_with_remaining_reads && !self.keep {
-            while let Some(Ok(record)) = fq_reader.next() {
-                record.write(&mut writer, None)?;
+        let progress = Progress::reading(&hts);
+        let reader =
+            parse_fastx_reader(hts.decompressed_reader(self.threads.n_threads(), &progress)?)
+                .with_context(|| format!("Error opening HTS file {}", hts.path().display()))?;
+
+        Ok((hts, reader, progress))
+    }
+
+    /// Only keep records that pass a check on their read names, and the chastity filter.
+    fn filter_names<F: Fn(&[u8]) -> bool>(&self, passes: F) -> anyhow::Result<()> {
+        let (hts, mut fq_reader, progress) = self.get_hts_reader()?;
+        let mut writer = self.writer_output()?;
+
+        let mut n_kept = 0;
+        while let Some(record) = fq_reader.next() {
+            let rec = record
+                .with_context(|| format!("Error reading HTS file {}", hts.path().display()))?;
+            if passes(read_name(rec.id())) && self.passes_chastity(&rec) {
+                rec.write(&mut writer, None)?;
+                n_kept += 1;
             }
+            progress.record();
         }
+        writer.finish()?;
+        self.log_kept(n_kept, false, &hts, progress);
 
         Ok(())
     }
 
-    pub fn get_hts_reader(&self) -> FastqReader<File> {
-        FastqReader::from_path(&self.hts_path).expect("Error opening HTS file.")
+    /// Whether a record can be written out, given its chastity filter flag.
+    fn passes_chastity(&self, rec: &SequenceRecord) -> bool {
+        !self.drop_filtered || !is_chastity_filtered(rec.id())
     }
 
-    pub fn get_id_file_lines(&self) -> Result<io::Lines<BufReader<File>>, FastqFilterError> {
-        // the unwrap is guaranteed based on the `FastqFilterOpts::exec()` implementation
-        let ids = match self.id_list_path.as_ref() {
-            Some(path) => path,
-            None => return Err(FastqFilterError::IdFileNotProvidedWhenRequired),
-        };
+    /// Log how many records were kept, once the whole file (or as much as needed) has been read
+    ///
+    /// If reading stopped before the end of the file, only the records read so far are known.
+    fn log_kept(&self, n_kept: u64, stopped_early: bool, hts: &HtsFile, progress: Progress) {
+        let read = if stopped_early { "the first " } else { "" };
+        info!(
+            "Kept {} of {}{} reads from {}",
+            n_kept,
+            read,
+            progress.n_records(),
+            hts.path().display()
+        );
+        progress.finish(hts);
+    }
+}
+
+/// Whether a read failed the chastity filter, according to its Casava 1.8 read name.
+///
+/// Reads without a Casava 1.8 read name are never considered filtered.
+fn is_chastity_filtered(rname: &[u8]) -> bool {
+    CasavaV1_8Name::try_from(rname)
+        .ok()
+        .and_then(|name| name.comment)
+        .is_some_and(|comment| comment.filtered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, write};
+    use tempfile::tempdir;
+
+    const EXAMPLE: &str = "examples/SRR0000001.fastq";
+
+    const PAIRS: &str =
+        "@r1/1\nA\n+\nE\n@r1/2\nC\n+\nE\n@r2/1\nG\n+\nE\n@r2/2\nT\n+\nE\n@r10/1\nN\n+\n#\n";
+
+    /// Casava 1.8 reads, where the even-numbered reads failed the chastity filter
+    const CASAVA: &str = "\
+@SEQ01:1:FC1:1:1:1:1 1:N:0:ACGT\nACGT\n+\nEEEE\n\
+@SEQ01:1:FC1:1:1:1:2 1:Y:0:ACGT\nACGT\n+\nEEEE\n\
+@SEQ01:1:FC1:1:1:1:3 1:N:0:ACGT\nACGT\n+\nEEEE\n\
+@SEQ01:1:FC1:1:1:1:4 1:Y:0:ACGT\nACGT\n+\nEEEE\n";
 
-        // open the file with the IDs to filter
-        let id_file = match File::open(ids) {
-            Ok(f) => BufReader::new(f).lines(),
-            Err(_) => return Err(FastqFilterError::IdFileCannotBeOpened),
+    /// Filter a FASTQ file with some arguments, and return the names of the reads that were kept
+    fn filter(input: &str, args: &[&str]) -> anyhow::Result<Vec<String>> {
+        let dir = tempdir().unwrap();
+        let output = dir.path().join("out.fastq");
+        let mut cli = vec!["fastq", input, "-o", output.to_str().unwrap()];
+        cli.extend(args);
+        FastqFilterOpts::parse_from(cli).exec()?;
+
+        Ok(read_to_string(&output)
+            .unwrap()
+            .lines()
+            .step_by(4)
+            .filter_map(|l| l.strip_prefix('@'))
+            .filter_map(|l| l.split_whitespace().next())
+            .map(String::from)
+            .collect())
+    }
+
+    /// Filter a FASTQ file against an ID file with the given contents
+    fn filter_ids(input: &str, ids: &str, args: &[&str]) -> anyhow::Result<Vec<String>> {
+        let dir = tempdir().unwrap();
+        let id_path = dir.path().join("ids.txt");
+        write(&id_path, ids).unwrap();
+        let mut cli = vec!["-f", id_path.to_str().unwrap()];
+        cli.extend(args);
+
+        filter(input, &cli)
+    }
+
+    #[test]
+    fn example_ids_match_nothing() {
+        let discarded = filter(EXAMPLE, &["-f", "examples/ids.txt"]).unwrap();
+        let kept = filter(EXAMPLE, &["-f", "examples/ids.txt", "-k"]).unwrap();
+
+        assert_eq!(discarded.len(), 2500);
+        assert!(kept.is_empty());
+    }
+
+    #[test]
+    fn ids_are_matched_in_natural_order() {
+        let ids = "SRR0000001.2\nSRR0000001.10\nSRR0000001.10\n\nSRR0000001.2500/1\n";
+        let kept = filter_ids(EXAMPLE, ids, &["--keep"]).unwrap();
+        let discarded = filter_ids(EXAMPLE, ids, &[]).unwrap();
+
+        assert_eq!(kept, ["SRR0000001.2", "SRR0000001.10", "SRR0000001.2500"]);
+        assert_eq!(discarded.len(), 2497);
+        assert_eq!(
+            discarded[..3],
+            ["SRR0000001.1", "SRR0000001.3", "SRR0000001.4"]
+        );
+    }
+
+    #[test]
+    fn ids_match_both_mates() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("pairs.fastq");
+        write(&input, PAIRS).unwrap();
+        let input = input.to_str().unwrap();
+        let kept = filter_ids(input, "r2\n", &["-k"]).unwrap();
+        let discarded = filter_ids(input, "r1/2\nr2/1\n", &[]).unwrap();
+
+        assert_eq!(kept, ["r2/1", "r2/2"]);
+        assert_eq!(discarded, ["r10/1"]);
+    }
+
+    #[test]
+    fn unsorted_ids_are_err() {
+        let obs = filter_ids(EXAMPLE, "SRR0000001.10\nSRR0000001.9\n", &[]);
+        let err = obs.unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<BjtError>(),
+            Some(BjtError::Unsorted { record: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn regex_discards_matches() {
+        let obs = filter(EXAMPLE, &["-r", r"\.1\d*$"]).unwrap();
+
+        assert_eq!(obs.len(), 2500 - 1111);
+        assert_eq!(obs[0], "SRR0000001.2");
+    }
+
+    /// Filter the Casava 1.8 reads, with or without an ID file, and return the numbers of the reads that were kept
+    fn filter_casava(ids: Option<&str>, args: &[&str]) -> Vec<String> {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("casava.fastq");
+        write(&input, CASAVA).unwrap();
+        let input = input.to_str().unwrap();
+        let kept = match ids {
+            Some(ids) => filter_ids(input, ids, args),
+            None => filter(input, args),
         };
 
-        Ok(id_file)
+        kept.unwrap()
+            .iter()
+            .filter_map(|name| name.rsplit(':').next())
+            .map(String::from)
+            .collect()
     }
 
-    /// Filter out records using a regular expression to match against record IDs.
-    fn filter_with_id_regex(&self) -> anyhow::Result<()> {
-        Ok(())
+    #[test]
+    fn drop_filtered_alone_discards_filtered_reads() {
+        assert_eq!(filter_casava(None, &["--drop-filtered"]), ["1", "3"]);
     }
 
-    /// Filter out records containing a provided sequence.
-    fn filter_seq(&self) -> anyhow::Result<()> {
-        Ok(())
+    #[test]
+    fn drop_filtered_applies_after_id_file() {
+        let ids = "SEQ01:1:FC1:1:1:1:2\nSEQ01:1:FC1:1:1:1:3\n";
+
+        assert_eq!(filter_casava(Some(ids), &["--drop-filtered"]), ["1"]);
+        assert_eq!(
+            filter_casava(Some(ids), &["--drop-filtered", "--keep"]),
+            ["3"]
+        );
+        assert_eq!(filter_casava(Some(ids), &[]), ["1", "4"]);
     }
 
-    /// Filter out records based on its quality scores.
-    fn filter_qual(&self) -> anyhow::Result<()> {
-        Ok(())
+    #[test]
+    fn drop_filtered_applies_after_regex() {
+        let re = ":[12]$";
+
+        assert_eq!(filter_casava(None, &["-r", re, "--drop-filtered"]), ["3"]);
+        assert_eq!(
+            filter_casava(None, &["-r", re, "--drop-filtered", "--keep"]),
+            ["1"]
+        );
+        assert_eq!(filter_casava(None, &["-r", re, "--keep"]), ["1", "2"]);
     }
-}
 
-/// Check that the FASTQ records are in sorted order.
-fn assert_records_are_sorted(
-    curr_record: &Option<Result<SequenceRecord, ParseError>>,
-    prev_record_id: Option<&[u8]>,
-) -> Result<(), FastqFilterError> {
-    match (curr_record, prev_record_id) {
-        (Some(Ok(curr)), Some(prev)) => {
-            if curr.id() < prev {
-                Ok(())
-            } else {
-                Err(FastqFilterError::FastqNotSorted)
-            }
-        }
-        (Some(Err(e)), _) => Err(FastqFilterError::CannotParseFastqRecord(e.clone())),
-        (_, _) => Ok(()),
+    #[test]
+    fn only_casava_names_are_chastity_filtered() {
+        assert!(is_chastity_filtered(b"SEQ01:1:FC1:1:1:1:2 1:Y:0:ACGT"));
+        assert!(!is_chastity_filtered(b"SEQ01:1:FC1:1:1:1:1 1:N:0:ACGT"));
+        assert!(!is_chastity_filtered(b"SRR0000001.1 Y"));
     }
 }
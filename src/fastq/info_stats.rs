@@ -1,26 +1,52 @@
 //! Statistics for a FASTQ file.
 
+use super::{
+    composition::{BaseComposition, BaseCompositionState},
+    encoding::{EncodingScan, EncodingScanState},
+    quality::{QualityProfile, QualityProfileState, DEFAULT_MAX_CYCLES},
+};
 use crate::{
     cli::CliOpt,
+    error::BjtError,
     record::{
-        error::RecordError,
-        header::{RecordName, ILLUMINA_SEPARATOR_ASCII_CODE, RNAME_SEPARATOR_ASCII_CODE},
-        stats::RecordStats,
+        barcodes::{IndexTally, SampleSheet, DEFAULT_TOP_INDEXES},
+        grammar::NameGrammar,
+        nanopore::{NanoporeRuns, NanoporeRunsState, DEFAULT_TIMELINE_BIN_MINUTES},
+        stats::{
+            is_zero, merge_counts, serialize_length_distribution, FilterFlags, MergeStats,
+            NameOpts, RecordStats, SraRuns,
+        },
+    },
+    utils::{
+        batch::{expand_paths, join_keys, BatchReport, SummaryRow},
+        decompress::ThreadOpts,
+        formats::{serialize_sorted_map, OutputFormat},
+        progress::Progress,
+        stats_file::PartialStats,
+        Fastx, Hts, HtsFile,
     },
-    utils::{formats::OutputFormat, Fastx, Hts, HtsFile},
 };
+use anyhow::{bail, Context};
 use clap::Parser;
-use needletail::parse_fastx_file;
+use log::debug;
+use needletail::parse_fastx_reader;
 use needletail::{errors::ParseError, parser::SequenceRecord};
-use std::collections::HashMap;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::{collections::HashMap, sync::Arc};
 
 /// CLI options for getting info from an HTS file
 #[derive(Debug, Parser)]
 pub(crate) struct FastqInfoOpts {
-    /// Get info about this HTS file
-    #[clap(name = "HTS")]
-    hts_path: PathBuf,
+    /// Get info about these HTS files (glob patterns are expanded).
+    /// Multiple files are summarized in a table with one row per file.
+    /// Use `-` to read from standard input.
+    #[clap(name = "HTS", required = true)]
+    hts_paths: Vec<PathBuf>,
+
+    /// Format of the input, instead of detecting it from the file extension (required for standard input)
+    #[clap(long, value_name = "FORMAT")]
+    input_format: Option<Hts>,
 
     /// Count the total number of records
     #[clap(short, long)]
@@ -34,57 +60,172 @@ pub(crate) struct FastqInfoOpts {
     #[clap(short, long)]
     instruments: bool,
 
-    /// Track flow cell IDs
+    /// Track flow cell IDs and lanes
     #[clap(short = 'F', long)]
     flow_cell_ids: bool,
 
+    /// Track SRA run accessions and spot numbers, and check the lengths in SRA read names
+    #[clap(long)]
+    sra: bool,
+
+    /// Parse read names with this regular expression before trying the built-in formats.
+    /// Named capture groups `instrument`, `flowcell`, `lane`, and `barcode` are recognized,
+    /// and whichever ones the expression uses are tracked, as if `-i`, `-F`, or `--indexes` were given.
+    /// Can be given multiple times, and the first matching expression is used.
+    #[clap(long = "name-regex", value_name = "REGEX")]
+    name_grammars: Vec<NameGrammar>,
+
+    /// Track Oxford Nanopore runs, flow cells, channels, basecalling models, and throughput over time
+    #[clap(long)]
+    nanopore: bool,
+
+    /// Width of the bins in Oxford Nanopore throughput timelines, in minutes
+    #[clap(
+        long = "timeline-bin",
+        value_name = "MINUTES",
+        default_value_t = DEFAULT_TIMELINE_BIN_MINUTES
+    )]
+    timeline_bin_minutes: u64,
+
+    /// Count reads that failed the chastity filter, and control reads, from Casava 1.8 read names
+    #[clap(long)]
+    filter_flags: bool,
+
+    /// Count the index (barcode) sequences in read names
+    #[clap(long)]
+    indexes: bool,
+
+    /// Number of the most common index sequences to report
+    #[clap(
+        long = "top-indexes",
+        value_name = "N",
+        default_value_t = DEFAULT_TOP_INDEXES
+    )]
+    n_top_indexes: usize,
+
+    /// Check index sequences against the samples in this sample sheet (implies --indexes).
+    /// Either an Illumina sample sheet, or a CSV/TSV file with `sample,i7[,i5]` on each line.
+    #[clap(long, value_name = "FILE", value_parser = SampleSheet::from_path_arg)]
+    sample_sheet: Option<Arc<SampleSheet>>,
+
+    /// Summarize the distribution of quality scores at each cycle
+    #[clap(short = 'Q', long = "quality")]
+    quality_profile: bool,
+
+    /// Tally the nucleotides at each cycle and the GC content of each record
+    #[clap(short, long)]
+    composition: bool,
+
+    /// Track the quality scores and nucleotides of at most the first N cycles of each record
+    #[clap(long, value_name = "N", default_value_t = DEFAULT_MAX_CYCLES)]
+    max_cycles: usize,
+
     /// Output format to return statistics in
     #[clap(short = 'f', long, default_value = "human")]
     format: OutputFormat,
 
+    /// Also save the raw statistics to this file, to be combined with others by `bjt info merge`.
+    /// For multiple files, the statistics of all the files combined are saved.
+    #[clap(long, value_name = "FILE")]
+    save_stats: Option<PathBuf>,
+
     /// Keep statistics on the first N records
     #[clap(short = 'N', long = "max-records", value_name = "N")]
     n_max_records: Option<u64>,
+
+    /// Process N files in parallel [default: the number of CPUs]
+    #[clap(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    #[clap(flatten)]
+    threads: ThreadOpts,
 }
 
 impl FastqInfoOpts {
+    /// Which pieces of information to track from record names
+    fn name_opts(&self) -> NameOpts<'_> {
+        NameOpts {
+            instruments: self.instruments,
+            flow_cells: self.flow_cell_ids,
+            sra: self.sra,
+            grammars: &self.name_grammars,
+            nanopore: self.nanopore,
+            timeline_bin_minutes: self.timeline_bin_minutes,
+            indexes: self.indexes || self.sample_sheet.is_some(),
+            filter_flags: self.filter_flags,
+        }
+        .with_grammar_groups()
+    }
+
+    /// Get information and statistics about a single file
+    fn calc_file_info(&self, path: &Path) -> anyhow::Result<FastqStats> {
+        let hts = HtsFile::with_format(path, self.input_format)?;
+        debug!("Reading {} as {}", path.display(), hts.filetype());
+        match hts.filetype() {
+            Hts::Fastx(Fastx::Fastq) => self.calc_fastq_info(hts),
+            _ => bail!(BjtError::WrongFormat {
+                path: path.to_path_buf(),
+                expected: "FASTQ",
+            }),
+        }
+    }
+
     /// Get information and statistics about a desired FASTQ file
-    fn calc_fastq_info(&self, hts: HtsFile) -> FastqStats {
+    fn calc_fastq_info(&self, hts: HtsFile) -> anyhow::Result<FastqStats> {
         let mut stats = FastqStats::new();
-        let mut reader = parse_fastx_file(hts.path()).expect("Error opening HTS file");
+        let name_opts = self.name_opts();
+        let progress = Progress::reading(&hts);
+        let mut reader =
+            parse_fastx_reader(hts.decompressed_reader(self.threads.n_threads(), &progress)?)
+                .with_context(|| format!("Error opening HTS file {}", hts.path().display()))?;
 
         if let Some(n_max) = self.n_max_records {
             // check if the max capacity has been hit
             while let (true, Some(record)) = (stats.n_records() < n_max, reader.next()) {
-                stats.process_record(&record, self);
+                stats.process_record(&record, self, &name_opts);
+                progress.record();
             }
         } else {
             while let Some(record) = reader.next() {
-                stats.process_record(&record, self);
+                stats.process_record(&record, self, &name_opts);
+                progress.record();
             }
         }
+        progress.finish(&hts);
+        stats.apply_quality_encoding();
+        stats.set_index_report(self.n_top_indexes, self.sample_sheet.clone());
 
-        stats
+        Ok(stats)
     }
 }
 
 impl CliOpt for FastqInfoOpts {
     fn exec(&self) -> anyhow::Result<()> {
-        let hts = HtsFile::new(&self.hts_path);
-        match hts.filetype() {
-            Hts::Fastx(Fastx::Fastq) => {
-                let stats = self.calc_fastq_info(hts);
-                println!("{:#?}", stats);
+        let paths = expand_paths(&self.hts_paths)?;
+
+        // a single file gets a detailed report
+        if let [path] = paths.as_slice() {
+            let stats = self.calc_file_info(path)?;
+            if let Some(stats_path) = &self.save_stats {
+                PartialStats::Fastq(Box::new((&stats).into())).save(stats_path)?;
             }
-            _ => todo!(),
+            print!("{}", self.format.render(&stats)?);
+
+            return Ok(());
+        }
+
+        let batch = BatchReport::process(&paths, self.jobs, |path| self.calc_file_info(path))?;
+        if let (Some(stats_path), Some(total)) = (&self.save_stats, batch.total()) {
+            PartialStats::Fastq(Box::new(total.into())).save(stats_path)?;
         }
+        print!("{}", batch.render(&self.format)?);
 
-        Ok(())
+        Ok(batch.check()?)
     }
 }
 
 /// Statistics from a FASTQ file
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub(crate) struct FastqStats {
     /// Total number of valid records
     valid_records: u64,
@@ -96,35 +237,278 @@ pub(crate) struct FastqStats {
     bases: u64,
 
     /// Length distribution of records
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_length_distribution"
+    )]
     lengths: HashMap<u64, u64>,
 
     /// Sequencing instruments
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
     instruments: HashMap<String, u64>,
 
     /// Flow cell IDs
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    flow_cell_ids: HashMap<String, u64>,
+
+    /// Flow cell lanes, as `flowcell:lane`
+    #[serde(
+        skip_serializing_if = "HashMap::is_empty",
+        serialize_with = "serialize_sorted_map"
+    )]
+    lanes: HashMap<String, u64>,
+
+    /// Runs and spots from SRA read names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sra_runs: Option<SraRuns>,
+
+    /// Runs, channels, and throughput from Oxford Nanopore read names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nanopore: Option<NanoporeRuns>,
+
+    /// Chastity filter flags and control bits from read names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter_flags: Option<FilterFlags>,
+
+    /// Index sequences from read names
+    #[serde(skip_serializing_if = "Option::is_none")]
+    indexes: Option<IndexTally>,
+
+    /// Number of records whose name format couldn't be determined
+    #[serde(skip_serializing_if = "is_zero")]
+    unrecognized_names: u64,
+
+    /// Quality encoding detected from the quality characters of every record
+    #[serde(skip_serializing_if = "EncodingScan::is_empty")]
+    quality_encoding: EncodingScan,
+
+    /// Quality score distribution at each cycle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality_profile: Option<QualityProfile>,
+
+    /// Nucleotide composition and GC content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    composition: Option<BaseComposition>,
+}
+
+/// Raw statistics from a FASTQ file, as saved in a stats file
+///
+/// Unlike [`FastqStats`], which is serialized as a report, this keeps every tally needed to combine the statistics with those of other parts of the same file.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct FastqStatsState {
+    valid_records: u64,
+    invalid_records: u64,
+    bases: u64,
+    lengths: HashMap<u64, u64>,
+    instruments: HashMap<String, u64>,
     flow_cell_ids: HashMap<String, u64>,
+    lanes: HashMap<String, u64>,
+    sra_runs: Option<SraRuns>,
+    nanopore: Option<NanoporeRunsState>,
+    filter_flags: Option<FilterFlags>,
+    indexes: Option<HashMap<String, u64>>,
+    unrecognized_names: u64,
+    quality_encoding: EncodingScanState,
+    quality_profile: Option<QualityProfileState>,
+    composition: Option<BaseCompositionState>,
+}
+
+impl From<&FastqStats> for FastqStatsState {
+    fn from(stats: &FastqStats) -> Self {
+        Self {
+            valid_records: stats.valid_records,
+            invalid_records: stats.invalid_records,
+            bases: stats.bases,
+            lengths: stats.lengths.clone(),
+            instruments: stats.instruments.clone(),
+            flow_cell_ids: stats.flow_cell_ids.clone(),
+            lanes: stats.lanes.clone(),
+            sra_runs: stats.sra_runs.clone(),
+            nanopore: stats.nanopore.as_ref().map(NanoporeRunsState::from),
+            filter_flags: stats.filter_flags,
+            indexes: stats.indexes.as_ref().map(|tally| tally.counts().clone()),
+            unrecognized_names: stats.unrecognized_names,
+            quality_encoding: (&stats.quality_encoding).into(),
+            quality_profile: stats
+                .quality_profile
+                .as_ref()
+                .map(QualityProfileState::from),
+            composition: stats.composition.as_ref().map(BaseCompositionState::from),
+        }
+    }
+}
+
+impl TryFrom<FastqStatsState> for FastqStats {
+    type Error = &'static str;
+
+    fn try_from(state: FastqStatsState) -> Result<Self, Self::Error> {
+        let mut stats = Self {
+            valid_records: state.valid_records,
+            invalid_records: state.invalid_records,
+            bases: state.bases,
+            lengths: state.lengths,
+            instruments: state.instruments,
+            flow_cell_ids: state.flow_cell_ids,
+            lanes: state.lanes,
+            sra_runs: state.sra_runs,
+            nanopore: state.nanopore.map(NanoporeRuns::try_from).transpose()?,
+            filter_flags: state.filter_flags,
+            indexes: state.indexes.map(IndexTally::from_counts),
+            unrecognized_names: state.unrecognized_names,
+            quality_encoding: state.quality_encoding.into(),
+            quality_profile: state
+                .quality_profile
+                .map(QualityProfile::try_from)
+                .transpose()?,
+            composition: state
+                .composition
+                .map(BaseComposition::try_from)
+                .transpose()?,
+        };
+        stats.apply_quality_encoding();
+
+        Ok(stats)
+    }
 }
 
 impl FastqStats {
-    /// Process an Illumina (Casava >= v1.8) formatted FASTQ record
-    fn process_illumina_split_record(&mut self, rname: &[u8], opts: &FastqInfoOpts) {
-        // Illumina Casava >= v1.8 format
-        let mut id_splits = rname.split(|x| *x == ILLUMINA_SEPARATOR_ASCII_CODE);
+    /// Width of the Oxford Nanopore timeline bins, in minutes, if Nanopore runs were tracked
+    pub fn nanopore_bin_minutes(&self) -> Option<u64> {
+        self.nanopore.as_ref().map(NanoporeRuns::bin_minutes)
+    }
+
+    /// Set how index sequences are reported: the number of the most common sequences, and an optional sample sheet
+    pub fn set_index_report(&mut self, n_top: usize, sample_sheet: Option<Arc<SampleSheet>>) {
+        if let Some(tally) = self.indexes.as_mut() {
+            tally.set_report(n_top, sample_sheet);
+        }
+    }
 
-        // instrument name
-        let inst = id_splits.next();
-        if opts.instruments {
-            self.process_illumina_instrument(inst);
+    /// Interpret quality-based statistics with the detected quality encoding
+    fn apply_quality_encoding(&mut self) {
+        if let (Some(encoding), Some(profile)) = (
+            self.quality_encoding.encoding(),
+            self.quality_profile.as_mut(),
+        ) {
+            profile.set_offset(encoding.offset());
         }
+    }
+}
 
-        // run ID
-        let _run_id = id_splits.next();
+impl MergeStats for FastqStats {
+    fn merge(&mut self, other: &Self) {
+        self.valid_records += other.valid_records;
+        self.invalid_records += other.invalid_records;
+        self.bases += other.bases;
+        merge_counts(&mut self.lengths, &other.lengths);
+        merge_counts(&mut self.instruments, &other.instruments);
+        merge_counts(&mut self.flow_cell_ids, &other.flow_cell_ids);
+        merge_counts(&mut self.lanes, &other.lanes);
+        self.unrecognized_names += other.unrecognized_names;
+        match (self.filter_flags.as_mut(), &other.filter_flags) {
+            (Some(flags), Some(other_flags)) => flags.merge(other_flags),
+            (None, other_flags) => self.filter_flags = *other_flags,
+            (Some(_), None) => {}
+        }
+        match (self.indexes.as_mut(), &other.indexes) {
+            (Some(tally), Some(other_tally)) => tally.merge(other_tally),
+            (None, other_tally) => self.indexes = other_tally.clone(),
+            (Some(_), None) => {}
+        }
+        match (self.nanopore.as_mut(), &other.nanopore) {
+            (Some(runs), Some(other_runs)) => runs.merge(other_runs),
+            (None, other_runs) => self.nanopore = other_runs.clone(),
+            (Some(_), None) => {}
+        }
+        match (self.sra_runs.as_mut(), &other.sra_runs) {
+            (Some(runs), Some(other_runs)) => runs.merge(other_runs),
+            (None, other_runs) => self.sra_runs = other_runs.clone(),
+            (Some(_), None) => {}
+        }
+        self.quality_encoding.merge(&other.quality_encoding);
 
-        // flow cell ID
-        let fcid = id_splits.next();
-        if opts.flow_cell_ids {
-            self.process_illumina_flowcell(fcid);
+        match (self.quality_profile.as_mut(), &other.quality_profile) {
+            (Some(profile), Some(other_profile)) => profile.merge(other_profile),
+            (None, other_profile) => self.quality_profile = other_profile.clone(),
+            (Some(_), None) => {}
+        }
+        match (self.composition.as_mut(), &other.composition) {
+            (Some(comp), Some(other_comp)) => comp.merge(other_comp),
+            (None, other_comp) => self.composition = other_comp.clone(),
+            (Some(_), None) => {}
         }
+
+        // the combined records may have revealed a different encoding
+        self.apply_quality_encoding();
+    }
+}
+
+impl SummaryRow for FastqStats {
+    fn summary_row(&self) -> Vec<(&'static str, String)> {
+        let mut row = vec![
+            ("valid_records", self.valid_records.to_string()),
+            ("invalid_records", self.invalid_records.to_string()),
+            ("bases", self.bases.to_string()),
+        ];
+
+        if let Some(summary) = self.length_summary() {
+            row.extend(summary.summary_row());
+        }
+        if let Some(encoding) = self.quality_encoding.encoding() {
+            row.push(("quality_encoding", encoding.to_string()));
+        }
+        if let Some(comp) = &self.composition {
+            if let Some(gc) = comp.gc_fraction() {
+                row.push(("gc_fraction", gc.to_string()));
+            }
+            if let Some(n_rate) = comp.n_rate() {
+                row.push(("n_rate", n_rate.to_string()));
+            }
+        }
+        if !self.instruments.is_empty() {
+            row.push(("instruments", join_keys(&self.instruments)));
+        }
+        if !self.flow_cell_ids.is_empty() {
+            row.push(("flow_cell_ids", join_keys(&self.flow_cell_ids)));
+        }
+        if let Some(sra) = &self.sra_runs {
+            row.push(("sra_runs", join_keys(&sra.runs)));
+            row.push(("sra_length_mismatches", sra.length_mismatches.to_string()));
+        }
+        if let Some(ont) = &self.nanopore {
+            row.push(("nanopore_runs", join_keys(ont.runs())));
+            row.push(("basecall_models", join_keys(ont.basecall_models())));
+            row.push(("active_channels", ont.active_channels().to_string()));
+        }
+        if let Some(flags) = &self.filter_flags {
+            row.push(("filtered_reads", flags.filtered.to_string()));
+            row.push(("control_reads", flags.control.to_string()));
+        }
+        if let Some(tally) = &self.indexes {
+            row.push(("distinct_indexes", tally.n_distinct().to_string()));
+            if let Some((index, _)) = tally.top().first() {
+                row.push(("top_index", index.to_string()));
+            }
+            if let Some(report) = tally.report() {
+                row.push(("index_exact_fraction", report.exact.to_string()));
+                row.push((
+                    "index_one_mismatch_fraction",
+                    report.one_mismatch.to_string(),
+                ));
+                row.push(("index_hopped_fraction", report.hopped.to_string()));
+            }
+        }
+        if self.unrecognized_names > 0 {
+            row.push(("unrecognized_names", self.unrecognized_names.to_string()));
+        }
+
+        row
     }
 }
 
@@ -142,6 +526,15 @@ impl<'a> RecordStats<'a> for FastqStats {
             lengths: HashMap::new(),
             instruments: HashMap::new(),
             flow_cell_ids: HashMap::new(),
+            lanes: HashMap::new(),
+            sra_runs: None,
+            nanopore: None,
+            filter_flags: None,
+            indexes: None,
+            unrecognized_names: 0,
+            quality_encoding: EncodingScan::new(),
+            quality_profile: None,
+            composition: None,
         }
     }
 
@@ -153,6 +546,10 @@ impl<'a> RecordStats<'a> for FastqStats {
         self.invalid_records
     }
 
+    fn lengths(&self) -> &HashMap<u64, u64> {
+        &self.lengths
+    }
+
     fn mut_lengths(&mut self) -> &mut HashMap<u64, u64> {
         &mut self.lengths
     }
@@ -165,7 +562,39 @@ impl<'a> RecordStats<'a> for FastqStats {
         &mut self.instruments
     }
 
-    fn process_valid_record(&mut self, seq: &SequenceRecord, opts: &FastqInfoOpts) {
+    fn mut_lanes(&mut self) -> &mut HashMap<String, u64> {
+        &mut self.lanes
+    }
+
+    fn mut_sra_runs(&mut self) -> &mut SraRuns {
+        self.sra_runs.get_or_insert_with(SraRuns::new)
+    }
+
+    fn mut_nanopore_runs(&mut self, bin_minutes: u64) -> Option<&mut NanoporeRuns> {
+        Some(
+            self.nanopore
+                .get_or_insert_with(|| NanoporeRuns::new(bin_minutes)),
+        )
+    }
+
+    fn mut_filter_flags(&mut self) -> Option<&mut FilterFlags> {
+        Some(self.filter_flags.get_or_insert_with(FilterFlags::new))
+    }
+
+    fn mut_index_tally(&mut self) -> Option<&mut IndexTally> {
+        Some(self.indexes.get_or_insert_with(IndexTally::new))
+    }
+
+    fn mut_unrecognized_names(&mut self) -> &mut u64 {
+        &mut self.unrecognized_names
+    }
+
+    fn process_valid_record(
+        &mut self,
+        seq: &SequenceRecord,
+        opts: &FastqInfoOpts,
+        name_opts: &NameOpts,
+    ) {
         self.valid_records += 1;
 
         let seq_length: u64 = seq.num_bases().try_into().unwrap();
@@ -174,21 +603,23 @@ impl<'a> RecordStats<'a> for FastqStats {
         if opts.lengths {
             self.update_lengths(seq_length);
         }
-        if opts.flow_cell_ids || opts.instruments {
-            if opts.flow_cell_ids || opts.instruments {
-                match RecordName::try_from(seq.id()) {
-                    Ok(RecordName::CasavaV1_8) => {
-                        let mut splits = seq.id().split(|x| *x == RNAME_SEPARATOR_ASCII_CODE);
-                        let a = splits.next().unwrap();
-                        self.process_illumina_split_record(a, opts);
-                    }
-                    Ok(RecordName::SequenceReadArchive) => {
-                        self.process_sra_split_record();
-                    }
-                    Err(RecordError::UncertainRecordNameFormat) => todo!(),
-                }
+        if let Some(qual) = seq.qual() {
+            self.quality_encoding.update(qual);
+
+            if opts.quality_profile {
+                self.quality_profile
+                    .get_or_insert_with(QualityProfile::new)
+                    .update(qual, opts.max_cycles);
             }
         }
+        if opts.composition {
+            self.composition
+                .get_or_insert_with(BaseComposition::new)
+                .update(&seq.seq(), opts.max_cycles);
+        }
+        if name_opts.any() {
+            self.process_name(seq.id(), Some(seq_length), name_opts);
+        }
     }
 
     fn process_invalid_record(&mut self) {
@@ -198,6 +629,100 @@ impl<'a> RecordStats<'a> for FastqStats {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::fs::{read_to_string, write};
+    use tempfile::tempdir;
+
+    /// Save statistics to a stats file and load them again
+    fn round_trip(stats: &FastqStats) -> FastqStats {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stats.json");
+        PartialStats::Fastq(Box::new(stats.into()))
+            .save(&path)
+            .unwrap();
+
+        match PartialStats::load(&path).unwrap() {
+            PartialStats::Fastq(state) => FastqStats::try_from(*state).unwrap(),
+            _ => panic!("FASTQ statistics were loaded as another kind"),
+        }
+    }
+
+    /// Check that the statistics of a FASTQ file, split after `n_head` records, merge into those of the whole file
+    #[track_caller]
+    fn check_merged_chunks(content: &str, n_head: usize) {
+        let lines: Vec<&str> = content.lines().collect();
+        // each record has 4 lines, so split on a record boundary
+        let (head, tail) = lines.split_at(n_head * 4);
+        let dir = tempdir().unwrap();
+        let whole_path = dir.path().join("whole.fastq");
+        write(&whole_path, content).unwrap();
+        let chunks: Vec<PathBuf> = [head, tail]
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let path = dir.path().join(format!("{}.fastq", i));
+                write(&path, chunk.join("\n") + "\n").unwrap();
+                path
+            })
+            .collect();
+
+        let opts =
+            FastqInfoOpts::parse_from(["fastq", "-", "-l", "-i", "-F", "-Q", "-c", "--indexes"]);
+        let exp = opts.calc_file_info(&whole_path).unwrap();
+        let mut obs = round_trip(&opts.calc_file_info(&chunks[0]).unwrap());
+        obs.merge(&round_trip(&opts.calc_file_info(&chunks[1]).unwrap()));
+
+        assert_eq!(
+            serde_json::to_value(&obs).unwrap(),
+            serde_json::to_value(&exp).unwrap()
+        );
+    }
+
+    #[test]
+    fn merged_chunks_match_whole_file() {
+        let content = read_to_string("examples/SRR0000001.fastq").unwrap();
+        let n_records = content.lines().count() / 4;
+
+        check_merged_chunks(&content, n_records / 2);
+    }
+
+    #[test]
+    fn merged_large_chunks_match_whole_file() {
+        // the lowest quality characters only appear at the end of each chunk
+        let n_records = 30_000;
+        let content: String = (0..n_records)
+            .map(|i| {
+                let qual = match i {
+                    14_999 => "#hhh",
+                    29_999 => "BBhh",
+                    _ => "hhhh",
+                };
+                format!("@SEQ01:1:FC1:2:1:{}:1 1:N:0:ACGT\nACGT\n+\n{}\n", i, qual)
+            })
+            .collect();
+
+        check_merged_chunks(&content, n_records / 2);
+    }
+
+    #[test]
+    fn grammar_lanes_without_flow_cell_are_counted() {
+        let grammars: Vec<NameGrammar> =
+            vec![r"^(?P<instrument>[^_]+)_L(?P<lane>\d+)_".parse().unwrap()];
+        let opts = NameOpts {
+            grammars: &grammars,
+            ..Default::default()
+        }
+        .with_grammar_groups();
+        let mut obs = FastqStats::new();
+        for rname in ["SEQ01_L2_1", "SEQ01_L2_2", "SEQ01_L3_3"] {
+            obs.process_name(rname.as_bytes(), None, &opts);
+        }
+
+        assert_eq!(obs.lanes.get("unknown:2"), Some(&2));
+        assert_eq!(obs.lanes.get("unknown:3"), Some(&1));
+        assert!(obs.flow_cell_ids.is_empty());
+    }
+
     #[test]
     fn it_works() {
         let expected = 4;
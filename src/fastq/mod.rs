@@ -1,4 +1,7 @@
 //! Process raw sequencing [FASTQ](https://en.wikipedia.org/wiki/FASTQ_format) files.
 
-// pub mod filter;
+pub mod composition;
+pub mod encoding;
+pub mod filter;
 pub mod info_stats;
+pub mod quality;
@@ -0,0 +1,300 @@
+//! Per-cycle base quality profiles for FASTQ records.
+
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+
+/// ASCII offset for Phred+33 (Sanger, Illumina >= v1.8) encoded quality scores
+pub const PHRED33_OFFSET: u8 = 33;
+
+/// Lowest printable ASCII character allowed in a quality string (`!`)
+const MIN_QUAL_ASCII: u8 = 33;
+
+/// Highest printable ASCII character allowed in a quality string (`~`)
+const MAX_QUAL_ASCII: u8 = 126;
+
+/// Number of distinct quality characters that can be tallied
+const N_QUAL_ASCII: usize = (MAX_QUAL_ASCII - MIN_QUAL_ASCII + 1) as usize;
+
+/// Default number of cycles to track in per-cycle profiles
+pub const DEFAULT_MAX_CYCLES: usize = 1_000;
+
+/// Distribution of quality scores at each cycle (position) of the reads
+///
+/// Quality characters are tallied as raw ASCII values and only converted to scores when summarizing,
+/// so the same tallies can be interpreted with whichever encoding offset the file uses.
+#[derive(Debug, Clone)]
+pub struct QualityProfile {
+    /// ASCII offset used to convert quality characters to scores
+    offset: u8,
+
+    /// Counts of each quality character, for each cycle
+    cycles: Vec<[u64; N_QUAL_ASCII]>,
+}
+
+/// Summary of the quality scores observed at a single cycle
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CycleQuality {
+    /// Mean quality score
+    pub mean: f64,
+
+    /// Median quality score
+    pub median: i16,
+
+    /// First quartile (25th percentile) of the quality scores
+    pub lower_quartile: i16,
+
+    /// Third quartile (75th percentile) of the quality scores
+    pub upper_quartile: i16,
+
+    /// 10th percentile of the quality scores
+    pub percentile_10: i16,
+
+    /// 90th percentile of the quality scores
+    pub percentile_90: i16,
+}
+
+/// Raw tallies of a [`QualityProfile`], as saved in a stats file
+///
+/// The offset isn't saved, since it comes from the quality encoding detected across all the records.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QualityProfileState {
+    /// Counts of each quality character from `!` to `~`, for each cycle
+    cycles: Vec<Vec<u64>>,
+}
+
+impl From<&QualityProfile> for QualityProfileState {
+    fn from(profile: &QualityProfile) -> Self {
+        Self {
+            cycles: profile
+                .cycles
+                .iter()
+                .map(|counts| counts.to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<QualityProfileState> for QualityProfile {
+    type Error = &'static str;
+
+    fn try_from(state: QualityProfileState) -> Result<Self, Self::Error> {
+        let cycles = state
+            .cycles
+            .into_iter()
+            .map(|counts| counts.try_into())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "Each cycle of a quality profile must have a count for every quality character from `!` to `~`.")?;
+
+        Ok(Self {
+            cycles,
+            ..Self::default()
+        })
+    }
+}
+
+impl Default for QualityProfile {
+    fn default() -> Self {
+        Self {
+            offset: PHRED33_OFFSET,
+            cycles: vec![],
+        }
+    }
+}
+
+impl QualityProfile {
+    /// Create a new, empty quality profile
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the ASCII offset used to convert quality characters to scores
+    pub fn set_offset(&mut self, offset: u8) {
+        self.offset = offset;
+    }
+
+    /// Tally the quality characters of a single record, up to the first `max_cycles` cycles
+    ///
+    /// Long reads would otherwise need tallies for every one of their cycles.
+    pub fn update(&mut self, qual: &[u8], max_cycles: usize) {
+        let qual = &qual[..qual.len().min(max_cycles)];
+        if self.cycles.len() < qual.len() {
+            self.cycles.resize(qual.len(), [0; N_QUAL_ASCII]);
+        }
+
+        for (counts, q) in self.cycles.iter_mut().zip(qual) {
+            counts[qual_index(*q)] += 1;
+        }
+    }
+
+    /// Add the tallies from another quality profile into this one
+    pub fn merge(&mut self, other: &Self) {
+        if self.cycles.len() < other.cycles.len() {
+            self.cycles.resize(other.cycles.len(), [0; N_QUAL_ASCII]);
+        }
+
+        for (counts, other_counts) in self.cycles.iter_mut().zip(&other.cycles) {
+            for (n, other_n) in counts.iter_mut().zip(other_counts) {
+                *n += other_n;
+            }
+        }
+    }
+
+    /// Number of cycles observed so far
+    pub fn n_cycles(&self) -> usize {
+        self.cycles.len()
+    }
+
+    /// Summarize the quality scores at a single cycle (0-based)
+    pub fn cycle_summary(&self, cycle: usize) -> Option<CycleQuality> {
+        let counts = self.cycles.get(cycle)?;
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+
+        let sum: i64 = counts
+            .iter()
+            .enumerate()
+            .map(|(i, n)| self.score(i) as i64 * *n as i64)
+            .sum();
+
+        Some(CycleQuality {
+            mean: sum as f64 / total as f64,
+            median: self.percentile(counts, total, 0.5),
+            lower_quartile: self.percentile(counts, total, 0.25),
+            upper_quartile: self.percentile(counts, total, 0.75),
+            percentile_10: self.percentile(counts, total, 0.1),
+            percentile_90: self.percentile(counts, total, 0.9),
+        })
+    }
+
+    /// Quality score for the character tallied at a given index
+    fn score(&self, idx: usize) -> i16 {
+        (idx as i16 + MIN_QUAL_ASCII as i16) - self.offset as i16
+    }
+
+    /// Nearest-rank percentile of the scores tallied in `counts`
+    fn percentile(&self, counts: &[u64; N_QUAL_ASCII], total: u64, p: f64) -> i16 {
+        // the rank of the observation to report (1-based)
+        let rank = ((p * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+
+        for (i, n) in counts.iter().enumerate() {
+            seen += n;
+            if seen >= rank {
+                return self.score(i);
+            }
+        }
+
+        // unreachable since `rank <= total`, but fall back on the highest score
+        self.score(N_QUAL_ASCII - 1)
+    }
+}
+
+/// Index into the tallies for a quality character, clamping anything outside the printable range
+fn qual_index(q: u8) -> usize {
+    (q.clamp(MIN_QUAL_ASCII, MAX_QUAL_ASCII) - MIN_QUAL_ASCII) as usize
+}
+
+impl Serialize for QualityProfile {
+    /// Serialize the summary of each cycle, keyed by the 1-based cycle number
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.n_cycles()))?;
+        for cycle in 0..self.n_cycles() {
+            if let Some(summary) = self.cycle_summary(cycle) {
+                map.serialize_entry(&(cycle + 1).to_string(), &summary)?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(quals: &[&str]) -> QualityProfile {
+        let mut profile = QualityProfile::new();
+        for q in quals {
+            profile.update(q.as_bytes(), DEFAULT_MAX_CYCLES);
+        }
+
+        profile
+    }
+
+    #[test]
+    fn empty_profile_has_no_cycles() {
+        let obs = QualityProfile::new();
+
+        assert_eq!(obs.n_cycles(), 0);
+        assert_eq!(obs.cycle_summary(0), None);
+    }
+
+    #[test]
+    fn cycles_grow_with_longest_record() {
+        let obs = profile(&["II", "IIII", "I"]);
+
+        assert_eq!(obs.n_cycles(), 4);
+    }
+
+    #[test]
+    fn cycles_stop_at_limit() {
+        let mut obs = QualityProfile::new();
+        obs.update(b"IIIIII", 4);
+
+        assert_eq!(obs.n_cycles(), 4);
+    }
+
+    #[test]
+    fn single_score_summary() {
+        // 'I' is Phred 40 in Phred+33
+        let obs = profile(&["I", "I"]).cycle_summary(0).unwrap();
+        let exp = CycleQuality {
+            mean: 40.0,
+            median: 40,
+            lower_quartile: 40,
+            upper_quartile: 40,
+            percentile_10: 40,
+            percentile_90: 40,
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        // Phred scores 0, 10, 20, ..., 90 at the first cycle
+        let quals = ["!", "+", "5", "?", "I", "S", "]", "g", "q", "{"];
+        let obs = profile(&quals).cycle_summary(0).unwrap();
+
+        assert_eq!(obs.mean, 45.0);
+        assert_eq!(obs.median, 40);
+        assert_eq!(obs.lower_quartile, 20);
+        assert_eq!(obs.upper_quartile, 70);
+        assert_eq!(obs.percentile_10, 0);
+        assert_eq!(obs.percentile_90, 80);
+    }
+
+    #[test]
+    fn merged_profiles_equal_combined_profile() {
+        let mut obs = profile(&["II", "#"]);
+        obs.merge(&profile(&["5", "III"]));
+        let exp = profile(&["II", "#", "5", "III"]);
+
+        assert_eq!(obs.cycles, exp.cycles);
+    }
+
+    #[test]
+    fn offset_shifts_scores() {
+        let mut obs = profile(&["h"]);
+        obs.set_offset(64);
+
+        assert_eq!(obs.cycle_summary(0).unwrap().median, 40);
+    }
+
+    #[test]
+    fn later_cycles_only_count_longer_records() {
+        let obs = profile(&["!I", "!"]).cycle_summary(1).unwrap();
+
+        assert_eq!(obs.mean, 40.0);
+    }
+}
@@ -5,21 +5,36 @@
 mod align;
 mod cli;
 mod data;
+mod error;
+mod fasta;
 mod fastq;
 mod record;
 mod utils;
 
 use clap::Parser;
 use cli::{Cli, CliOpt, SubCmd};
+use std::process::ExitCode;
+use utils::progress;
 
-fn main() -> anyhow::Result<()> {
+fn main() -> ExitCode {
     let args = Cli::parse();
+    if let Err(e) = progress::init_logging(args.verbose.log_level_filter()) {
+        eprintln!("Error: Cannot set up logging. {}", e);
+    }
 
-    match args.cmd {
+    let result = match args.cmd {
         SubCmd::Info(info_opts) => info_opts.exec(),
         SubCmd::Filter(filter_opts) => filter_opts.exec(),
-        SubCmd::Organize => {
-            todo!()
+        SubCmd::Faidx(faidx_opts) => faidx_opts.exec(),
+        SubCmd::Genome(genome_opts) => genome_opts.exec(),
+        SubCmd::Organize(org_opts) => org_opts.exec(),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            error::exit_code(&e)
         }
     }
 
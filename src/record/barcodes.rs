@@ -0,0 +1,532 @@
+//! Index (barcode) sequences from read names, and how well they match a sample sheet.
+//!
+//! Sample sheets can either be Illumina sample sheets, where the samples are listed in a `[Data]` (or `[BCLConvert_Data]`) section with `Sample_ID`, `index`, and `index2` columns,
+//! or a simple comma- or tab-separated file with `sample,i7[,i5]` on each line.
+
+use super::stats::merge_counts;
+use csv::{ReaderBuilder, StringRecord};
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use std::{cmp::Reverse, collections::HashMap, fs, path::Path, sync::Arc};
+use thiserror::Error;
+
+/// Default number of the most common index sequences to report
+pub const DEFAULT_TOP_INDEXES: usize = 10;
+
+/// Separator between the i7 and i5 indexes of a dual-indexed read
+const DUAL_INDEX_SEPARATOR_ASCII_CODE: u8 = b'+';
+
+/// Columns of an Illumina sample sheet with the sample ID, i7 index, and i5 index
+const SAMPLE_SHEET_COLUMNS: [&str; 3] = ["sample_id", "index", "index2"];
+
+/// Sample ID, i7 index, and i5 index (if any) from a row of a sample sheet
+type SampleRow = (String, String, Option<String>);
+
+/// Errors when reading a sample sheet
+#[derive(Debug, Error)]
+pub enum SampleSheetError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error("The sample sheet has no `{0}` column.")]
+    MissingColumn(&'static str),
+
+    #[error("Sample `{sample}` has an invalid index sequence `{index}`.")]
+    InvalidIndex { sample: String, index: String },
+
+    #[error("The sample sheet doesn't list any samples.")]
+    NoSamples,
+}
+
+/// Expected index sequences of a single sample
+#[derive(Debug, Clone, PartialEq)]
+struct SampleIndex {
+    /// i7 index sequence
+    i7: Vec<u8>,
+
+    /// i5 index sequence, for dual-indexed samples
+    i5: Option<Vec<u8>>,
+}
+
+/// Expected index sequences of all samples in a sequencing run
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleSheet {
+    samples: Vec<SampleIndex>,
+}
+
+/// Whether an index sequence is made up of nucleotides
+fn is_index_sequence(seq: &str) -> bool {
+    !seq.is_empty() && seq.bytes().all(|b| b"ACGTNacgtn".contains(&b))
+}
+
+impl SampleSheet {
+    /// Read a sample sheet from a file, for use as a command line argument
+    pub fn from_path_arg(path: &str) -> Result<Arc<Self>, SampleSheetError> {
+        Self::from_path(Path::new(path)).map(Arc::new)
+    }
+
+    /// Read a sample sheet from a file
+    pub fn from_path(path: &Path) -> Result<Self, SampleSheetError> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents)
+    }
+
+    /// Parse the contents of a sample sheet
+    pub fn parse(contents: &str) -> Result<Self, SampleSheetError> {
+        let data_section = contents.lines().position(|l| {
+            let section = l.trim().trim_end_matches(',').to_ascii_lowercase();
+            section == "[data]" || section == "[bclconvert_data]"
+        });
+
+        let rows: Vec<SampleRow> = match data_section {
+            Some(i) => {
+                let data: Vec<&str> = contents
+                    .lines()
+                    .skip(i + 1)
+                    .take_while(|l| !l.trim_start().starts_with('['))
+                    .collect();
+                Self::parse_illumina_data(&data.join("\n"))?
+            }
+            None => Self::parse_simple(contents)?,
+        };
+
+        let mut samples = vec![];
+        for (sample, i7, i5) in rows {
+            for index in std::iter::once(&i7).chain(i5.iter()) {
+                if !is_index_sequence(index) {
+                    return Err(SampleSheetError::InvalidIndex {
+                        sample,
+                        index: index.clone(),
+                    });
+                }
+            }
+            samples.push(SampleIndex {
+                i7: i7.to_ascii_uppercase().into_bytes(),
+                i5: i5.map(|i| i.to_ascii_uppercase().into_bytes()),
+            });
+        }
+
+        if samples.is_empty() {
+            return Err(SampleSheetError::NoSamples);
+        }
+
+        Ok(Self { samples })
+    }
+
+    /// Samples and their indexes from the data section of an Illumina sample sheet
+    fn parse_illumina_data(data: &str) -> Result<Vec<SampleRow>, SampleSheetError> {
+        let mut reader = ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(data.as_bytes());
+        let header: Vec<String> = reader
+            .headers()?
+            .iter()
+            .map(|h| h.trim().to_ascii_lowercase())
+            .collect();
+        let column = |name: &'static str| header.iter().position(|h| h == name);
+
+        let sample_col = column(SAMPLE_SHEET_COLUMNS[0])
+            .ok_or(SampleSheetError::MissingColumn(SAMPLE_SHEET_COLUMNS[0]))?;
+        let i7_col = column(SAMPLE_SHEET_COLUMNS[1])
+            .ok_or(SampleSheetError::MissingColumn(SAMPLE_SHEET_COLUMNS[1]))?;
+        let i5_col = column(SAMPLE_SHEET_COLUMNS[2]);
+
+        let mut rows = vec![];
+        for record in reader.records() {
+            let record = record?;
+            let field = |i: usize| record.get(i).map(str::trim).unwrap_or_default();
+            if field(sample_col).is_empty() {
+                continue;
+            }
+
+            let i5 = i5_col
+                .map(field)
+                .filter(|i| !i.is_empty())
+                .map(str::to_string);
+            rows.push((field(sample_col).to_string(), field(i7_col).to_string(), i5));
+        }
+
+        Ok(rows)
+    }
+
+    /// Samples and their indexes from a simple `sample,i7[,i5]` file, with an optional header
+    fn parse_simple(contents: &str) -> Result<Vec<SampleRow>, SampleSheetError> {
+        let delimiter = if contents.contains('\t') { b'\t' } else { b',' };
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_reader(contents.as_bytes());
+
+        let mut rows = vec![];
+        for (i, record) in reader.records().enumerate() {
+            let record: StringRecord = record?;
+            let sample = record.get(0).map(str::trim).unwrap_or_default();
+            let i7 = record.get(1).map(str::trim).unwrap_or_default();
+
+            // skip a header line
+            if sample.is_empty() || (i == 0 && !is_index_sequence(i7)) {
+                continue;
+            }
+
+            let i5 = record
+                .get(2)
+                .map(str::trim)
+                .filter(|i| !i.is_empty())
+                .map(str::to_string);
+            rows.push((sample.to_string(), i7.to_string(), i5));
+        }
+
+        Ok(rows)
+    }
+
+    /// Determine how well an observed index sequence (`i7` or `i7+i5`) matches the expected samples
+    pub fn classify(&self, index: &[u8]) -> IndexMatch {
+        let mut parts = index.splitn(2, |x| *x == DUAL_INDEX_SEPARATOR_ASCII_CODE);
+        let i7 = parts.next().unwrap_or_default();
+        let i5 = parts.next();
+
+        // total number of mismatches to each sample, if the lengths are compatible
+        let mismatches = |s: &SampleIndex| -> Option<usize> {
+            let i7_mm = hamming(i7, &s.i7)?;
+            let i5_mm = match (i5, &s.i5) {
+                (Some(obs), Some(exp)) => hamming(obs, exp)?,
+                (None, None) => 0,
+                _ => return None,
+            };
+            Some(i7_mm + i5_mm)
+        };
+
+        let best = self.samples.iter().filter_map(mismatches).min();
+        match best {
+            Some(0) => IndexMatch::Exact,
+            Some(1) => IndexMatch::OneMismatch,
+            _ => {
+                let valid_i7 = self.samples.iter().any(|s| s.i7 == i7);
+                let valid_i5 =
+                    i5.is_some_and(|i5| self.samples.iter().any(|s| s.i5.as_deref() == Some(i5)));
+                if valid_i7 && valid_i5 {
+                    IndexMatch::Hopped
+                } else {
+                    IndexMatch::Unknown
+                }
+            }
+        }
+    }
+}
+
+/// Number of mismatches between two sequences of the same length
+fn hamming(a: &[u8], b: &[u8]) -> Option<usize> {
+    if a.len() != b.len() {
+        return None;
+    }
+
+    Some(
+        a.iter()
+            .zip(b)
+            .filter(|(x, y)| !x.eq_ignore_ascii_case(y))
+            .count(),
+    )
+}
+
+/// How well an observed index sequence matches the samples in a sample sheet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexMatch {
+    /// Matches a sample exactly
+    Exact,
+
+    /// Matches a sample with a single mismatch
+    OneMismatch,
+
+    /// The i7 and i5 indexes each match a sample exactly, but not the same sample
+    Hopped,
+
+    /// Doesn't match any sample
+    Unknown,
+}
+
+/// Fractions of reads whose index sequences match the samples in a sample sheet
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct IndexReport {
+    /// Fraction of reads matching a sample exactly
+    pub exact: f64,
+
+    /// Fraction of reads matching a sample with a single mismatch
+    pub one_mismatch: f64,
+
+    /// Fraction of reads whose i7 and i5 indexes match different samples
+    pub hopped: f64,
+
+    /// Fraction of reads that don't match any sample
+    pub unknown: f64,
+}
+
+/// Tally of the index sequences in read names
+#[derive(Debug, Clone)]
+pub struct IndexTally {
+    /// Number of reads with each index sequence
+    counts: HashMap<String, u64>,
+
+    /// Number of the most common index sequences to report
+    n_top: usize,
+
+    /// Expected index sequences to check the observed ones against
+    sample_sheet: Option<Arc<SampleSheet>>,
+}
+
+impl Default for IndexTally {
+    fn default() -> Self {
+        Self {
+            counts: HashMap::new(),
+            n_top: DEFAULT_TOP_INDEXES,
+            sample_sheet: None,
+        }
+    }
+}
+
+impl IndexTally {
+    /// Create a new, empty tally
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how the tally is reported: the number of the most common index sequences, and an optional sample sheet
+    pub fn set_report(&mut self, n_top: usize, sample_sheet: Option<Arc<SampleSheet>>) {
+        self.n_top = n_top;
+        self.sample_sheet = sample_sheet;
+    }
+
+    /// Include the index sequence of a read
+    ///
+    /// Index sequences are counted in uppercase, like the indexes in a sample sheet.
+    pub fn update(&mut self, index: &[u8]) {
+        if index.is_empty() {
+            return;
+        }
+
+        let mut index = String::from_utf8_lossy(index);
+        if index.bytes().any(|b| b.is_ascii_lowercase()) {
+            index.to_mut().make_ascii_uppercase();
+        }
+        match self.counts.get_mut(index.as_ref()) {
+            Some(n) => *n += 1,
+            None => {
+                self.counts.insert(index.into_owned(), 1);
+            }
+        }
+    }
+
+    /// Tally of index sequences that were counted earlier, like those loaded from a stats file
+    pub fn from_counts(counts: HashMap<String, u64>) -> Self {
+        Self {
+            counts,
+            ..Self::default()
+        }
+    }
+
+    /// Number of reads with each index sequence
+    pub fn counts(&self) -> &HashMap<String, u64> {
+        &self.counts
+    }
+
+    /// Combine the index sequences from another tally with this one
+    pub fn merge(&mut self, other: &Self) {
+        merge_counts(&mut self.counts, &other.counts);
+    }
+
+    /// Number of reads with an index sequence
+    pub fn n_reads(&self) -> u64 {
+        self.counts.values().sum()
+    }
+
+    /// Number of distinct index sequences
+    pub fn n_distinct(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// The most common index sequences and their counts, from most to least common
+    pub fn top(&self) -> Vec<(&str, u64)> {
+        let mut counts: Vec<(&str, u64)> =
+            self.counts.iter().map(|(k, n)| (k.as_str(), *n)).collect();
+        counts.sort_unstable_by_key(|(k, n)| (Reverse(*n), *k));
+        counts.truncate(self.n_top);
+
+        counts
+    }
+
+    /// How well the index sequences match the sample sheet, if one was given
+    pub fn report(&self) -> Option<IndexReport> {
+        let sheet = self.sample_sheet.as_ref()?;
+        let total = self.n_reads();
+        if total == 0 {
+            return Some(IndexReport::default());
+        }
+
+        let mut report = IndexReport::default();
+        for (index, n) in &self.counts {
+            let frac = *n as f64 / total as f64;
+            match sheet.classify(index.as_bytes()) {
+                IndexMatch::Exact => report.exact += frac,
+                IndexMatch::OneMismatch => report.one_mismatch += frac,
+                IndexMatch::Hopped => report.hopped += frac,
+                IndexMatch::Unknown => report.unknown += frac,
+            }
+        }
+
+        Some(report)
+    }
+}
+
+/// A single common index sequence
+#[derive(Serialize)]
+struct TopIndex<'a> {
+    index: &'a str,
+    reads: u64,
+    fraction: f64,
+}
+
+impl Serialize for IndexTally {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let total = self.n_reads();
+        let top: Vec<TopIndex> = self
+            .top()
+            .into_iter()
+            .map(|(index, reads)| TopIndex {
+                index,
+                reads,
+                fraction: reads as f64 / total as f64,
+            })
+            .collect();
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("reads", &total)?;
+        map.serialize_entry("distinct", &self.n_distinct())?;
+        map.serialize_entry("top", &top)?;
+        if let Some(report) = self.report() {
+            map.serialize_entry("sample_sheet", &report)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ILLUMINA_SHEET: &str = "[Header]
+IEMFileVersion,4
+
+[Data]
+Sample_ID,Sample_Name,index,index2
+s1,,AAAAAAAA,CCCCCCCC
+s2,,GGGGGGGG,TTTTTTTT
+
+[Settings]
+Adapter,AGATCGGAAGAGC
+";
+
+    #[test]
+    fn illumina_sample_sheet_is_parsed() {
+        let obs = SampleSheet::parse(ILLUMINA_SHEET).unwrap();
+
+        assert_eq!(obs.samples.len(), 2);
+        assert_eq!(obs.samples[1].i7, b"GGGGGGGG");
+        assert_eq!(obs.samples[1].i5.as_deref(), Some(b"TTTTTTTT".as_slice()));
+    }
+
+    #[test]
+    fn simple_sample_sheet_is_parsed() {
+        let obs = SampleSheet::parse("sample\ti7\ns1\tACGTAC\ns2\tTGCATG\n").unwrap();
+
+        assert_eq!(obs.samples.len(), 2);
+        assert_eq!(obs.samples[0].i5, None);
+    }
+
+    #[test]
+    fn sample_sheet_with_bad_index_is_err() {
+        let obs = SampleSheet::parse("s1,ACGT\ns2,NOTANINDEX\n");
+
+        assert!(
+            matches!(obs, Err(SampleSheetError::InvalidIndex { sample, .. }) if sample == "s2")
+        );
+    }
+
+    #[test]
+    fn indexes_are_classified() {
+        let sheet = SampleSheet::parse(ILLUMINA_SHEET).unwrap();
+
+        assert_eq!(sheet.classify(b"AAAAAAAA+CCCCCCCC"), IndexMatch::Exact);
+        assert_eq!(
+            sheet.classify(b"AAAAAAAT+CCCCCCCC"),
+            IndexMatch::OneMismatch
+        );
+        assert_eq!(sheet.classify(b"AAAAAAAA+TTTTTTTT"), IndexMatch::Hopped);
+        assert_eq!(sheet.classify(b"AAAAAAAA"), IndexMatch::Unknown);
+        assert_eq!(sheet.classify(b"ATATATAT+CCCCCCCC"), IndexMatch::Unknown);
+    }
+
+    #[test]
+    fn top_indexes_are_most_common() {
+        let mut tally = IndexTally::new();
+        tally.set_report(2, None);
+        for index in ["AC", "GT", "GT", "TT", "TT", "TT"] {
+            tally.update(index.as_bytes());
+        }
+
+        assert_eq!(tally.top(), vec![("TT", 3), ("GT", 2)]);
+        assert_eq!(tally.n_distinct(), 3);
+    }
+
+    #[test]
+    fn report_has_fraction_of_reads() {
+        let mut tally = IndexTally::new();
+        tally.set_report(
+            DEFAULT_TOP_INDEXES,
+            Some(Arc::new(SampleSheet::parse(ILLUMINA_SHEET).unwrap())),
+        );
+        for index in [
+            "AAAAAAAA+CCCCCCCC",
+            "AAAAAAAA+CCCCCCCC",
+            "GGGGGGGG+CCCCCCCC",
+            "NNNNNNNN+NNNNNNNN",
+        ] {
+            tally.update(index.as_bytes());
+        }
+        let exp = IndexReport {
+            exact: 0.5,
+            one_mismatch: 0.0,
+            hopped: 0.25,
+            unknown: 0.25,
+        };
+
+        assert_eq!(tally.report(), Some(exp));
+    }
+
+    #[test]
+    fn indexes_are_classified_in_any_case() {
+        let mut tally = IndexTally::new();
+        tally.set_report(
+            DEFAULT_TOP_INDEXES,
+            Some(Arc::new(
+                SampleSheet::parse(&ILLUMINA_SHEET.to_ascii_lowercase()).unwrap(),
+            )),
+        );
+        for index in [
+            "aaaaaaaa+CCCCCCCC",
+            "AAAAAAAA+cccccccc",
+            "aaaaaaaa+tttttttt",
+        ] {
+            tally.update(index.as_bytes());
+        }
+        let exp = IndexReport {
+            exact: 2.0 / 3.0,
+            hopped: 1.0 / 3.0,
+            ..Default::default()
+        };
+
+        assert_eq!(tally.top()[0], ("AAAAAAAA+CCCCCCCC", 2));
+        assert_eq!(tally.report(), Some(exp));
+    }
+}
@@ -6,4 +6,22 @@ use thiserror::Error;
 pub enum RecordError {
     #[error("Could not determine what type of information is encoded in the record name.")]
     UncertainRecordNameFormat,
+
+    #[error("Could not parse the {0} from a Casava 1.8 record name.")]
+    InvalidCasavaField(&'static str),
+
+    #[error("Could not parse the {0} from a Sequence Read Archive record name.")]
+    InvalidSraField(&'static str),
+
+    #[error("Could not parse the {0} from an MGI/BGI record name.")]
+    InvalidMgiField(&'static str),
+
+    #[error("Could not parse the {0} from a PacBio record name.")]
+    InvalidPacBioField(&'static str),
+
+    #[error("Could not parse the {0} from an Ultima Genomics record name.")]
+    InvalidUltimaField(&'static str),
+
+    #[error("Could not parse the {0} from an Oxford Nanopore record name.")]
+    InvalidNanoporeField(&'static str),
 }
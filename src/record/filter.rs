@@ -1,20 +1,16 @@
 //! Methods for filtering HTS records.
 
-use std::{
-    fs::File,
-    io::{self, Write},
-    path::PathBuf,
+use crate::{
+    error::BjtError,
+    utils::sink::{OutputOpts, OutputSink},
 };
 
 pub trait RecordFilter {
-    /// Output file name (or STDOUT if file not given)
-    fn output(&self) -> Option<&PathBuf>;
+    /// Where and how to write the filtered records
+    fn output(&self) -> &OutputOpts;
 
-    /// Return what type of writer to use here (STDOUT, a file, or something else)
-    fn writer_output(&self) -> Result<Box<dyn Write>, io::Error> {
-        match self.output() {
-            Some(path) => File::open(path).map(|f| Box::new(f) as Box<dyn Write>),
-            None => Ok(Box::new(io::stdout())),
-        }
+    /// Open the output for writing (a file, STDOUT, or something else), compressed as requested
+    fn writer_output(&self) -> Result<OutputSink, BjtError> {
+        self.output().sink()
     }
 }
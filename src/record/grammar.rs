@@ -0,0 +1,182 @@
+//! User-defined read name formats.
+//!
+//! A grammar is a regular expression with named capture groups for the pieces of information in a read name.
+//! Any of these capture groups can be used, and at least one is required:
+//!
+//! - `instrument`: instrument name
+//! - `flowcell`: flow cell ID
+//! - `lane`: flow cell lane, counted under an `unknown` flow cell if `flowcell` isn't captured
+//! - `barcode`: sample index or barcode sequence
+//!
+//! For example, `^(?P<instrument>[^_]+)_(?P<flowcell>[^_]+)_` parses names like `SEQ01_FC123_42`.
+
+use regex::bytes::{Captures, Regex};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Capture group for the instrument name
+const INSTRUMENT_GROUP: &str = "instrument";
+
+/// Capture group for the flow cell ID
+const FLOW_CELL_GROUP: &str = "flowcell";
+
+/// Capture group for the flow cell lane
+const LANE_GROUP: &str = "lane";
+
+/// Capture group for the sample index or barcode
+const BARCODE_GROUP: &str = "barcode";
+
+/// Capture groups that a grammar can use
+const KNOWN_GROUPS: [&str; 4] = [INSTRUMENT_GROUP, FLOW_CELL_GROUP, LANE_GROUP, BARCODE_GROUP];
+
+/// Errors when defining a read name grammar
+#[derive(Debug, Error)]
+pub enum GrammarError {
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+
+    #[error("Unknown capture group `{0}`. Expected one of `instrument`, `flowcell`, `lane`, or `barcode`.")]
+    UnknownGroup(String),
+
+    #[error("No named capture groups. Use at least one of `(?P<instrument>...)`, `(?P<flowcell>...)`, `(?P<lane>...)`, or `(?P<barcode>...)`.")]
+    NoGroups,
+}
+
+/// Read name format defined by a regular expression with named capture groups
+#[derive(Debug, Clone)]
+pub struct NameGrammar {
+    regex: Regex,
+}
+
+impl NameGrammar {
+    /// Whether the grammar captures a given group
+    fn has_group(&self, group: &str) -> bool {
+        self.regex.capture_names().flatten().any(|g| g == group)
+    }
+
+    /// Whether the grammar captures the instrument name
+    pub fn has_instrument(&self) -> bool {
+        self.has_group(INSTRUMENT_GROUP)
+    }
+
+    /// Whether the grammar captures the flow cell ID or lane
+    pub fn has_flow_cell(&self) -> bool {
+        self.has_group(FLOW_CELL_GROUP) || self.has_group(LANE_GROUP)
+    }
+
+    /// Whether the grammar captures the sample index or barcode
+    pub fn has_barcode(&self) -> bool {
+        self.has_group(BARCODE_GROUP)
+    }
+
+    /// Parse a read name, if it matches this grammar
+    pub fn parse<'id>(&self, rname: &'id [u8]) -> Option<GrammarName<'id>> {
+        self.regex.captures(rname).map(|caps| GrammarName {
+            instrument: capture(&caps, INSTRUMENT_GROUP),
+            flow_cell: capture(&caps, FLOW_CELL_GROUP),
+            lane: capture(&caps, LANE_GROUP),
+            barcode: capture(&caps, BARCODE_GROUP),
+        })
+    }
+}
+
+impl FromStr for NameGrammar {
+    type Err = GrammarError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let regex = Regex::new(s)?;
+
+        let groups: Vec<&str> = regex.capture_names().flatten().collect();
+        if groups.is_empty() {
+            return Err(GrammarError::NoGroups);
+        }
+        if let Some(unknown) = groups.iter().find(|g| !KNOWN_GROUPS.contains(g)) {
+            return Err(GrammarError::UnknownGroup(unknown.to_string()));
+        }
+
+        Ok(NameGrammar { regex })
+    }
+}
+
+/// Get a non-empty capture group from a match
+fn capture<'id>(caps: &Captures<'id>, group: &str) -> Option<&'id [u8]> {
+    caps.name(group)
+        .map(|m| m.as_bytes())
+        .filter(|m| !m.is_empty())
+}
+
+/// Pieces of information parsed from a read name with a user-defined grammar
+#[derive(Debug, PartialEq)]
+pub struct GrammarName<'id> {
+    /// Instrument name
+    pub instrument: Option<&'id [u8]>,
+
+    /// Flow cell ID
+    pub flow_cell: Option<&'id [u8]>,
+
+    /// Flow cell lane
+    pub lane: Option<&'id [u8]>,
+
+    /// Sample index or barcode
+    pub barcode: Option<&'id [u8]>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_groups_are_parsed() {
+        let grammar: NameGrammar =
+            r"^(?P<instrument>[^_]+)_(?P<flowcell>[^_]+)_L(?P<lane>\d+)_(?P<barcode>[ACGT]+)"
+                .parse()
+                .unwrap();
+        let obs = grammar.parse(b"SEQ01_FC123_L2_ACGTAC_42").unwrap();
+        let exp = GrammarName {
+            instrument: Some(b"SEQ01"),
+            flow_cell: Some(b"FC123"),
+            lane: Some(b"2"),
+            barcode: Some(b"ACGTAC"),
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn captured_groups_are_reported() {
+        let grammar: NameGrammar = r"^(?P<instrument>[^_]+)_L(?P<lane>\d+)".parse().unwrap();
+
+        assert!(grammar.has_instrument());
+        assert!(grammar.has_flow_cell());
+        assert!(!grammar.has_barcode());
+    }
+
+    #[test]
+    fn missing_groups_are_none() {
+        let grammar: NameGrammar = r"^(?P<instrument>\w+)/\d+$".parse().unwrap();
+        let obs = grammar.parse(b"SEQ01/7").unwrap();
+
+        assert_eq!(obs.instrument, Some(b"SEQ01".as_slice()));
+        assert_eq!(obs.flow_cell, None);
+    }
+
+    #[test]
+    fn non_matching_name_is_none() {
+        let grammar: NameGrammar = r"^(?P<instrument>\w+)/\d+$".parse().unwrap();
+
+        assert_eq!(grammar.parse(b"no match here"), None);
+    }
+
+    #[test]
+    fn unknown_group_is_err() {
+        let obs = NameGrammar::from_str(r"(?P<machine>\w+)");
+
+        assert!(matches!(obs, Err(GrammarError::UnknownGroup(g)) if g == "machine"));
+    }
+
+    #[test]
+    fn unnamed_groups_are_err() {
+        let obs = NameGrammar::from_str(r"(\w+):(\w+)");
+
+        assert!(matches!(obs, Err(GrammarError::NoGroups)));
+    }
+}
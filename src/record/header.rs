@@ -1,6 +1,6 @@
 //! Header formats for FASTA and FASTQ files.
 
-use std::borrow::Cow;
+use std::str::FromStr;
 
 use super::error::RecordError;
 
@@ -19,62 +19,496 @@ pub const RNAME_SEPARATOR_ASCII_CODE: u8 = 32;
 /// This is "SRR" encoded in ASCII bytes.
 const SRA_RNAME_PREFIX: &[u8] = "SRR".as_bytes();
 
+/// Prefix of instrument names from Element Biosciences AVITI sequencers
+const AVITI_INSTRUMENT_PREFIX: &[u8] = b"AV";
+
+/// Separator between the fields of PacBio read names
+const PACBIO_SEPARATOR_ASCII_CODE: u8 = b'/';
+
+/// Separator between the parts of PacBio movie names and Ultima read names
+const UNDERSCORE_ASCII_CODE: u8 = b'_';
+
+/// Separator between the fields of Ultima read names
+const ULTIMA_SEPARATOR_ASCII_CODE: u8 = b'-';
+
 #[derive(Debug, PartialEq)]
 pub enum RecordName {
     CasavaV1_8,
     SequenceReadArchive,
+    ElementAviti,
+    MgiDnbseq,
+    PacBio,
+    Ultima,
+    Nanopore,
 }
 
 impl TryFrom<&[u8]> for RecordName {
     type Error = RecordError;
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        if value[0..3] == SRA_RNAME_PREFIX[0..3] {
-            return Ok(RecordName::SequenceReadArchive);
-        } else if value.contains(&ILLUMINA_SEPARATOR_ASCII_CODE) {
-            return Ok(RecordName::CasavaV1_8);
+        let id = value
+            .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+            .next()
+            .unwrap_or_default();
+
+        if value.starts_with(SRA_RNAME_PREFIX) {
+            Ok(RecordName::SequenceReadArchive)
+        } else if is_nanopore_name(value) {
+            Ok(RecordName::Nanopore)
+        } else if id.contains(&ILLUMINA_SEPARATOR_ASCII_CODE) {
+            // AVITI read names follow the Casava >= v1.8 layout
+            if id.starts_with(AVITI_INSTRUMENT_PREFIX) {
+                Ok(RecordName::ElementAviti)
+            } else {
+                Ok(RecordName::CasavaV1_8)
+            }
+        } else if MgiName::try_from(id).is_ok() {
+            Ok(RecordName::MgiDnbseq)
+        } else if PacBioName::try_from(id).is_ok() {
+            Ok(RecordName::PacBio)
+        } else if UltimaName::try_from(id).is_ok() {
+            Ok(RecordName::Ultima)
         } else {
-            return Err(RecordError::UncertainRecordNameFormat);
+            Err(RecordError::UncertainRecordNameFormat)
         }
     }
 }
 
+/// Whether a read name has the key-value pairs of an Oxford Nanopore read name
+fn is_nanopore_name(value: &[u8]) -> bool {
+    value
+        .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+        .skip(1)
+        .any(|kv| kv.starts_with(NANOPORE_RUN_ID_KEY))
+}
+
+/// Split a byte string into its leading ASCII digits and the rest
+fn split_digits(value: &[u8]) -> (&[u8], &[u8]) {
+    let n = value.iter().take_while(|x| x.is_ascii_digit()).count();
+    value.split_at(n)
+}
+
+/// Split a byte string into its leading ASCII uppercase letters and the rest
+fn split_uppercase(value: &[u8]) -> (&[u8], &[u8]) {
+    let n = value.iter().take_while(|x| x.is_ascii_uppercase()).count();
+    value.split_at(n)
+}
+
+/// Value of the filter flag for reads that failed the chastity filter
+const CASAVA_FILTERED_FLAG: &[u8] = b"Y";
+
+/// Value of the filter flag for reads that passed the chastity filter
+const CASAVA_UNFILTERED_FLAG: &[u8] = b"N";
+
 /// FASTQ ID from Casava-processed files, version >=1.8
-#[derive(Debug)]
-pub(crate) struct CasavaV1_8Name {
+///
+/// These have the layout `instrument:run:flowcell:lane:tile:x:y read:filtered:control:index`.
+/// Text fields borrow from the record name, so parsing a name doesn't allocate.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CasavaV1_8Name<'id> {
     /// Instrument name
-    instrument: Option<String>,
+    pub instrument: &'id [u8],
+
+    /// Run number on the instrument
+    pub run: u32,
+
+    /// Flow cell ID
+    pub flow_cell: &'id [u8],
 
     /// Flow cell lane
-    lane: Option<u8>,
+    pub lane: u16,
 
     /// Tile number within the flow cell lane
-    tile: Option<u8>,
+    pub tile: u32,
 
     /// x-coordinate of the cluster within the tile
-    x: Option<u8>,
+    pub x: u32,
 
     /// y-coordinate of the cluster within the tile
-    y: Option<u8>,
+    pub y: u32,
+
+    /// Information after the space, if the name has one
+    pub comment: Option<CasavaV1_8Comment<'id>>,
+}
+
+/// Read information following the space in a Casava >=1.8 read name
+#[derive(Debug, PartialEq)]
+pub(crate) struct CasavaV1_8Comment<'id> {
+    /// Member of a pair (1 or 2), or the read number for multi-read runs
+    pub read: u8,
+
+    /// Whether the read was filtered out by the chastity filter
+    pub filtered: bool,
+
+    /// Control bits (0 when none are on)
+    pub control: u16,
+
+    /// Index sequence, or the sample number if the index wasn't recorded
+    pub index: &'id [u8],
+}
+
+/// Parse an ASCII decimal number from a field in a read name
+fn parse_number<T: FromStr>(field: Option<&[u8]>) -> Option<T> {
+    field
+        .and_then(|f| std::str::from_utf8(f).ok())
+        .and_then(|f| f.parse().ok())
+}
+
+/// Parse an ASCII decimal number from a field in a Casava read name
+fn parse_casava_number<T: FromStr>(
+    field: Option<&[u8]>,
+    name: &'static str,
+) -> Result<T, RecordError> {
+    parse_number(field).ok_or(RecordError::InvalidCasavaField(name))
+}
 
-    /// Index number for a multi-plexed sample
-    /// (0 for no indexing)
-    sample_index: Option<u8>,
+/// Get a non-empty text field from a read name
+fn parse_text<'id>(field: Option<&'id [u8]>, name: &'static str) -> Result<&'id [u8], RecordError> {
+    field
+        .filter(|f| !f.is_empty())
+        .ok_or(RecordError::InvalidCasavaField(name))
+}
+
+impl<'id> TryFrom<&'id [u8]> for CasavaV1_8Name<'id> {
+    type Error = RecordError;
+    fn try_from(value: &'id [u8]) -> Result<Self, Self::Error> {
+        let mut groups = value.splitn(2, |x| *x == RNAME_SEPARATOR_ASCII_CODE);
+        let id = groups.next().unwrap_or_default();
+
+        let mut fields = id.split(|x| *x == ILLUMINA_SEPARATOR_ASCII_CODE);
+        let name = CasavaV1_8Name {
+            instrument: parse_text(fields.next(), "instrument")?,
+            run: parse_casava_number(fields.next(), "run number")?,
+            flow_cell: parse_text(fields.next(), "flow cell ID")?,
+            lane: parse_casava_number(fields.next(), "lane")?,
+            tile: parse_casava_number(fields.next(), "tile")?,
+            x: parse_casava_number(fields.next(), "x-coordinate")?,
+            y: parse_casava_number(fields.next(), "y-coordinate")?,
+            comment: groups.next().map(CasavaV1_8Comment::try_from).transpose()?,
+        };
+        if fields.next().is_some() {
+            return Err(RecordError::InvalidCasavaField("read ID"));
+        }
+
+        Ok(name)
+    }
+}
+
+impl<'id> TryFrom<&'id [u8]> for CasavaV1_8Comment<'id> {
+    type Error = RecordError;
+    fn try_from(value: &'id [u8]) -> Result<Self, Self::Error> {
+        // anything after another space is free text and isn't part of the Casava format
+        let comment = value
+            .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+            .next()
+            .unwrap_or_default();
+        let mut fields = comment.splitn(4, |x| *x == ILLUMINA_SEPARATOR_ASCII_CODE);
+
+        let read = parse_casava_number(fields.next(), "read number")?;
+        let filtered = match fields.next() {
+            Some(CASAVA_FILTERED_FLAG) => true,
+            Some(CASAVA_UNFILTERED_FLAG) => false,
+            _ => return Err(RecordError::InvalidCasavaField("filter flag")),
+        };
+        let control = parse_casava_number(fields.next(), "control number")?;
+        // the index may be missing from reads that weren't demultiplexed
+        let index = fields.next().unwrap_or_default();
+
+        Ok(CasavaV1_8Comment {
+            read,
+            filtered,
+            control,
+            index,
+        })
+    }
+}
+
+/// FASTQ ID from MGI/BGI DNBSEQ sequencers
+///
+/// These have the layout `{flowcell}L{lane}C{column}R{row}{read}/{member}`, like `V350012345L1C001R0010000001/1`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct MgiName<'id> {
+    /// Flow cell ID
+    pub flow_cell: &'id [u8],
+
+    /// Flow cell lane
+    pub lane: u8,
+
+    /// Field of view column
+    pub column: u16,
+
+    /// Field of view row
+    pub row: u16,
+
+    /// Read number within the field of view
+    pub read: u64,
 
     /// Member of a pair
-    pair_member: Option<u8>,
+    pub member: Option<u8>,
 }
 
+impl<'id> TryFrom<&'id [u8]> for MgiName<'id> {
+    type Error = RecordError;
+    fn try_from(value: &'id [u8]) -> Result<Self, Self::Error> {
+        let invalid = RecordError::InvalidMgiField;
+        let id = value
+            .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+            .next()
+            .unwrap_or_default();
+        let mut parts = id.splitn(2, |x| *x == PACBIO_SEPARATOR_ASCII_CODE);
+        let location = parts.next().unwrap_or_default();
+        let member = parts
+            .next()
+            .map(|m| parse_number(Some(m)).ok_or(invalid("pair member")))
+            .transpose()?;
+
+        // flow cell IDs are a few letters followed by digits
+        let (letters, rest) = split_uppercase(location);
+        let (digits, rest) = split_digits(rest);
+        if letters.is_empty() || digits.is_empty() {
+            return Err(invalid("flow cell ID"));
+        }
+        let flow_cell = &location[..letters.len() + digits.len()];
+
+        let rest = rest.strip_prefix(b"L").ok_or(invalid("lane"))?;
+        let (lane, rest) = split_digits(rest);
+        let rest = rest.strip_prefix(b"C").ok_or(invalid("column"))?;
+        let (column, rest) = split_digits(rest);
+        let rest = rest.strip_prefix(b"R").ok_or(invalid("row"))?;
+
+        // the row is always 3 digits, and is immediately followed by the read number
+        let (row_and_read, rest) = split_digits(rest);
+        if !rest.is_empty() || row_and_read.len() <= 3 {
+            return Err(invalid("read number"));
+        }
+        let (row, read) = row_and_read.split_at(3);
+
+        Ok(MgiName {
+            flow_cell,
+            lane: parse_number(Some(lane)).ok_or(invalid("lane"))?,
+            column: parse_number(Some(column)).ok_or(invalid("column"))?,
+            row: parse_number(Some(row)).ok_or(invalid("row"))?,
+            read: parse_number(Some(read)).ok_or(invalid("read number"))?,
+            member,
+        })
+    }
+}
+
+/// FASTQ ID from PacBio sequencers
+///
+/// These have the layout `{movie}/{zmw}/{ccs or start_end}`, like `m64011_190830_220126/1/ccs`.
+/// The movie name starts with the instrument name, like `m64011`, and identifies a single SMRT Cell.
+#[derive(Debug, PartialEq)]
+pub(crate) struct PacBioName<'id> {
+    /// Movie name
+    pub movie: &'id [u8],
+
+    /// Instrument name
+    pub instrument: &'id [u8],
+
+    /// Zero-mode waveguide (ZMW) the read came from
+    pub zmw: u64,
+
+    /// Read type (`ccs`) or subread coordinates (`start_end`), if any
+    pub suffix: Option<&'id [u8]>,
+}
+
+impl<'id> TryFrom<&'id [u8]> for PacBioName<'id> {
+    type Error = RecordError;
+    fn try_from(value: &'id [u8]) -> Result<Self, Self::Error> {
+        let invalid = RecordError::InvalidPacBioField;
+        let id = value
+            .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+            .next()
+            .unwrap_or_default();
+        let mut fields = id.splitn(3, |x| *x == PACBIO_SEPARATOR_ASCII_CODE);
+
+        let movie = fields.next().unwrap_or_default();
+        let instrument = movie
+            .split(|x| *x == UNDERSCORE_ASCII_CODE)
+            .next()
+            .filter(|i| i.len() > 1 && i.starts_with(b"m") && movie.len() > i.len())
+            .ok_or(invalid("movie name"))?;
+        let zmw = parse_number(fields.next()).ok_or(invalid("ZMW"))?;
+        let suffix = fields.next().filter(|s| !s.is_empty());
+
+        Ok(PacBioName {
+            movie,
+            instrument,
+            zmw,
+            suffix,
+        })
+    }
+}
+
+/// FASTQ ID from Ultima Genomics sequencers
+///
+/// These have the layout `{run}_{slot}-{...}`, like `004733_1-Z0001-1701223000`.
+/// Only the run ID is parsed, which identifies a single wafer.
+#[derive(Debug, PartialEq)]
+pub(crate) struct UltimaName<'id> {
+    /// Run ID
+    pub run: &'id [u8],
+
+    /// Everything after the run ID
+    pub read: &'id [u8],
+}
+
+impl<'id> TryFrom<&'id [u8]> for UltimaName<'id> {
+    type Error = RecordError;
+    fn try_from(value: &'id [u8]) -> Result<Self, Self::Error> {
+        let invalid = RecordError::InvalidUltimaField;
+        let id = value
+            .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+            .next()
+            .unwrap_or_default();
+
+        let (run, rest) = split_digits(id);
+        let read = rest
+            .strip_prefix(&[UNDERSCORE_ASCII_CODE])
+            .filter(|r| !run.is_empty() && !r.is_empty())
+            .ok_or(invalid("run ID"))?;
+
+        // the slot on the instrument is followed by the rest of the read information
+        let (slot, rest) = split_digits(read);
+        if slot.is_empty() || !rest.starts_with(&[ULTIMA_SEPARATOR_ASCII_CODE]) {
+            return Err(invalid("slot"));
+        }
+
+        Ok(UltimaName { run, read })
+    }
+}
+
+/// Key for the run ID in Oxford Nanopore read names
+const NANOPORE_RUN_ID_KEY: &[u8] = b"runid=";
+
+/// Separator between keys and values in Oxford Nanopore read names
+const NANOPORE_KV_SEPARATOR_ASCII_CODE: u8 = b'=';
+
+/// FASTQ ID from Oxford Nanopore sequencers
+///
+/// These have a read ID followed by `key=value` pairs, like
+/// `0a1b2c3d-... runid=5e6f... read=12 ch=104 start_time=2021-03-01T12:00:00Z flow_cell_id=FAP12345`.
+/// Keys that aren't recognized are ignored, and any recognized key may be missing.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct NanoporeName<'id> {
+    /// Read ID
+    pub read_id: &'id [u8],
+
+    /// Run ID
+    pub run_id: Option<&'id [u8]>,
+
+    /// Read number on its channel
+    pub read: Option<u64>,
+
+    /// Channel the read was sequenced on
+    pub channel: Option<u16>,
+
+    /// When the read started sequencing, as an RFC 3339 timestamp
+    pub start_time: Option<&'id [u8]>,
+
+    /// Flow cell ID
+    pub flow_cell: Option<&'id [u8]>,
+
+    /// Basecalling model
+    pub basecall_model: Option<&'id [u8]>,
+}
+
+impl<'id> TryFrom<&'id [u8]> for NanoporeName<'id> {
+    type Error = RecordError;
+    fn try_from(value: &'id [u8]) -> Result<Self, Self::Error> {
+        let invalid = RecordError::InvalidNanoporeField;
+        let mut groups = value
+            .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+            .filter(|g| !g.is_empty());
+
+        let mut name = NanoporeName {
+            read_id: groups.next().ok_or(invalid("read ID"))?,
+            ..Default::default()
+        };
+        for group in groups {
+            let mut kv = group.splitn(2, |x| *x == NANOPORE_KV_SEPARATOR_ASCII_CODE);
+            let (Some(key), Some(val)) = (kv.next(), kv.next()) else {
+                continue;
+            };
+
+            match key {
+                b"runid" => name.run_id = Some(val),
+                b"read" => name.read = Some(parse_number(Some(val)).ok_or(invalid("read number"))?),
+                b"ch" => name.channel = Some(parse_number(Some(val)).ok_or(invalid("channel"))?),
+                b"start_time" => name.start_time = Some(val),
+                b"flow_cell_id" => name.flow_cell = Some(val),
+                b"basecall_model_version_id" => name.basecall_model = Some(val),
+                _ => {}
+            }
+        }
+
+        Ok(name)
+    }
+}
+
+/// Prefix of the record length in the description of SRA read names
+const SRA_LENGTH_PREFIX: &[u8] = b"length=";
+
+/// Separator between the run accession and the spot number in SRA read names
+const SRA_SPOT_SEPARATOR_ASCII_CODE: u8 = b'.';
+
 /// FASTQ ID from FASTQ files processes by the Sequence Read Archive
-#[derive(Debug)]
+///
+/// These have the layout `accession.spot original length=N`, where the original read name and the length are optional.
+/// Text fields borrow from the record name, so parsing a name doesn't allocate.
+#[derive(Debug, PartialEq)]
 pub(crate) struct SraName<'id> {
-    /// Clobbered record ID
-    id: Cow<'id, [u8]>,
+    /// Run accession
+    pub accession: &'id [u8],
+
+    /// Spot number within the run
+    pub spot: Option<u64>,
 
-    /// Short description or other info
-    description: Cow<'id, [u8]>,
+    /// Original read name from the submitted file
+    pub original: Option<&'id [u8]>,
 
     /// Length of the record
-    length: Cow<'id, [u8]>,
+    pub length: Option<u64>,
+}
+
+impl<'id> TryFrom<&'id [u8]> for SraName<'id> {
+    type Error = RecordError;
+    fn try_from(value: &'id [u8]) -> Result<Self, Self::Error> {
+        let mut groups = value
+            .split(|x| *x == RNAME_SEPARATOR_ASCII_CODE)
+            .filter(|g| !g.is_empty());
+
+        // the spot can be followed by a read number (e.g. `SRR001666.1.2`)
+        let mut id = groups
+            .next()
+            .unwrap_or_default()
+            .split(|x| *x == SRA_SPOT_SEPARATOR_ASCII_CODE);
+        let accession = id
+            .next()
+            .filter(|a| !a.is_empty())
+            .ok_or(RecordError::InvalidSraField("accession"))?;
+        let spot = id
+            .next()
+            .map(|s| parse_number(Some(s)).ok_or(RecordError::InvalidSraField("spot number")))
+            .transpose()?;
+
+        let mut original = None;
+        let mut length = None;
+        for group in groups {
+            if let Some(l) = group.strip_prefix(SRA_LENGTH_PREFIX) {
+                length = Some(parse_number(Some(l)).ok_or(RecordError::InvalidSraField("length"))?);
+            } else if original.is_none() {
+                original = Some(group);
+            }
+        }
+
+        Ok(SraName {
+            accession,
+            spot,
+            original,
+            length,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -151,4 +585,247 @@ mod tests {
 
         check_read_name_fmt(rname, Err(RecordError::UncertainRecordNameFormat));
     }
+
+    #[test]
+    fn casavav1_8_name_is_parsed() {
+        let rname = "EAS139:136:FC706VJ:2:2104:15343:197393 1:Y:18:ATCACG".as_bytes();
+        let obs = CasavaV1_8Name::try_from(rname).unwrap();
+        let exp = CasavaV1_8Name {
+            instrument: b"EAS139",
+            run: 136,
+            flow_cell: b"FC706VJ",
+            lane: 2,
+            tile: 2104,
+            x: 15343,
+            y: 197393,
+            comment: Some(CasavaV1_8Comment {
+                read: 1,
+                filtered: true,
+                control: 18,
+                index: b"ATCACG",
+            }),
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn casavav1_8_name_with_dual_index_is_parsed() {
+        let rname = "A00123:8:HJKLMDSXX:4:1101:10004:1000 2:N:0:ACGTACGT+TTGGCCAA".as_bytes();
+        let obs = CasavaV1_8Name::try_from(rname).unwrap().comment.unwrap();
+
+        assert_eq!(obs.read, 2);
+        assert!(!obs.filtered);
+        assert_eq!(obs.control, 0);
+        assert_eq!(obs.index, b"ACGTACGT+TTGGCCAA");
+    }
+
+    #[test]
+    fn casavav1_8_name_without_comment_is_parsed() {
+        let rname = "EAS139:136:FC706VJ:2:2104:15343:197393".as_bytes();
+        let obs = CasavaV1_8Name::try_from(rname).unwrap();
+
+        assert_eq!(obs.flow_cell, b"FC706VJ");
+        assert_eq!(obs.comment, None);
+    }
+
+    #[test]
+    fn casavav1_8_name_ignores_trailing_text() {
+        let rname = "EAS139:136:FC706VJ:2:2104:15343:197393 1:N:0:1 extra info".as_bytes();
+        let obs = CasavaV1_8Name::try_from(rname).unwrap().comment.unwrap();
+
+        assert_eq!(obs.index, b"1");
+    }
+
+    #[test]
+    fn casavav1_4_name_is_not_casavav1_8() {
+        let rname = "HWUSI-EAS100R:6:73:941:1973#0/1".as_bytes();
+
+        assert_eq!(
+            CasavaV1_8Name::try_from(rname),
+            Err(RecordError::InvalidCasavaField("tile"))
+        );
+    }
+
+    #[test]
+    fn casavav1_8_name_with_bad_filter_flag_is_err() {
+        let rname = "EAS139:136:FC706VJ:2:2104:15343:197393 1:X:18:ATCACG".as_bytes();
+
+        assert_eq!(
+            CasavaV1_8Name::try_from(rname),
+            Err(RecordError::InvalidCasavaField("filter flag"))
+        );
+    }
+
+    #[test]
+    fn sra_name_with_original_is_parsed() {
+        let rname = "SRR001666.1 071112_SLXA-EAS1_s_7:5:1:817:345 length=36".as_bytes();
+        let obs = SraName::try_from(rname).unwrap();
+        let exp = SraName {
+            accession: b"SRR001666",
+            spot: Some(1),
+            original: Some(b"071112_SLXA-EAS1_s_7:5:1:817:345"),
+            length: Some(36),
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn sra_name_without_original_is_parsed() {
+        let rname = "SRR001666.25 length=36".as_bytes();
+        let obs = SraName::try_from(rname).unwrap();
+
+        assert_eq!(obs.spot, Some(25));
+        assert_eq!(obs.original, None);
+        assert_eq!(obs.length, Some(36));
+    }
+
+    #[test]
+    fn sra_name_with_read_number_is_parsed() {
+        let rname = "SRR001666.7.2".as_bytes();
+        let obs = SraName::try_from(rname).unwrap();
+
+        assert_eq!(obs.accession, b"SRR001666");
+        assert_eq!(obs.spot, Some(7));
+        assert_eq!(obs.length, None);
+    }
+
+    #[test]
+    fn sra_name_with_bad_length_is_err() {
+        let rname = "SRR001666.1 length=abc".as_bytes();
+
+        assert_eq!(
+            SraName::try_from(rname),
+            Err(RecordError::InvalidSraField("length"))
+        );
+    }
+
+    #[test]
+    fn empty_name_is_err() {
+        check_read_name_fmt("", Err(RecordError::UncertainRecordNameFormat));
+    }
+
+    #[test]
+    fn short_name_is_err() {
+        check_read_name_fmt("r1", Err(RecordError::UncertainRecordNameFormat));
+    }
+
+    #[test]
+    fn aviti_is_aviti() {
+        let rname = "AV223701:1:2324106785:1:10102:0227:0034 1:N:0:AACCTGAA+GTTCGACA";
+
+        check_read_name_fmt(rname, Ok(RecordName::ElementAviti));
+    }
+
+    #[test]
+    fn mgi_is_mgi() {
+        check_read_name_fmt("V350012345L1C001R0010000001/1", Ok(RecordName::MgiDnbseq));
+    }
+
+    #[test]
+    fn pacbio_is_pacbio() {
+        check_read_name_fmt("m64011_190830_220126/1/ccs", Ok(RecordName::PacBio));
+    }
+
+    #[test]
+    fn ultima_is_ultima() {
+        check_read_name_fmt("004733_1-Z0001-1701223000", Ok(RecordName::Ultima));
+    }
+
+    #[test]
+    fn mgi_name_is_parsed() {
+        let rname = "V350012345L1C001R0010000001/1".as_bytes();
+        let obs = MgiName::try_from(rname).unwrap();
+        let exp = MgiName {
+            flow_cell: b"V350012345",
+            lane: 1,
+            column: 1,
+            row: 1,
+            read: 1,
+            member: Some(1),
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn mgi_name_with_two_letter_flow_cell_is_parsed() {
+        let rname = "CL100012345L4C012R0340012345".as_bytes();
+        let obs = MgiName::try_from(rname).unwrap();
+
+        assert_eq!(obs.flow_cell, b"CL100012345");
+        assert_eq!(obs.lane, 4);
+        assert_eq!(obs.column, 12);
+        assert_eq!(obs.row, 34);
+        assert_eq!(obs.read, 12345);
+        assert_eq!(obs.member, None);
+    }
+
+    #[test]
+    fn pacbio_ccs_name_is_parsed() {
+        let rname = "m64011_190830_220126/1/ccs".as_bytes();
+        let obs = PacBioName::try_from(rname).unwrap();
+        let exp = PacBioName {
+            movie: b"m64011_190830_220126",
+            instrument: b"m64011",
+            zmw: 1,
+            suffix: Some(b"ccs"),
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn pacbio_subread_name_is_parsed() {
+        let rname = "m84011_220902_175841_s1/4325/0_1500".as_bytes();
+        let obs = PacBioName::try_from(rname).unwrap();
+
+        assert_eq!(obs.movie, b"m84011_220902_175841_s1");
+        assert_eq!(obs.zmw, 4325);
+        assert_eq!(obs.suffix, Some(b"0_1500".as_slice()));
+    }
+
+    #[test]
+    fn ultima_name_is_parsed() {
+        let rname = "004733_1-Z0001-1701223000".as_bytes();
+        let obs = UltimaName::try_from(rname).unwrap();
+
+        assert_eq!(obs.run, b"004733");
+        assert_eq!(obs.read, b"1-Z0001-1701223000");
+    }
+
+    #[test]
+    fn nanopore_is_nanopore() {
+        let rname = "0a1b2c3d-4e5f-6789-abcd-ef0123456789 runid=5e6f read=12 ch=104 start_time=2021-03-01T12:00:00Z";
+
+        check_read_name_fmt(rname, Ok(RecordName::Nanopore));
+    }
+
+    #[test]
+    fn nanopore_name_is_parsed() {
+        let rname = "0a1b2c3d runid=5e6f sampleid=s1 read=12 ch=104 start_time=2021-03-01T12:00:00Z flow_cell_id=FAP12345 basecall_model_version_id=dna_r9.4.1_450bps_hac".as_bytes();
+        let obs = NanoporeName::try_from(rname).unwrap();
+        let exp = NanoporeName {
+            read_id: b"0a1b2c3d",
+            run_id: Some(b"5e6f"),
+            read: Some(12),
+            channel: Some(104),
+            start_time: Some(b"2021-03-01T12:00:00Z"),
+            flow_cell: Some(b"FAP12345"),
+            basecall_model: Some(b"dna_r9.4.1_450bps_hac"),
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn nanopore_name_with_bad_channel_is_err() {
+        let rname = "0a1b2c3d runid=5e6f ch=abc".as_bytes();
+
+        assert_eq!(
+            NanoporeName::try_from(rname),
+            Err(RecordError::InvalidNanoporeField("channel"))
+        );
+    }
 }
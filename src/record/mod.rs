@@ -1,6 +1,9 @@
 //! Functions for processing records from a HTS file.
 
+pub mod barcodes;
 pub mod error;
 pub mod filter;
+pub mod grammar;
 pub mod header;
+pub mod nanopore;
 pub mod stats;
@@ -0,0 +1,311 @@
+//! Statistics from Oxford Nanopore read names.
+
+use super::{header::NanoporeName, stats::merge_counts};
+use crate::utils::formats::serialize_sorted_map;
+use chrono::{DateTime, SecondsFormat};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+
+/// Default width of the bins in a sequencing timeline, in minutes
+pub const DEFAULT_TIMELINE_BIN_MINUTES: u64 = 10;
+
+/// Number of seconds in a minute
+const SECONDS_PER_MINUTE: i64 = 60;
+
+/// Number of reads and bases sequenced in a period of time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Throughput {
+    /// Number of reads
+    pub reads: u64,
+
+    /// Number of bases
+    pub bases: u64,
+}
+
+/// A single bin of a sequencing timeline
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TimelineBin {
+    /// When the bin starts, as an RFC 3339 timestamp
+    pub start: String,
+
+    /// Minutes between the start of the first bin of the run and the start of this bin
+    pub elapsed_minutes: i64,
+
+    /// Reads and bases that started sequencing in this bin
+    #[serde(flatten)]
+    pub throughput: Throughput,
+}
+
+/// Runs, channels, and throughput over time from Oxford Nanopore read names
+#[derive(Debug, Clone)]
+pub struct NanoporeRuns {
+    /// Width of the timeline bins, in seconds
+    bin_seconds: i64,
+
+    /// Number of reads from each run
+    runs: HashMap<String, u64>,
+
+    /// Number of reads from each flow cell
+    flow_cells: HashMap<String, u64>,
+
+    /// Number of reads from each basecalling model
+    basecall_models: HashMap<String, u64>,
+
+    /// Number of reads from each channel
+    channels: HashMap<u16, u64>,
+
+    /// Throughput of each run, keyed by the index of each bin since the Unix epoch
+    timelines: HashMap<String, BTreeMap<i64, Throughput>>,
+}
+
+/// Raw counts of [`NanoporeRuns`], as saved in a stats file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NanoporeRunsState {
+    bin_seconds: i64,
+    runs: HashMap<String, u64>,
+    flow_cells: HashMap<String, u64>,
+    basecall_models: HashMap<String, u64>,
+    channels: HashMap<u16, u64>,
+    timelines: HashMap<String, BTreeMap<i64, Throughput>>,
+}
+
+impl From<&NanoporeRuns> for NanoporeRunsState {
+    fn from(runs: &NanoporeRuns) -> Self {
+        Self {
+            bin_seconds: runs.bin_seconds,
+            runs: runs.runs.clone(),
+            flow_cells: runs.flow_cells.clone(),
+            basecall_models: runs.basecall_models.clone(),
+            channels: runs.channels.clone(),
+            timelines: runs.timelines.clone(),
+        }
+    }
+}
+
+impl TryFrom<NanoporeRunsState> for NanoporeRuns {
+    type Error = &'static str;
+
+    fn try_from(state: NanoporeRunsState) -> Result<Self, Self::Error> {
+        if state.bin_seconds <= 0 {
+            return Err("Nanopore timeline bins must be at least 1 minute wide.");
+        }
+
+        Ok(Self {
+            bin_seconds: state.bin_seconds,
+            runs: state.runs,
+            flow_cells: state.flow_cells,
+            basecall_models: state.basecall_models,
+            channels: state.channels,
+            timelines: state.timelines,
+        })
+    }
+}
+
+/// Add one to the count for a key, only allocating a new key when it hasn't been seen yet
+fn tally(counts: &mut HashMap<String, u64>, key: Option<&[u8]>) {
+    let Some(key) = key else {
+        return;
+    };
+
+    let key = String::from_utf8_lossy(key);
+    match counts.get_mut(key.as_ref()) {
+        Some(n) => *n += 1,
+        None => {
+            counts.insert(key.into_owned(), 1);
+        }
+    }
+}
+
+impl NanoporeRuns {
+    /// Create a new, empty collection with timeline bins that are `bin_minutes` wide
+    pub fn new(bin_minutes: u64) -> Self {
+        Self {
+            bin_seconds: (bin_minutes.max(1) as i64).saturating_mul(SECONDS_PER_MINUTE),
+            runs: HashMap::new(),
+            flow_cells: HashMap::new(),
+            basecall_models: HashMap::new(),
+            channels: HashMap::new(),
+            timelines: HashMap::new(),
+        }
+    }
+
+    /// Include a parsed read name and the length of its sequence
+    ///
+    /// Reads without a valid start time are tallied, but left out of the timeline.
+    pub fn update(&mut self, name: &NanoporeName, seq_length: u64) {
+        tally(&mut self.runs, name.run_id);
+        tally(&mut self.flow_cells, name.flow_cell);
+        tally(&mut self.basecall_models, name.basecall_model);
+        if let Some(ch) = name.channel {
+            *self.channels.entry(ch).or_insert(0) += 1;
+        }
+
+        let start = name
+            .start_time
+            .and_then(|t| std::str::from_utf8(t).ok())
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok());
+        if let (Some(run), Some(start)) = (name.run_id, start) {
+            let bin = start.timestamp().div_euclid(self.bin_seconds);
+            let run = String::from_utf8_lossy(run).into_owned();
+            let throughput = self
+                .timelines
+                .entry(run)
+                .or_default()
+                .entry(bin)
+                .or_default();
+            throughput.reads += 1;
+            throughput.bases += seq_length;
+        }
+    }
+
+    /// Width of the timeline bins, in minutes
+    pub fn bin_minutes(&self) -> u64 {
+        (self.bin_seconds / SECONDS_PER_MINUTE) as u64
+    }
+
+    /// Combine the runs from another collection with this one
+    ///
+    /// Both collections should have timeline bins of the same width.
+    pub fn merge(&mut self, other: &Self) {
+        merge_counts(&mut self.runs, &other.runs);
+        merge_counts(&mut self.flow_cells, &other.flow_cells);
+        merge_counts(&mut self.basecall_models, &other.basecall_models);
+        merge_counts(&mut self.channels, &other.channels);
+
+        for (run, timeline) in &other.timelines {
+            let bins = self.timelines.entry(run.clone()).or_default();
+            for (bin, throughput) in timeline {
+                let total = bins.entry(*bin).or_default();
+                total.reads += throughput.reads;
+                total.bases += throughput.bases;
+            }
+        }
+    }
+
+    /// Run IDs and the number of reads from each
+    pub fn runs(&self) -> &HashMap<String, u64> {
+        &self.runs
+    }
+
+    /// Basecalling models and the number of reads from each
+    pub fn basecall_models(&self) -> &HashMap<String, u64> {
+        &self.basecall_models
+    }
+
+    /// Number of channels that produced any reads
+    pub fn active_channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Non-empty timeline bins of a run, in order
+    pub fn timeline(&self, run: &str) -> Vec<TimelineBin> {
+        let Some(bins) = self.timelines.get(run) else {
+            return vec![];
+        };
+        let Some(first) = bins.keys().next() else {
+            return vec![];
+        };
+
+        bins.iter()
+            .map(|(bin, throughput)| TimelineBin {
+                start: DateTime::from_timestamp(bin * self.bin_seconds, 0)
+                    .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+                    .unwrap_or_default(),
+                elapsed_minutes: (bin - first) * self.bin_seconds / SECONDS_PER_MINUTE,
+                throughput: *throughput,
+            })
+            .collect()
+    }
+}
+
+impl Serialize for NanoporeRuns {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        /// Map of counts with its keys in sorted order
+        struct Sorted<'a, K>(&'a HashMap<K, u64>);
+
+        impl<K: Ord + std::fmt::Display> Serialize for Sorted<'_, K> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serialize_sorted_map(self.0, serializer)
+            }
+        }
+
+        let timelines: BTreeMap<&String, Vec<TimelineBin>> = self
+            .timelines
+            .keys()
+            .map(|r| (r, self.timeline(r)))
+            .collect();
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("runs", &Sorted(&self.runs))?;
+        map.serialize_entry("flow_cells", &Sorted(&self.flow_cells))?;
+        map.serialize_entry("basecall_models", &Sorted(&self.basecall_models))?;
+        map.serialize_entry("active_channels", &self.active_channels())?;
+        map.serialize_entry("channels", &Sorted(&self.channels))?;
+        map.serialize_entry(
+            "timeline_bin_minutes",
+            &(self.bin_seconds / SECONDS_PER_MINUTE),
+        )?;
+        map.serialize_entry("timelines", &timelines)?;
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runs(names: &[(&str, u64)]) -> NanoporeRuns {
+        let mut runs = NanoporeRuns::new(DEFAULT_TIMELINE_BIN_MINUTES);
+        for (rname, length) in names {
+            let name = NanoporeName::try_from(rname.as_bytes()).unwrap();
+            runs.update(&name, *length);
+        }
+
+        runs
+    }
+
+    #[test]
+    fn reads_are_binned_by_start_time() {
+        let obs = runs(&[
+            ("r1 runid=a ch=1 start_time=2021-03-01T12:01:00Z", 100),
+            ("r2 runid=a ch=2 start_time=2021-03-01T12:09:59Z", 50),
+            ("r3 runid=a ch=1 start_time=2021-03-01T12:25:00+00:00", 10),
+            ("r4 runid=a ch=1", 10),
+        ]);
+        let exp = vec![
+            TimelineBin {
+                start: "2021-03-01T12:00:00Z".to_string(),
+                elapsed_minutes: 0,
+                throughput: Throughput {
+                    reads: 2,
+                    bases: 150,
+                },
+            },
+            TimelineBin {
+                start: "2021-03-01T12:20:00Z".to_string(),
+                elapsed_minutes: 20,
+                throughput: Throughput {
+                    reads: 1,
+                    bases: 10,
+                },
+            },
+        ];
+
+        assert_eq!(obs.timeline("a"), exp);
+        assert_eq!(obs.runs()["a"], 4);
+        assert_eq!(obs.active_channels(), 2);
+    }
+
+    #[test]
+    fn merged_timelines_are_summed() {
+        let mut obs = runs(&[("r1 runid=a start_time=2021-03-01T12:01:00Z", 100)]);
+        obs.merge(&runs(&[
+            ("r2 runid=a start_time=2021-03-01T12:02:00Z", 20),
+            ("r3 runid=b start_time=2021-03-01T12:02:00Z", 20),
+        ]));
+
+        assert_eq!(obs.timeline("a")[0].throughput.bases, 120);
+        assert_eq!(obs.timeline("b").len(), 1);
+        assert_eq!(obs.runs().len(), 2);
+    }
+}
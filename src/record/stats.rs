@@ -1,6 +1,362 @@
 //! Methods for calculating statistics from HTS records.
 
-use std::{collections::HashMap, io::Read};
+use super::{
+    barcodes::IndexTally,
+    grammar::{GrammarName, NameGrammar},
+    header::{
+        CasavaV1_8Comment, CasavaV1_8Name, MgiName, NanoporeName, PacBioName, RecordName, SraName,
+        UltimaName, ILLUMINA_SEPARATOR_ASCII_CODE,
+    },
+    nanopore::NanoporeRuns,
+};
+use crate::utils::{batch::SummaryRow, formats::serialize_sorted_map};
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use std::{cmp::Reverse, collections::HashMap, fmt::Display, hash::Hash, io::Read};
+
+/// Flow cell ID for lanes from read names that don't include their flow cell
+const UNKNOWN_FLOW_CELL: &[u8] = b"unknown";
+
+/// Statistics that can be combined with statistics calculated from other records
+pub trait MergeStats {
+    /// Add the statistics calculated from other records into these ones
+    fn merge(&mut self, other: &Self);
+}
+
+/// Add the tallies from one map of counts into another
+pub fn merge_counts<K: Eq + Hash + Clone>(counts: &mut HashMap<K, u64>, other: &HashMap<K, u64>) {
+    for (k, n) in other {
+        match counts.get_mut(k) {
+            Some(count) => *count += n,
+            None => {
+                counts.insert(k.clone(), *n);
+            }
+        }
+    }
+}
+
+/// Which pieces of information to track from record names
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NameOpts<'g> {
+    /// Track the sequencing instruments used
+    pub instruments: bool,
+
+    /// Track flow cell IDs
+    pub flow_cells: bool,
+
+    /// Track SRA run accessions and spot numbers
+    pub sra: bool,
+
+    /// Track Oxford Nanopore runs, channels, and throughput over time
+    pub nanopore: bool,
+
+    /// Track index sequences
+    pub indexes: bool,
+
+    /// Track the chastity filter flags and control bits
+    pub filter_flags: bool,
+
+    /// Width of the bins in Oxford Nanopore throughput timelines, in minutes
+    pub timeline_bin_minutes: u64,
+
+    /// User-defined name formats, tried in order before any of the built-in formats
+    pub grammars: &'g [NameGrammar],
+}
+
+impl NameOpts<'_> {
+    /// Also track every piece of information captured by the user-defined name formats
+    pub fn with_grammar_groups(mut self) -> Self {
+        for grammar in self.grammars {
+            self.instruments |= grammar.has_instrument();
+            self.flow_cells |= grammar.has_flow_cell();
+            self.indexes |= grammar.has_barcode();
+        }
+
+        self
+    }
+
+    /// Whether record names need to be parsed at all
+    pub fn any(&self) -> bool {
+        self.instruments
+            || self.flow_cells
+            || self.sra
+            || self.nanopore
+            || self.indexes
+            || self.filter_flags
+    }
+}
+
+/// Chastity filter flags and control bits from Casava >= v1.8 read names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FilterFlags {
+    /// Number of reads that passed the chastity filter
+    pub unfiltered: u64,
+
+    /// Number of reads that failed the chastity filter
+    pub filtered: u64,
+
+    /// Number of reads with any control bits on
+    pub control: u64,
+}
+
+impl FilterFlags {
+    /// Create a new, empty set of counts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include the flags of a read
+    pub fn update(&mut self, comment: &CasavaV1_8Comment) {
+        if comment.filtered {
+            self.filtered += 1;
+        } else {
+            self.unfiltered += 1;
+        }
+        if comment.control != 0 {
+            self.control += 1;
+        }
+    }
+
+    /// Combine the counts from other reads with these ones
+    pub fn merge(&mut self, other: &Self) {
+        self.unfiltered += other.unfiltered;
+        self.filtered += other.filtered;
+        self.control += other.control;
+    }
+}
+
+/// Range of spots observed from a single SRA run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SpotRange {
+    /// Number of records from the run
+    pub records: u64,
+
+    /// Lowest spot number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_spot: Option<u64>,
+
+    /// Highest spot number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_spot: Option<u64>,
+}
+
+impl SpotRange {
+    /// Include a record with an optional spot number
+    fn update(&mut self, spot: Option<u64>) {
+        self.records += 1;
+        if let Some(spot) = spot {
+            self.first_spot = Some(self.first_spot.map_or(spot, |s| s.min(spot)));
+            self.last_spot = Some(self.last_spot.map_or(spot, |s| s.max(spot)));
+        }
+    }
+
+    /// Combine the spots from another range of the same run with this one
+    fn merge(&mut self, other: &Self) {
+        self.records += other.records;
+        self.first_spot = match (self.first_spot, other.first_spot) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.last_spot = match (self.last_spot, other.last_spot) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// Runs and spots of records from the Sequence Read Archive
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SraRuns {
+    /// Spots observed from each run accession
+    #[serde(serialize_with = "serialize_sorted_map")]
+    pub runs: HashMap<String, SpotRange>,
+
+    /// Number of records whose `length=` doesn't match the length of their sequence
+    pub length_mismatches: u64,
+}
+
+impl SraRuns {
+    /// Create a new, empty collection of runs
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include a parsed SRA read name, and check its length against the sequence, if known
+    pub fn update(&mut self, name: &SraName, seq_length: Option<u64>) {
+        let accession = String::from_utf8_lossy(name.accession);
+        match self.runs.get_mut(accession.as_ref()) {
+            Some(range) => range.update(name.spot),
+            None => {
+                let mut range = SpotRange::default();
+                range.update(name.spot);
+                self.runs.insert(accession.into_owned(), range);
+            }
+        }
+
+        if let (Some(expected), Some(observed)) = (name.length, seq_length) {
+            if expected != observed {
+                self.length_mismatches += 1;
+            }
+        }
+    }
+
+    /// Combine the runs from another collection with this one
+    pub fn merge(&mut self, other: &Self) {
+        self.length_mismatches += other.length_mismatches;
+        for (accession, range) in &other.runs {
+            match self.runs.get_mut(accession) {
+                Some(r) => r.merge(range),
+                None => {
+                    self.runs.insert(accession.clone(), range.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Whether a count is zero, to skip serializing it
+pub(crate) fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// Summary statistics of a distribution of record lengths
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LengthSummary {
+    /// Mean record length
+    pub mean: f64,
+
+    /// Median record length
+    pub median: u64,
+
+    /// Most common record length (the shortest, if there is a tie)
+    pub mode: u64,
+
+    /// Shortest record length
+    pub min: u64,
+
+    /// Longest record length
+    pub max: u64,
+
+    /// Length of the record where the longest records cover at least 50% of all bases
+    pub n50: u64,
+
+    /// Length of the record where the longest records cover at least 90% of all bases
+    pub n90: u64,
+
+    /// Number of records, longest first, needed to cover at least 50% of all bases
+    pub l50: u64,
+}
+
+impl LengthSummary {
+    /// Summarize a histogram that maps record lengths to the number of records with that length
+    pub fn from_histogram(lengths: &HashMap<u64, u64>) -> Option<Self> {
+        // lengths and counts, from longest to shortest
+        let mut hist: Vec<(u64, u64)> = lengths
+            .iter()
+            .filter(|(_, n)| **n > 0)
+            .map(|(l, n)| (*l, *n))
+            .collect();
+        hist.sort_unstable_by_key(|(l, _)| Reverse(*l));
+
+        let n_records: u64 = hist.iter().map(|(_, n)| n).sum();
+        if n_records == 0 {
+            return None;
+        }
+        let n_bases: u64 = hist.iter().map(|(l, n)| l * n).sum();
+
+        let mode = hist
+            .iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+            .map(|(l, _)| *l)
+            .unwrap_or_default();
+
+        // the median is counted from the shortest record, the N-statistics from the longest
+        let median_rank = n_records.div_ceil(2);
+        let mut median = 0;
+        let mut seen = 0;
+        for (l, n) in hist.iter().rev() {
+            seen += n;
+            if seen >= median_rank {
+                median = *l;
+                break;
+            }
+        }
+
+        let (n50, l50) = n_statistic(&hist, n_bases, 0.5);
+        let (n90, _) = n_statistic(&hist, n_bases, 0.9);
+
+        Some(Self {
+            mean: n_bases as f64 / n_records as f64,
+            median,
+            mode,
+            min: hist[hist.len() - 1].0,
+            max: hist[0].0,
+            n50,
+            n90,
+            l50,
+        })
+    }
+}
+
+impl SummaryRow for LengthSummary {
+    fn summary_row(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("mean_length", self.mean.to_string()),
+            ("median_length", self.median.to_string()),
+            ("min_length", self.min.to_string()),
+            ("max_length", self.max.to_string()),
+            ("n50", self.n50.to_string()),
+        ]
+    }
+}
+
+/// Calculate the N-statistic (and L-statistic) for a fraction of all bases
+///
+/// `hist` must be sorted from the longest to the shortest length.
+fn n_statistic(hist: &[(u64, u64)], n_bases: u64, frac: f64) -> (u64, u64) {
+    let target = frac * n_bases as f64;
+    let mut covered = 0;
+    let mut n_records = 0;
+
+    for (l, n) in hist {
+        // only as many records of this length as are needed to reach the target
+        let remaining = target - covered as f64;
+        let needed = match *l {
+            _ if remaining <= 0.0 => 1,
+            0 => u64::MAX,
+            l => ((remaining / l as f64).ceil() as u64).max(1),
+        };
+        if needed <= *n {
+            return (*l, n_records + needed);
+        }
+
+        covered += l * n;
+        n_records += n;
+    }
+
+    (0, n_records)
+}
+
+/// Serialize a histogram of record lengths alongside its summary statistics
+pub(crate) fn serialize_length_distribution<S: Serializer>(
+    lengths: &HashMap<u64, u64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    /// Length histogram with its keys in sorted order
+    struct Histogram<'a>(&'a HashMap<u64, u64>);
+
+    impl Serialize for Histogram<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_sorted_map(self.0, serializer)
+        }
+    }
+
+    let mut map = serializer.serialize_map(Some(2))?;
+    if let Some(summary) = LengthSummary::from_histogram(lengths) {
+        map.serialize_entry("summary", &summary)?;
+    }
+    map.serialize_entry("histogram", &Histogram(lengths))?;
+    map.end()
+}
 
 pub trait RecordStats<'a> {
     type Record;
@@ -21,6 +377,14 @@ pub trait RecordStats<'a> {
         self.n_valid() + self.n_invalid()
     }
 
+    /// Get the HashMap of lengths
+    fn lengths(&self) -> &HashMap<u64, u64>;
+
+    /// Summarize the distribution of record lengths
+    fn length_summary(&self) -> Option<LengthSummary> {
+        LengthSummary::from_histogram(self.lengths())
+    }
+
     /// Get the mutable HashMap of lengths
     fn mut_lengths(&mut self) -> &mut HashMap<u64, u64>;
 
@@ -39,26 +403,229 @@ pub trait RecordStats<'a> {
     /// Get the mutable HashSet of instrument IDs
     fn mut_instruments(&mut self) -> &mut HashMap<String, u64>;
 
+    /// Get the mutable HashMap of flow cell lanes
+    fn mut_lanes(&mut self) -> &mut HashMap<String, u64>;
+
+    /// Get the mutable collection of SRA runs, creating it if it hasn't been yet
+    fn mut_sra_runs(&mut self) -> &mut SraRuns;
+
+    /// Get the mutable collection of Oxford Nanopore runs, creating it with `bin_minutes` wide timeline bins if it hasn't been yet
+    ///
+    /// Statistics that don't track Oxford Nanopore runs return `None`.
+    fn mut_nanopore_runs(&mut self, _bin_minutes: u64) -> Option<&mut NanoporeRuns> {
+        None
+    }
+
+    /// Get the mutable tally of index sequences, creating it if it hasn't been yet
+    ///
+    /// Statistics that don't track index sequences return `None`.
+    fn mut_index_tally(&mut self) -> Option<&mut IndexTally> {
+        None
+    }
+
+    /// Get the mutable counts of chastity filter flags, creating them if they haven't been yet
+    ///
+    /// Statistics that don't track filter flags return `None`.
+    fn mut_filter_flags(&mut self) -> Option<&mut FilterFlags> {
+        None
+    }
+
+    /// Get the mutable number of records whose name format couldn't be determined
+    fn mut_unrecognized_names(&mut self) -> &mut u64;
+
     /// Process a single record from an HTS file to record its statistics
-    fn process_record(&mut self, rec: &Result<Self::Record, Self::Error>, opts: &Self::InfoOpts) {
+    ///
+    /// `name_opts` is built once per file from `opts`, so it isn't rebuilt for every record.
+    fn process_record(
+        &mut self,
+        rec: &Result<Self::Record, Self::Error>,
+        opts: &Self::InfoOpts,
+        name_opts: &NameOpts,
+    ) {
         if let Ok(seq) = rec {
-            self.process_valid_record(seq, opts);
+            self.process_valid_record(seq, opts, name_opts);
         } else {
             self.process_invalid_record();
         }
     }
 
     /// Process the statistics for a valid record
-    fn process_valid_record(&mut self, seq: &Self::Record, opts: &Self::InfoOpts);
+    fn process_valid_record(
+        &mut self,
+        seq: &Self::Record,
+        opts: &Self::InfoOpts,
+        name_opts: &NameOpts,
+    );
 
     /// Process the statistics for an invalid record
     fn process_invalid_record(&mut self);
 
-    /// Process a Sequence Read Archive record
+    /// Process the information in a record name, in whichever format it is in
+    ///
+    /// Names that don't match any user-defined or built-in format are counted, but otherwise ignored.
+    fn process_name(&mut self, rname: &[u8], seq_length: Option<u64>, opts: &NameOpts) {
+        if let Some(name) = opts.grammars.iter().find_map(|g| g.parse(rname)) {
+            self.process_grammar_name(&name, opts);
+            return;
+        }
+
+        let parsed = match RecordName::try_from(rname) {
+            Ok(RecordName::CasavaV1_8 | RecordName::ElementAviti) => {
+                self.process_illumina_name(rname, opts);
+                Ok(())
+            }
+            Ok(RecordName::SequenceReadArchive) => {
+                self.process_sra_name(rname, seq_length, opts);
+                Ok(())
+            }
+            Ok(RecordName::MgiDnbseq) => {
+                MgiName::try_from(rname).map(|name| self.process_mgi_name(&name, opts))
+            }
+            Ok(RecordName::PacBio) => {
+                PacBioName::try_from(rname).map(|name| self.process_pacbio_name(&name, opts))
+            }
+            Ok(RecordName::Nanopore) => NanoporeName::try_from(rname)
+                .map(|name| self.process_nanopore_name(&name, seq_length, opts)),
+            Ok(RecordName::Ultima) => {
+                UltimaName::try_from(rname).map(|name| self.process_ultima_name(&name, opts))
+            }
+            Err(e) => Err(e),
+        };
+
+        if parsed.is_err() {
+            *self.mut_unrecognized_names() += 1;
+        }
+    }
+
+    /// Process the information in a parsed MGI/BGI DNBSEQ read name
+    ///
+    /// These names don't contain the instrument name.
+    fn process_mgi_name(&mut self, name: &MgiName, opts: &NameOpts) {
+        if opts.flow_cells {
+            self.process_illumina_flowcell(Some(name.flow_cell));
+            self.process_flowcell_lane(name.flow_cell, name.lane);
+        }
+    }
+
+    /// Process the information in a parsed Oxford Nanopore read name
+    fn process_nanopore_name(
+        &mut self,
+        name: &NanoporeName,
+        seq_length: Option<u64>,
+        opts: &NameOpts,
+    ) {
+        if opts.flow_cells {
+            self.process_illumina_flowcell(name.flow_cell);
+        }
+        if opts.nanopore {
+            if let Some(runs) = self.mut_nanopore_runs(opts.timeline_bin_minutes) {
+                runs.update(name, seq_length.unwrap_or_default());
+            }
+        }
+    }
+
+    /// Process the information in a parsed PacBio read name
+    ///
+    /// Each movie comes from a single SMRT Cell, so movies are tracked as flow cells.
+    fn process_pacbio_name(&mut self, name: &PacBioName, opts: &NameOpts) {
+        if opts.instruments {
+            self.process_illumina_instrument(Some(name.instrument));
+        }
+        if opts.flow_cells {
+            self.process_illumina_flowcell(Some(name.movie));
+        }
+    }
+
+    /// Process the information in a parsed Ultima Genomics read name
+    ///
+    /// Each run uses a single wafer, so runs are tracked as flow cells.
+    fn process_ultima_name(&mut self, name: &UltimaName, opts: &NameOpts) {
+        if opts.flow_cells {
+            self.process_illumina_flowcell(Some(name.run));
+        }
+    }
+
+    /// Process the information in a read name parsed by a user-defined grammar
+    ///
+    /// Lanes captured without a flow cell are counted under an `unknown` flow cell.
+    fn process_grammar_name(&mut self, name: &GrammarName, opts: &NameOpts) {
+        if opts.instruments {
+            self.process_illumina_instrument(name.instrument);
+        }
+        if opts.flow_cells {
+            self.process_illumina_flowcell(name.flow_cell);
+            if let Some(lane) = name.lane {
+                let fcid = name.flow_cell.unwrap_or(UNKNOWN_FLOW_CELL);
+                self.process_flowcell_lane(fcid, String::from_utf8_lossy(lane));
+            }
+        }
+        if opts.indexes {
+            if let (Some(index), Some(tally)) = (name.barcode, self.mut_index_tally()) {
+                tally.update(index);
+            }
+        }
+    }
+
+    /// Process a Sequence Read Archive formatted read name
+    ///
+    /// The `length=` in the name is checked against `seq_length`, if it's known.
+    /// An original Illumina read name embedded in the description is processed like any other Illumina read name.
+    fn process_sra_name(&mut self, rname: &[u8], seq_length: Option<u64>, opts: &NameOpts) {
+        let Ok(name) = SraName::try_from(rname) else {
+            return;
+        };
+
+        if opts.sra {
+            self.mut_sra_runs().update(&name, seq_length);
+        }
+        if let Some(original) = name.original {
+            if original.contains(&ILLUMINA_SEPARATOR_ASCII_CODE) {
+                self.process_illumina_name(original, opts);
+            }
+        }
+    }
+
+    /// Process an Illumina formatted read name
     ///
-    // Sequence Read Archive ID will be ignored since there is no
-    // way to figure out what the original flow cell IDs were.
-    fn process_sra_split_record(&mut self) {}
+    /// Names that aren't in the full Casava >= v1.8 format, like those from older versions of Casava, only have their instrument tracked.
+    fn process_illumina_name(&mut self, rname: &[u8], opts: &NameOpts) {
+        match CasavaV1_8Name::try_from(rname) {
+            Ok(name) => self.process_casava_v1_8_name(&name, opts),
+            Err(_) => {
+                if opts.instruments {
+                    let inst = rname.split(|x| *x == ILLUMINA_SEPARATOR_ASCII_CODE).next();
+                    self.process_illumina_instrument(inst);
+                }
+            }
+        }
+    }
+
+    /// Process the information in a parsed Casava >= v1.8 read name
+    fn process_casava_v1_8_name(&mut self, name: &CasavaV1_8Name, opts: &NameOpts) {
+        if opts.instruments {
+            self.process_illumina_instrument(Some(name.instrument));
+        }
+        if opts.flow_cells {
+            self.process_illumina_flowcell(Some(name.flow_cell));
+            self.process_flowcell_lane(name.flow_cell, name.lane);
+        }
+        if opts.indexes {
+            if let (Some(comment), Some(tally)) = (&name.comment, self.mut_index_tally()) {
+                tally.update(comment.index);
+            }
+        }
+        if opts.filter_flags {
+            if let (Some(comment), Some(flags)) = (&name.comment, self.mut_filter_flags()) {
+                flags.update(comment);
+            }
+        }
+    }
+
+    /// Process the lane of a flow cell from a read name
+    fn process_flowcell_lane(&mut self, fcid: &[u8], lane: impl Display) {
+        let key = format!("{}:{}", String::from_utf8_lossy(fcid), lane);
+        *self.mut_lanes().entry(key).or_insert(0) += 1;
+    }
 
     /// Process the flow cell ID from an Illumina read name
     fn process_illumina_flowcell(&mut self, fcid: Option<&[u8]>) {
@@ -105,3 +672,153 @@ pub trait RecordStats<'a> {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grammar_groups_are_tracked() {
+        let grammars: Vec<NameGrammar> = vec![r"^(?P<instrument>\w+)_".parse().unwrap()];
+        let opts = NameOpts {
+            grammars: &grammars,
+            ..Default::default()
+        };
+        assert!(!opts.any());
+
+        let obs = opts.with_grammar_groups();
+        assert!(obs.any());
+        assert!(obs.instruments);
+        assert!(!obs.flow_cells);
+        assert!(!obs.indexes);
+    }
+
+    #[test]
+    fn merged_counts_are_summed() {
+        let mut obs = HashMap::from([(36, 1), (100, 2)]);
+        merge_counts(&mut obs, &HashMap::from([(100, 3), (150, 4)]));
+
+        assert_eq!(obs, HashMap::from([(36, 1), (100, 5), (150, 4)]));
+    }
+
+    #[test]
+    fn filter_flags_are_counted() {
+        let mut obs = FilterFlags::new();
+        for rname in [
+            "EAS139:136:FC706VJ:2:2104:15343:197393 1:Y:18:ATCACG",
+            "EAS139:136:FC706VJ:2:2104:15343:197394 1:N:0:ATCACG",
+            "EAS139:136:FC706VJ:2:2104:15343:197395 1:N:0:ATCACG",
+        ] {
+            let name = CasavaV1_8Name::try_from(rname.as_bytes()).unwrap();
+            obs.update(name.comment.as_ref().unwrap());
+        }
+        let exp = FilterFlags {
+            unfiltered: 2,
+            filtered: 1,
+            control: 1,
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn sra_spots_and_lengths_are_tracked() {
+        let mut obs = SraRuns::new();
+        for (rname, seq_length) in [
+            ("SRR001666.2 length=36", 36),
+            ("SRR001666.1 length=36", 30),
+            ("SRR001667.5", 36),
+        ] {
+            let name = SraName::try_from(rname.as_bytes()).unwrap();
+            obs.update(&name, Some(seq_length));
+        }
+
+        assert_eq!(obs.length_mismatches, 1);
+        assert_eq!(
+            obs.runs["SRR001666"],
+            SpotRange {
+                records: 2,
+                first_spot: Some(1),
+                last_spot: Some(2),
+            }
+        );
+        assert_eq!(obs.runs["SRR001667"].records, 1);
+    }
+
+    #[test]
+    fn merged_sra_runs_combine_spots() {
+        let mut obs = SraRuns::new();
+        obs.update(&SraName::try_from("SRR1.10".as_bytes()).unwrap(), None);
+        let mut other = SraRuns::new();
+        other.update(&SraName::try_from("SRR1.3".as_bytes()).unwrap(), None);
+        obs.merge(&other);
+
+        assert_eq!(
+            obs.runs["SRR1"],
+            SpotRange {
+                records: 2,
+                first_spot: Some(3),
+                last_spot: Some(10),
+            }
+        );
+    }
+
+    #[test]
+    fn empty_histogram_has_no_summary() {
+        let obs = LengthSummary::from_histogram(&HashMap::new());
+
+        assert_eq!(obs, None);
+    }
+
+    #[test]
+    fn uniform_lengths() {
+        let obs = LengthSummary::from_histogram(&HashMap::from([(150, 4)])).unwrap();
+        let exp = LengthSummary {
+            mean: 150.0,
+            median: 150,
+            mode: 150,
+            min: 150,
+            max: 150,
+            n50: 150,
+            n90: 150,
+            l50: 2,
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn mixed_lengths() {
+        // 2, 2, 3, 4, 9: 20 bases in total
+        let hist = HashMap::from([(2, 2), (3, 1), (4, 1), (9, 1)]);
+        let obs = LengthSummary::from_histogram(&hist).unwrap();
+        let exp = LengthSummary {
+            mean: 4.0,
+            median: 3,
+            mode: 2,
+            min: 2,
+            max: 9,
+            n50: 4,
+            n90: 2,
+            l50: 2,
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn many_records_of_one_length() {
+        let hist = HashMap::from([(150, 100_000_000), (0, 5)]);
+        let obs = LengthSummary::from_histogram(&hist).unwrap();
+
+        assert_eq!((obs.n50, obs.n90, obs.l50), (150, 150, 50_000_000));
+    }
+
+    #[test]
+    fn mode_ties_pick_shortest() {
+        let hist = HashMap::from([(10, 3), (5, 3), (7, 1)]);
+        let obs = LengthSummary::from_histogram(&hist).unwrap();
+
+        assert_eq!(obs.mode, 5);
+    }
+}
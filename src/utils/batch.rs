@@ -0,0 +1,336 @@
+//! # Batches of HTS files
+//!
+//! Calculate statistics for many HTS files in parallel and report them together.
+//!
+//! Tabular formats (human-readable, CSV, TSV) have one row per file, followed by a `total` row that combines all the files that could be processed.
+//! Structured formats (JSON, YAML, TOML) list the full statistics for each file under `files`, and the combined statistics under `total`.
+
+use super::{
+    formats::{OutputFormat, OutputFormatError},
+    is_stdin,
+};
+use crate::{error::BjtError, record::stats::MergeStats};
+use anyhow::Context;
+use log::warn;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// Label for the row combining all files in a batch
+const TOTAL_LABEL: &str = "total";
+
+/// Column with the file path in batch tables
+const FILE_COLUMN: &str = "file";
+
+/// Column with the reason a file could not be processed in batch tables
+const ERROR_COLUMN: &str = "error";
+
+/// Label, summary statistics, and error message (if any) for a row in a batch table
+type LabelledRow<'a> = (String, Vec<(&'static str, String)>, Option<&'a str>);
+
+/// Statistics that can be summarized in a single row of a table
+pub(crate) trait SummaryRow {
+    /// Column names and values for the most important statistics
+    fn summary_row(&self) -> Vec<(&'static str, String)>;
+}
+
+/// Join the keys of a map of counts, in sorted order, into a single table value
+pub(crate) fn join_keys<V>(map: &HashMap<String, V>) -> String {
+    let mut keys: Vec<&str> = map.keys().map(|k| k.as_str()).collect();
+    keys.sort_unstable();
+
+    keys.join(";")
+}
+
+/// Expand any glob patterns in a list of paths
+///
+/// Paths without any glob characters are passed through as-is, so missing files are reported when they're processed.
+/// Standard input can only be read once, so it can't be given more than once.
+pub(crate) fn expand_paths(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    if paths.iter().filter(|p| is_stdin(p)).count() > 1 {
+        return Err(BjtError::StdinRepeated.into());
+    }
+
+    let mut expanded = vec![];
+
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if !pattern.contains(['*', '?', '[']) {
+            expanded.push(path.clone());
+            continue;
+        }
+
+        let mut matches = glob::glob(&pattern)
+            .with_context(|| format!("Invalid glob pattern {}", pattern))?
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        if matches.is_empty() {
+            return Err(BjtError::NoGlobMatches {
+                pattern: pattern.into_owned(),
+            }
+            .into());
+        }
+
+        matches.sort();
+        expanded.append(&mut matches);
+    }
+
+    Ok(expanded)
+}
+
+/// Statistics, or the reason they couldn't be calculated, for a single file in a batch
+#[derive(Debug, Serialize)]
+pub(crate) struct FileReport<T> {
+    /// Path to the file
+    path: PathBuf,
+
+    /// Statistics calculated from the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<T>,
+
+    /// Why the file couldn't be processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Statistics for a batch of files
+#[derive(Debug, Serialize)]
+pub(crate) struct BatchReport<T> {
+    /// Statistics for each file, in the order they were given
+    files: Vec<FileReport<T>>,
+
+    /// Statistics combined over all files that could be processed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<T>,
+}
+
+impl<T> BatchReport<T> {
+    /// Number of files that couldn't be processed
+    pub fn n_failed(&self) -> usize {
+        self.files.iter().filter(|f| f.error.is_some()).count()
+    }
+
+    /// Number of files in the batch
+    pub fn n_files(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Statistics combined over all files that could be processed
+    pub fn total(&self) -> Option<&T> {
+        self.total.as_ref()
+    }
+
+    /// Statistics of each file that could be processed, in the order they were given
+    pub fn file_stats(&self) -> impl Iterator<Item = (&Path, &T)> {
+        self.files
+            .iter()
+            .filter_map(|f| f.stats.as_ref().map(|s| (f.path.as_path(), s)))
+    }
+
+    /// Fail if any of the files couldn't be processed
+    pub fn check(&self) -> Result<(), BjtError> {
+        match self.n_failed() {
+            0 => Ok(()),
+            failed => Err(BjtError::BatchFailed {
+                failed,
+                total: self.n_files(),
+            }),
+        }
+    }
+}
+
+impl<T> BatchReport<T>
+where
+    T: MergeStats + Default + Send,
+{
+    /// Calculate statistics for each file on a pool of `jobs` worker threads
+    ///
+    /// A file that can't be processed is recorded in the report instead of stopping the rest of the batch.
+    /// Each file's statistics are added into the total by reference, so they're never copied.
+    /// If `jobs` isn't given, one worker is used for each CPU.
+    pub fn process<F>(paths: &[PathBuf], jobs: Option<usize>, calc: F) -> anyhow::Result<Self>
+    where
+        F: Fn(&Path) -> anyhow::Result<T> + Sync,
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs.unwrap_or_default())
+            .build()?;
+
+        let files: Vec<FileReport<T>> = pool.install(|| {
+            paths
+                .par_iter()
+                .map(|path| match calc(path) {
+                    Ok(stats) => FileReport {
+                        path: path.clone(),
+                        stats: Some(stats),
+                        error: None,
+                    },
+                    Err(e) => {
+                        warn!("Could not process {}: {:#}", path.display(), e);
+                        FileReport {
+                            path: path.clone(),
+                            stats: None,
+                            error: Some(format!("{:#}", e)),
+                        }
+                    }
+                })
+                .collect()
+        });
+
+        let mut total: Option<T> = None;
+        for stats in files.iter().filter_map(|f| f.stats.as_ref()) {
+            total.get_or_insert_with(T::default).merge(stats);
+        }
+
+        Ok(Self { files, total })
+    }
+}
+
+impl<T> BatchReport<T>
+where
+    T: Serialize + SummaryRow,
+{
+    /// Render the statistics for the batch in an output format
+    pub fn render(&self, format: &OutputFormat) -> Result<String, OutputFormatError> {
+        if !format.is_tabular() {
+            return format.render(self);
+        }
+
+        // labelled summaries for every row, including the total
+        let mut summaries: Vec<LabelledRow> = self
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    f.path.display().to_string(),
+                    f.stats
+                        .as_ref()
+                        .map(|s| s.summary_row())
+                        .unwrap_or_default(),
+                    f.error.as_deref(),
+                )
+            })
+            .collect();
+        if let Some(total) = &self.total {
+            summaries.push((TOTAL_LABEL.to_string(), total.summary_row(), None));
+        }
+
+        // columns in the order they first appear, since files may not share all the same statistics
+        let mut header: Vec<&str> = vec![FILE_COLUMN];
+        for (_, summary, _) in &summaries {
+            for (column, _) in summary {
+                if !header.contains(column) {
+                    header.push(column);
+                }
+            }
+        }
+        if self.n_failed() > 0 {
+            header.push(ERROR_COLUMN);
+        }
+
+        let rows: Vec<Vec<String>> = summaries
+            .into_iter()
+            .map(|(label, summary, error)| {
+                header
+                    .iter()
+                    .map(|column| match *column {
+                        FILE_COLUMN => label.clone(),
+                        ERROR_COLUMN => error.unwrap_or_default().to_string(),
+                        _ => summary
+                            .iter()
+                            .find(|(c, _)| c == column)
+                            .map(|(_, v)| v.clone())
+                            .unwrap_or_default(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        format.render_table(&header, &rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default, Serialize)]
+    struct Count(u64);
+
+    impl MergeStats for Count {
+        fn merge(&mut self, other: &Self) {
+            self.0 += other.0;
+        }
+    }
+
+    impl SummaryRow for Count {
+        fn summary_row(&self) -> Vec<(&'static str, String)> {
+            vec![("count", self.0.to_string())]
+        }
+    }
+
+    fn batch() -> BatchReport<Count> {
+        let paths: Vec<PathBuf> = ["a.txt", "bad.txt", "b.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        BatchReport::process(&paths, Some(2), |p| match p.to_str() {
+            Some("a.txt") => Ok(Count(1)),
+            Some("b.txt") => Ok(Count(2)),
+            _ => anyhow::bail!("cannot read"),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn failures_do_not_stop_batch() {
+        let obs = batch();
+
+        assert_eq!(obs.n_files(), 3);
+        assert_eq!(obs.n_failed(), 1);
+        assert_eq!(obs.total.unwrap().0, 3);
+    }
+
+    #[test]
+    fn table_has_row_per_file_and_total() {
+        let obs = batch().render(&OutputFormat::Csv).unwrap();
+        let exp = "file,count,error\na.txt,1,\nbad.txt,,cannot read\nb.txt,2,\ntotal,3,\n";
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn plain_paths_are_not_expanded() {
+        let paths = vec![PathBuf::from("does/not/exist.fastq")];
+
+        assert_eq!(expand_paths(&paths).unwrap(), paths);
+    }
+
+    #[test]
+    fn stdin_can_only_be_given_once() {
+        let paths = vec![PathBuf::from("-"), PathBuf::from("/dev/stdin")];
+
+        assert!(expand_paths(&paths).is_err());
+    }
+
+    #[test]
+    fn globs_are_expanded_in_order() {
+        let paths = vec![PathBuf::from("examples/SRR000000?.fastq.gz")];
+        let exp = vec![
+            PathBuf::from("examples/SRR0000001.fastq.gz"),
+            PathBuf::from("examples/SRR0000002.fastq.gz"),
+        ];
+
+        assert_eq!(expand_paths(&paths).unwrap(), exp);
+    }
+
+    #[test]
+    fn glob_without_matches_is_an_error() {
+        let paths = vec![PathBuf::from("examples/*.nothing")];
+
+        assert!(expand_paths(&paths).is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! # Decompression
+//!
+//! Read compressed HTS files on more than one thread.
+//!
+//! BGZF files are made of independently compressed blocks, so their blocks are decompressed in parallel.
+//! Ordinary gzip files can't be split up like this, but are decompressed on a separate thread from the one parsing them.
+//! Zstandard files are decompressed here as well, on a separate thread when more than one is available, since the parser can't read them.
+//! Other compressions are left for the parser to decompress.
+
+use super::sniff::Compression;
+use bam::bgzip::ConsecutiveReader;
+use clap::Args;
+use flate2::read::MultiGzDecoder;
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    sync::mpsc::{sync_channel, Receiver},
+    thread,
+};
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+/// Default number of threads used to read each file
+pub const DEFAULT_THREADS: u16 = 4;
+
+/// CLI options for how many threads to read files with
+#[derive(Debug, Args)]
+pub(crate) struct ThreadOpts {
+    /// Number of threads to read each file with, for decompression.
+    /// BGZF and BAM files are decompressed in parallel, and gzip and Zstandard files on a separate thread from parsing.
+    #[clap(long = "threads", value_name = "N", default_value_t = DEFAULT_THREADS)]
+    n_threads: u16,
+}
+
+impl ThreadOpts {
+    /// Number of threads to read each file with
+    pub fn n_threads(&self) -> u16 {
+        self.n_threads
+    }
+}
+
+/// Number of decompressed bytes passed between threads at a time
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Number of decompressed chunks that can be waiting to be parsed
+const CHUNK_QUEUE_LENGTH: usize = 16;
+
+/// Decompress a stream on up to `threads` threads, if it's compressed with gzip, BGZF, or Zstandard
+///
+/// With a single thread, Zstandard streams are decompressed on the current thread, and other streams are returned as-is,
+/// to be decompressed by the parser.
+pub fn decompress(reader: Box<dyn Read + Send>, threads: u16) -> io::Result<Box<dyn Read + Send>> {
+    let mut reader = BufReader::with_capacity(CHUNK_SIZE, reader);
+    let compression = Compression::from_magic(reader.fill_buf()?);
+
+    let decompressed: Box<dyn Read + Send> = match (compression, threads) {
+        (Compression::Zstd, 0 | 1) => Box::new(ZstdDecoder::with_buffer(reader)?),
+        (Compression::Zstd, _) => {
+            Box::new(ThreadedReader::spawn(ZstdDecoder::with_buffer(reader)?))
+        }
+        (_, 0 | 1) => Box::new(reader),
+        (Compression::Bgzf, n) => Box::new(ConsecutiveReader::from_stream(reader, n - 1)),
+        (Compression::Gzip, _) => Box::new(ThreadedReader::spawn(MultiGzDecoder::new(reader))),
+        _ => Box::new(reader),
+    };
+
+    Ok(decompressed)
+}
+
+/// Reads from a stream on a separate thread, so that decompressing and parsing can happen at the same time
+struct ThreadedReader {
+    /// Chunks read by the other thread
+    chunks: Receiver<io::Result<Vec<u8>>>,
+
+    /// Chunk currently being read
+    chunk: Vec<u8>,
+
+    /// Position in the current chunk
+    pos: usize,
+}
+
+impl ThreadedReader {
+    /// Start reading a stream on a new thread
+    ///
+    /// The thread stops at the end of the stream, after an error, or when this reader is dropped.
+    fn spawn<R: Read + Send + 'static>(mut inner: R) -> Self {
+        let (tx, rx) = sync_channel(CHUNK_QUEUE_LENGTH);
+
+        thread::spawn(move || loop {
+            let mut chunk = vec![0; CHUNK_SIZE];
+            match inner.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    if tx.send(Ok(chunk)).is_err() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    break;
+                }
+            }
+        });
+
+        Self {
+            chunks: rx,
+            chunk: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ThreadedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                // the other thread has finished the stream
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sink::OutputSink;
+    use std::{
+        fs::{read, File},
+        io::Write,
+        path::Path,
+    };
+    use tempfile::tempdir;
+
+    fn read_all(path: &Path, threads: u16) -> Vec<u8> {
+        let file: Box<dyn Read + Send> = Box::new(File::open(path).unwrap());
+        let mut content = vec![];
+        decompress(file, threads)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+
+        content
+    }
+
+    #[test]
+    fn gzip_is_decompressed_on_another_thread() {
+        let mut exp = vec![];
+        MultiGzDecoder::new(File::open("examples/SRR0000001.fastq.gz").unwrap())
+            .read_to_end(&mut exp)
+            .unwrap();
+        let obs = read_all(Path::new("examples/SRR0000001.fastq.gz"), 2);
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn bgzf_is_decompressed_in_parallel() {
+        let exp = read(Path::new("examples/SRR0000001.fastq")).unwrap();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("reads.fastq.bgz");
+        let mut sink = OutputSink::create(Some(&path), None, None).unwrap();
+        sink.write_all(&exp).unwrap();
+        sink.finish().unwrap();
+
+        let obs = read_all(&path, 4);
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn zstd_output_can_be_read_back() {
+        let exp = read(Path::new("examples/SRR0000001.fastq")).unwrap();
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("reads.fastq.zst");
+        let mut sink = OutputSink::create(Some(&path), None, None).unwrap();
+        sink.write_all(&exp).unwrap();
+        sink.finish().unwrap();
+
+        let single = read_all(&path, 1);
+        let threaded = read_all(&path, 4);
+
+        assert_eq!(single, exp);
+        assert_eq!(threaded, exp);
+    }
+
+    #[test]
+    fn single_thread_is_left_compressed() {
+        let exp = read(Path::new("examples/SRR0000001.fastq.gz")).unwrap();
+        let obs = read_all(Path::new("examples/SRR0000001.fastq.gz"), 1);
+
+        assert_eq!(obs, exp);
+    }
+}
@@ -1,14 +1,38 @@
 //! # Output formats
 //!
 //! Handle the formats in which the data can be returned.
+//!
+//! Structured formats (JSON, YAML, TOML) are serialized directly from the statistics, keeping their nesting.
+//! Tabular formats (human-readable, CSV, TSV) flatten the statistics into `field`/`value` rows, where nested fields are joined by `.`.
+//! For example, the number of records with length 150 appears as `lengths.150`.
 
-use std::str::FromStr;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::Value;
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 use thiserror::Error;
 
+/// Column headers for tabular output formats
+const TABLE_HEADER: [&str; 2] = ["field", "value"];
+
+/// Column headers for human-readable output
+const HUMAN_HEADER: [&str; 2] = ["Statistic", "Value"];
+
 #[derive(Debug, Error)]
 pub enum OutputFormatError {
     #[error("Output format {0} not understood.")]
     UnknownFormat(String),
+
+    #[error("Cannot serialize statistics as JSON. {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Cannot serialize statistics as YAML. {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("Cannot serialize statistics as TOML. {0}")]
+    Toml(#[from] toml::ser::Error),
+
+    #[error("Cannot write statistics as a table. {0}")]
+    Table(#[from] csv::Error),
 }
 
 #[derive(Debug, Clone)]
@@ -35,3 +59,299 @@ impl FromStr for OutputFormat {
         }
     }
 }
+
+impl OutputFormat {
+    /// Whether statistics are written as a table in this format
+    pub fn is_tabular(&self) -> bool {
+        matches!(self, Self::HumanReadable | Self::Csv | Self::Tsv)
+    }
+
+    /// Render a collection of statistics in this output format
+    pub fn render<T: Serialize>(&self, stats: &T) -> Result<String, OutputFormatError> {
+        let rendered = match self {
+            Self::HumanReadable | Self::Csv | Self::Tsv => {
+                let rows: Vec<[String; 2]> = flatten(stats)?
+                    .into_iter()
+                    .map(|(field, value)| [field, value])
+                    .collect();
+                let header = match self {
+                    Self::HumanReadable => HUMAN_HEADER,
+                    _ => TABLE_HEADER,
+                };
+
+                return self.render_table(&header, &rows);
+            }
+            Self::Json => serde_json::to_string_pretty(stats)?,
+            Self::Toml => toml::to_string_pretty(stats)?,
+            Self::Yaml => serde_yaml::to_string(stats)?,
+        };
+
+        Ok(with_final_newline(rendered))
+    }
+
+    /// Render rows of values under a header
+    ///
+    /// Structured formats have no notion of a table, so this always writes delimited values for them.
+    /// Check [`OutputFormat::is_tabular`] first.
+    pub fn render_table<H, V>(&self, header: &[H], rows: &[V]) -> Result<String, OutputFormatError>
+    where
+        H: AsRef<str>,
+        V: AsRef<[String]>,
+    {
+        let rendered = match self {
+            Self::HumanReadable => render_aligned(header, rows),
+            Self::Tsv => render_delimited(header, rows, b'\t')?,
+            _ => render_delimited(header, rows, b',')?,
+        };
+
+        Ok(with_final_newline(rendered))
+    }
+}
+
+/// Make sure every format ends with exactly one newline
+fn with_final_newline(mut rendered: String) -> String {
+    if !rendered.ends_with('\n') {
+        rendered.push('\n');
+    }
+
+    rendered
+}
+
+/// Flatten statistics into an ordered list of `(field, value)` pairs
+pub(crate) fn flatten<T: Serialize>(stats: &T) -> Result<Vec<(String, String)>, OutputFormatError> {
+    let mut rows = Vec::new();
+    flatten_value("", &serde_json::to_value(stats)?, &mut rows);
+
+    Ok(rows)
+}
+
+/// Recursively walk a serialized value, joining nested field names with `.`
+fn flatten_value(prefix: &str, value: &Value, rows: &mut Vec<(String, String)>) {
+    let join = |key: &dyn Display| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (key, val) in map {
+                flatten_value(&join(key), val, rows);
+            }
+        }
+        Value::Array(vals) => {
+            for (i, val) in vals.iter().enumerate() {
+                flatten_value(&join(&i), val, rows);
+            }
+        }
+        // missing values are left out of tables entirely
+        Value::Null => {}
+        Value::String(s) => rows.push((prefix.to_string(), s.clone())),
+        Value::Bool(b) => rows.push((prefix.to_string(), b.to_string())),
+        Value::Number(n) => rows.push((prefix.to_string(), n.to_string())),
+    }
+}
+
+/// Write rows as a table with columns aligned by padding with spaces
+fn render_aligned<H, V>(header: &[H], rows: &[V]) -> String
+where
+    H: AsRef<str>,
+    V: AsRef<[String]>,
+{
+    // widest value in each column, including the header
+    let mut widths: Vec<usize> = header.iter().map(|h| h.as_ref().len()).collect();
+    for row in rows {
+        for (i, value) in row.as_ref().iter().enumerate() {
+            match widths.get_mut(i) {
+                Some(w) => *w = (*w).max(value.len()),
+                None => widths.push(value.len()),
+            }
+        }
+    }
+
+    let mut table = String::new();
+    let header: Vec<&str> = header.iter().map(|h| h.as_ref()).collect();
+    push_aligned_line(&mut table, &header, &widths);
+    for row in rows {
+        let values: Vec<&str> = row.as_ref().iter().map(|v| v.as_str()).collect();
+        push_aligned_line(&mut table, &values, &widths);
+    }
+
+    table
+}
+
+/// Write a single line of an aligned table, without padding after the last value
+fn push_aligned_line(table: &mut String, values: &[&str], widths: &[usize]) {
+    let line: Vec<String> = values
+        .iter()
+        .zip(widths)
+        .map(|(value, width)| format!("{:<width$}", value))
+        .collect();
+
+    table.push_str(line.join("  ").trim_end());
+    table.push('\n');
+}
+
+/// Write rows as a delimited table with a header line
+fn render_delimited<H, V>(
+    header: &[H],
+    rows: &[V],
+    delimiter: u8,
+) -> Result<String, OutputFormatError>
+where
+    H: AsRef<str>,
+    V: AsRef<[String]>,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_writer(vec![]);
+
+    writer.write_record(header.iter().map(|h| h.as_ref()))?;
+    for row in rows {
+        writer.write_record(row.as_ref())?;
+    }
+
+    // the writer only ever holds a `Vec<u8>`, so this can't fail on I/O
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Serialize a `HashMap` with its keys in sorted order
+///
+/// `HashMap`s iterate in an arbitrary order, which would make the output change between runs.
+/// Keys are written as strings so that formats like TOML, which only allow string keys, can represent them.
+pub(crate) fn serialize_sorted_map<S, K, V>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    K: Ord + Display,
+    V: Serialize,
+{
+    let mut entries: Vec<(&K, &V)> = map.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut ser_map = serializer.serialize_map(Some(entries.len()))?;
+    for (k, v) in entries {
+        ser_map.serialize_entry(&k.to_string(), v)?;
+    }
+    ser_map.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct ExampleStats {
+        valid_records: u64,
+
+        #[serde(serialize_with = "serialize_sorted_map")]
+        lengths: HashMap<u64, u64>,
+    }
+
+    fn example() -> ExampleStats {
+        ExampleStats {
+            valid_records: 3,
+            lengths: HashMap::from([(100, 1), (36, 2)]),
+        }
+    }
+
+    #[track_caller]
+    fn check_render(fmt: OutputFormat, exp: &str) {
+        let obs = fmt.render(&example()).unwrap();
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn flatten_joins_nested_fields() {
+        let obs = flatten(&example()).unwrap();
+        let exp = vec![
+            ("valid_records".to_string(), "3".to_string()),
+            ("lengths.36".to_string(), "2".to_string()),
+            ("lengths.100".to_string(), "1".to_string()),
+        ];
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn human_readable_is_aligned() {
+        let exp = "\
+Statistic      Value
+valid_records  3
+lengths.36     2
+lengths.100    1
+";
+
+        check_render(OutputFormat::HumanReadable, exp);
+    }
+
+    #[test]
+    fn csv_has_header() {
+        let exp = "field,value\nvalid_records,3\nlengths.36,2\nlengths.100,1\n";
+
+        check_render(OutputFormat::Csv, exp);
+    }
+
+    #[test]
+    fn tsv_has_header() {
+        let exp = "field\tvalue\nvalid_records\t3\nlengths.36\t2\nlengths.100\t1\n";
+
+        check_render(OutputFormat::Tsv, exp);
+    }
+
+    #[test]
+    fn table_columns_are_aligned() {
+        let header = ["file", "valid_records"];
+        let rows = vec![
+            vec!["a.fastq".to_string(), "10".to_string()],
+            vec!["total".to_string(), "100".to_string()],
+        ];
+        let obs = OutputFormat::HumanReadable
+            .render_table(&header, &rows)
+            .unwrap();
+        let exp = "\
+file     valid_records
+a.fastq  10
+total    100
+";
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn json_keeps_nesting() {
+        let exp = r#"{
+  "valid_records": 3,
+  "lengths": {
+    "36": 2,
+    "100": 1
+  }
+}
+"#;
+
+        check_render(OutputFormat::Json, exp);
+    }
+
+    #[test]
+    fn yaml_keeps_nesting() {
+        let exp = "valid_records: 3\nlengths:\n  '36': 2\n  '100': 1\n";
+
+        check_render(OutputFormat::Yaml, exp);
+    }
+
+    #[test]
+    fn toml_uses_string_keys() {
+        let exp = "valid_records = 3\n\n[lengths]\n36 = 2\n100 = 1\n";
+
+        check_render(OutputFormat::Toml, exp);
+    }
+}
@@ -2,11 +2,33 @@
 //!
 //! Various helper functions used throughout the `bio-jtools` crate
 
+pub(crate) mod batch;
+pub(crate) mod decompress;
 pub(crate) mod formats;
+pub(crate) mod progress;
+pub(crate) mod sink;
+pub(crate) mod sniff;
+pub(crate) mod stats_file;
 
-use std::path::{Path, PathBuf};
+use crate::error::BjtError;
+use progress::Progress;
+use sniff::Compression;
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use thiserror::Error;
 
-#[derive(Clone, Copy, PartialEq)]
+/// Path used on the command line to read from standard input
+pub const STDIN_PATH: &str = "-";
+
+/// Path to standard input on Unix-like systems
+const DEV_STDIN_PATH: &str = "/dev/stdin";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Hts {
     Align(Align),
     Fastx(Fastx),
@@ -16,46 +38,85 @@ pub enum Hts {
     Peak(Peak),
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Fastx {
     Fasta,
     Fastq,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Align {
     Bam,
     Cram,
     Sam,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Tabix {
     Tab,
     Gff,
     Gtf,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Bed {
     Bed,
     BedPE,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Peak {
     BroadPeak,
     GappedPeak,
     NarrowPeak,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Variant {
     Bcf,
     Maf,
     Vcf,
 }
 
+#[derive(Debug, Error)]
+pub enum HtsFormatError {
+    #[error("Input format {0} not understood. Supported formats are: {SUPPORTED_EXTENSIONS:?}")]
+    UnknownFormat(String),
+}
+
+impl Display for Hts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Hts::Align(Align::Bam) => "BAM",
+            Hts::Align(Align::Cram) => "CRAM",
+            Hts::Align(Align::Sam) => "SAM",
+            Hts::Fastx(Fastx::Fasta) => "FASTA",
+            Hts::Fastx(Fastx::Fastq) => "FASTQ",
+            Hts::Variant(Variant::Bcf) => "BCF",
+            Hts::Variant(Variant::Maf) => "MAF",
+            Hts::Variant(Variant::Vcf) => "VCF",
+            Hts::Tabix(Tabix::Tab) => "tabix",
+            Hts::Tabix(Tabix::Gff) => "GFF",
+            Hts::Tabix(Tabix::Gtf) => "GTF",
+            Hts::Bed(Bed::Bed) => "BED",
+            Hts::Bed(Bed::BedPE) => "BEDPE",
+            Hts::Peak(Peak::BroadPeak) => "broadPeak",
+            Hts::Peak(Peak::GappedPeak) => "gappedPeak",
+            Hts::Peak(Peak::NarrowPeak) => "narrowPeak",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Hts {
+    type Err = HtsFormatError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // formats are named the same as their file extensions
+        hts_from_extension(s).ok_or_else(|| HtsFormatError::UnknownFormat(s.to_string()))
+    }
+}
+
 const SUPPORTED_EXTENSIONS: [&'static str; 18] = [
     "bam",
     "sam",
@@ -84,26 +145,49 @@ pub struct HtsFile {
 }
 
 impl HtsFile {
-    /// Create new HTS file
-    pub fn new(path: &Path) -> HtsFile {
-        // check for path existing and that it is a file
-        if !path.exists() {
-            panic!("File provided does not exist. Please provide a path that exists.");
-        } else if !path.is_file() {
-            panic!("Path provided is not a file. Please provide the path to a file.");
+    /// Create new HTS file, detecting its format from its content and extension
+    pub fn new(path: &Path) -> Result<HtsFile, BjtError> {
+        Self::with_format(path, None)
+    }
+
+    /// Create new HTS file, with an explicit format that overrides the one detected from its extension
+    ///
+    /// Without an explicit format, the format of a regular file is detected from its content, and checked against its extension.
+    /// Standard input (`-` or `/dev/stdin`) and named pipes can be read, but they can't be inspected ahead of time, so their formats are taken from their extensions.
+    /// Standard input has no extension, so its format must be given.
+    pub fn with_format(path: &Path, format: Option<Hts>) -> Result<HtsFile, BjtError> {
+        let is_stdin = is_stdin(path);
+
+        // check for path existing and that it is a file (or something that can be read like one)
+        if !is_stdin && !path.exists() {
+            return Err(BjtError::MissingPath {
+                path: path.to_path_buf(),
+            });
+        } else if path.is_dir() {
+            return Err(BjtError::NotAFile {
+                path: path.to_path_buf(),
+            });
         }
 
+        // regular files can be inspected without consuming their content
+        let format = match format {
+            None if path.is_file() => Some(sniff::detect_format(path)?),
+            _ => format,
+        };
+
         // create HtsFile everything looks good so far
-        if let Some(hts_type) = detect_filetype(path) {
-            HtsFile {
+        if let Some(hts_type) = format.or_else(|| detect_filetype(path)) {
+            Ok(HtsFile {
                 path: path.to_path_buf(),
                 hts_type,
-            }
+            })
+        } else if is_stdin {
+            Err(BjtError::StdinFormatRequired)
         } else {
-            panic!(
-                "{}",
-                format!("Could not parse HTS file type from path. Supported file extensions are (excluding compression): {:?}", SUPPORTED_EXTENSIONS)
-            );
+            Err(BjtError::UnknownFormat {
+                path: path.to_path_buf(),
+                extensions: &SUPPORTED_EXTENSIONS,
+            })
         }
     }
     /// HTS file path
@@ -114,22 +198,51 @@ impl HtsFile {
     pub fn filetype(&self) -> Hts {
         self.hts_type
     }
-}
+    /// Whether the HTS file is read from standard input
+    pub fn is_stdin(&self) -> bool {
+        is_stdin(&self.path)
+    }
+    /// Open the HTS file (or standard input) for reading
+    pub fn reader(&self) -> Result<Box<dyn Read + Send>, BjtError> {
+        if self.is_stdin() {
+            return Ok(Box::new(io::stdin()));
+        }
 
-/// Determine if a file is compressed or not
-fn file_is_zipped(path: &Path) -> bool {
-    if !path.is_file() {
-        return false;
+        let file = File::open(&self.path).map_err(|source| BjtError::Read {
+            path: self.path.clone(),
+            source,
+        })?;
+        Ok(Box::new(file))
     }
-    match path.extension() {
-        Some(ext) => match ext.to_str() {
-            Some("gz") | Some("bz2") => true,
-            _ => false,
-        },
-        None => false,
+
+    /// Open the HTS file (or standard input) for reading, decompressing gzip, BGZF, and Zstandard files on up to `threads` threads
+    ///
+    /// The bytes read from the file are counted by `progress`.
+    pub fn decompressed_reader(
+        &self,
+        threads: u16,
+        progress: &Progress,
+    ) -> Result<Box<dyn Read + Send>, BjtError> {
+        decompress::decompress(progress.track(self.reader()?), threads).map_err(|source| {
+            BjtError::Read {
+                path: self.path.clone(),
+                source,
+            }
+        })
     }
 }
 
+/// Whether a path refers to standard input
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH) || path == Path::new(DEV_STDIN_PATH)
+}
+
+/// Determine if a file is compressed or not, from its extension
+fn file_is_zipped(path: &Path) -> bool {
+    Compression::from_path(path) != Compression::None
+}
+
+/// Determine the HTS file type from a file's extension, ignoring any compression extension
 pub fn detect_filetype(path: &Path) -> Option<Hts> {
     let stem: &Path;
     // strip zipped extension if it's a zipped file
@@ -139,26 +252,78 @@ pub fn detect_filetype(path: &Path) -> Option<Hts> {
         stem = path;
     }
 
-    match stem.extension() {
-        Some(ext) => match ext.to_str() {
-            Some("bam") => Some(Hts::Align(Align::Bam)),
-            Some("sam") => Some(Hts::Align(Align::Sam)),
-            Some("cram") => Some(Hts::Align(Align::Cram)),
-            Some("fasta") | Some("fa") => Some(Hts::Fastx(Fastx::Fasta)),
-            Some("fastq") | Some("fq") => Some(Hts::Fastx(Fastx::Fastq)),
-            Some("bcf") => Some(Hts::Variant(Variant::Bcf)),
-            Some("vcf") => Some(Hts::Variant(Variant::Vcf)),
-            Some("maf") => Some(Hts::Variant(Variant::Maf)),
-            Some("tbx") => Some(Hts::Tabix(Tabix::Tab)),
-            Some("gff") => Some(Hts::Tabix(Tabix::Gff)),
-            Some("gtf") => Some(Hts::Tabix(Tabix::Gtf)),
-            Some("bed") => Some(Hts::Bed(Bed::Bed)),
-            Some("bedpe") => Some(Hts::Bed(Bed::BedPE)),
-            Some("narrowPeak") => Some(Hts::Peak(Peak::NarrowPeak)),
-            Some("broadPeak") => Some(Hts::Peak(Peak::BroadPeak)),
-            Some("gappedPeak") => Some(Hts::Peak(Peak::GappedPeak)),
-            _ => None,
-        },
-        None => None,
+    stem.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(hts_from_extension)
+}
+
+/// Determine the HTS file type from a file extension, excluding compression
+fn hts_from_extension(ext: &str) -> Option<Hts> {
+    match ext {
+        "bam" => Some(Hts::Align(Align::Bam)),
+        "sam" => Some(Hts::Align(Align::Sam)),
+        "cram" => Some(Hts::Align(Align::Cram)),
+        "fasta" | "fa" => Some(Hts::Fastx(Fastx::Fasta)),
+        "fastq" | "fq" => Some(Hts::Fastx(Fastx::Fastq)),
+        "bcf" => Some(Hts::Variant(Variant::Bcf)),
+        "vcf" => Some(Hts::Variant(Variant::Vcf)),
+        "maf" => Some(Hts::Variant(Variant::Maf)),
+        "tbx" => Some(Hts::Tabix(Tabix::Tab)),
+        "gff" => Some(Hts::Tabix(Tabix::Gff)),
+        "gtf" => Some(Hts::Tabix(Tabix::Gtf)),
+        "bed" => Some(Hts::Bed(Bed::Bed)),
+        "bedpe" => Some(Hts::Bed(Bed::BedPE)),
+        "narrowPeak" => Some(Hts::Peak(Peak::NarrowPeak)),
+        "broadPeak" => Some(Hts::Peak(Peak::BroadPeak)),
+        "gappedPeak" => Some(Hts::Peak(Peak::GappedPeak)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_format_is_parsed() {
+        assert_eq!("fq".parse::<Hts>().unwrap(), Hts::Fastx(Fastx::Fastq));
+        assert_eq!("bam".parse::<Hts>().unwrap(), Hts::Align(Align::Bam));
+        assert!("txt".parse::<Hts>().is_err());
+    }
+
+    #[test]
+    fn stdin_requires_format() {
+        let stdin = Path::new(STDIN_PATH);
+
+        assert!(matches!(
+            HtsFile::new(stdin),
+            Err(BjtError::StdinFormatRequired)
+        ));
+        assert_eq!(
+            HtsFile::with_format(stdin, Some(Hts::Fastx(Fastx::Fastq)))
+                .unwrap()
+                .filetype(),
+            Hts::Fastx(Fastx::Fastq)
+        );
+    }
+
+    #[test]
+    fn input_format_overrides_extension() {
+        let obs = HtsFile::with_format(
+            Path::new("examples/SRR0000001.fastq"),
+            Some(Hts::Fastx(Fastx::Fasta)),
+        )
+        .unwrap();
+
+        assert_eq!(obs.filetype(), Hts::Fastx(Fastx::Fasta));
+        assert!(!obs.is_stdin());
+    }
+
+    #[test]
+    fn directories_are_not_files() {
+        assert!(matches!(
+            HtsFile::new(Path::new("examples")),
+            Err(BjtError::NotAFile { .. })
+        ));
     }
 }
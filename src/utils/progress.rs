@@ -0,0 +1,215 @@
+//! # Progress and logging
+//!
+//! Log messages and progress bars written to STDERR, controlled by the global `-v/--verbose` and `-q/--quiet` flags.
+//!
+//! By default only warnings and errors are logged.
+//! Each `-v` shows more detail, and progress bars are shown from the first `-v` onward, as long as STDERR is a terminal.
+//! The `RUST_LOG` environment variable can further filter log messages by module.
+//!
+//! Log messages and progress bars share the terminal, so bars are cleared while a message is written and redrawn afterwards.
+
+use super::HtsFile;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use log::{info, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::{
+    fmt::Write as FmtWrite,
+    fs,
+    io::Read,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+/// Progress bars shown for all files being processed
+static PROGRESS_BARS: OnceLock<MultiProgress> = OnceLock::new();
+
+/// Template for reading a file of known size
+const FILE_TEMPLATE: &str = "{prefix} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} ({records} records, {records_per_sec}/s, ETA {eta})";
+
+/// Template for reading a stream of unknown size, like standard input
+const STREAM_TEMPLATE: &str =
+    "{spinner} {prefix} [{elapsed_precise}] {bytes} ({records} records, {records_per_sec}/s)";
+
+/// Template for a fixed number of steps
+const STEPS_TEMPLATE: &str = "{prefix} [{elapsed_precise}] {wide_bar} {pos}/{len} (ETA {eta})";
+
+/// Progress bars for all files, hidden until logging is set up
+fn progress_bars() -> &'static MultiProgress {
+    PROGRESS_BARS.get_or_init(|| MultiProgress::with_draw_target(ProgressDrawTarget::hidden()))
+}
+
+/// Log to STDERR without overwriting any progress bars
+struct ProgressLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for ProgressLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.matches(record) {
+            progress_bars().suspend(|| self.inner.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Send log messages at or above `level` to STDERR, and show progress bars from [`LevelFilter::Info`] onward
+pub fn init_logging(level: LevelFilter) -> Result<(), SetLoggerError> {
+    let target = if level >= LevelFilter::Info {
+        ProgressDrawTarget::stderr()
+    } else {
+        ProgressDrawTarget::hidden()
+    };
+    progress_bars().set_draw_target(target);
+
+    let inner = env_logger::Builder::new()
+        .filter_level(level)
+        .parse_default_env()
+        .build();
+    let max_level = inner.filter();
+    log::set_boxed_logger(Box::new(ProgressLogger { inner }))?;
+    log::set_max_level(max_level);
+
+    Ok(())
+}
+
+/// Progress through a file, or through a series of steps
+pub(crate) struct Progress {
+    bar: ProgressBar,
+
+    /// Number of records processed so far
+    records: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Track the bytes read from an HTS file, and the number of records parsed from it
+    pub fn reading(hts: &HtsFile) -> Self {
+        let records = Arc::new(AtomicU64::new(0));
+        let size = if hts.is_stdin() {
+            None
+        } else {
+            fs::metadata(hts.path()).ok().map(|m| m.len())
+        };
+        let (bar, template) = match size {
+            Some(len) => (ProgressBar::new(len), FILE_TEMPLATE),
+            None => (ProgressBar::new_spinner(), STREAM_TEMPLATE),
+        };
+
+        let (n, rate) = (records.clone(), records.clone());
+        let style = ProgressStyle::with_template(template)
+            .expect("progress templates are valid")
+            .with_key("records", move |_: &ProgressState, w: &mut dyn FmtWrite| {
+                let _ = write!(w, "{}", n.load(Ordering::Relaxed));
+            })
+            .with_key(
+                "records_per_sec",
+                move |state: &ProgressState, w: &mut dyn FmtWrite| {
+                    let secs = state.elapsed().as_secs_f64();
+                    let per_sec = if secs > 0.0 {
+                        rate.load(Ordering::Relaxed) as f64 / secs
+                    } else {
+                        0.0
+                    };
+                    let _ = write!(w, "{:.0}", per_sec);
+                },
+            );
+
+        let name = hts.path().file_name().unwrap_or_default().to_string_lossy();
+        let bar = progress_bars()
+            .add(bar)
+            .with_style(style)
+            .with_prefix(name.into_owned());
+
+        Self { bar, records }
+    }
+
+    /// Track a fixed number of steps
+    pub fn steps(prefix: &'static str, len: u64) -> Self {
+        let style =
+            ProgressStyle::with_template(STEPS_TEMPLATE).expect("progress templates are valid");
+        let bar = progress_bars()
+            .add(ProgressBar::new(len))
+            .with_style(style)
+            .with_prefix(prefix);
+
+        Self {
+            bar,
+            records: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Count the bytes read from a stream
+    ///
+    /// Compressed files are tracked before they are decompressed, so that progress is measured against the size of the file.
+    pub fn track(&self, reader: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+        if self.bar.is_hidden() {
+            reader
+        } else {
+            Box::new(self.bar.wrap_read(reader))
+        }
+    }
+
+    /// Mark a record as processed
+    pub fn record(&self) {
+        self.records.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Mark a step as done
+    pub fn step(&self) {
+        self.bar.inc(1);
+    }
+
+    /// Number of records processed so far
+    pub fn n_records(&self) -> u64 {
+        self.records.load(Ordering::Relaxed)
+    }
+
+    /// Remove the progress bar, and log how long it took to read a file
+    pub fn finish(self, hts: &HtsFile) {
+        info!(
+            "Processed {} records from {} in {:.1?}",
+            self.n_records(),
+            hts.path().display(),
+            self.bar.elapsed()
+        );
+    }
+}
+
+impl Drop for Progress {
+    /// Remove the progress bar when it's finished, or when processing stops early
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+        progress_bars().remove(&self.bar);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::read, path::Path};
+
+    #[test]
+    fn tracked_reader_is_unchanged() {
+        let path = Path::new("examples/SRR0000001.fastq");
+        let hts = HtsFile::new(path).unwrap();
+        let progress = Progress::reading(&hts);
+
+        let mut obs = vec![];
+        progress
+            .track(hts.reader().unwrap())
+            .read_to_end(&mut obs)
+            .unwrap();
+        progress.record();
+        progress.record();
+
+        assert_eq!(obs, read(path).unwrap());
+        assert_eq!(progress.n_records(), 2);
+    }
+}
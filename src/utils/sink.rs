@@ -0,0 +1,365 @@
+//! # Output sinks
+//!
+//! Write records to a file or STDOUT, optionally compressed with gzip, BGZF, or zstd.
+//!
+//! The compression is chosen with `--compress`, or from the extension of the output file (`.gz`, `.bgz`, `.zst`).
+//! Files are first written to a hidden temporary file next to the output, and only renamed into place once everything has been written.
+//! A command that fails or crashes partway through never leaves a truncated output behind.
+
+use super::sniff::Compression;
+use crate::error::BjtError;
+use bam::bgzip::Block;
+use clap::Args;
+use flate2::write::GzEncoder;
+use std::{
+    fs::{remove_file, rename, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Largest number of bytes to put in a single BGZF block, matching `bgzip`
+const BGZF_BLOCK_CONTENT: usize = 0xff00;
+
+/// Default gzip and BGZF compression level
+const DEFAULT_GZIP_LEVEL: u32 = 6;
+
+/// Highest gzip and BGZF compression level
+const MAX_GZIP_LEVEL: u32 = 9;
+
+/// Default zstd compression level
+const DEFAULT_ZSTD_LEVEL: u32 = 3;
+
+/// Highest zstd compression level
+const MAX_ZSTD_LEVEL: u32 = 22;
+
+/// CLI options for where and how to write output
+#[derive(Debug, Args)]
+pub(crate) struct OutputOpts {
+    /// Output file name (STDOUT if not given).
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Compress the output with gzip, bgzf, or zstd (or none), instead of detecting it from the output file extension.
+    #[clap(long, value_name = "FORMAT")]
+    compress: Option<Compression>,
+
+    /// Compression level [default: 6 for gzip and bgzf, 3 for zstd].
+    #[clap(long, value_name = "LEVEL")]
+    compress_level: Option<u32>,
+}
+
+impl OutputOpts {
+    /// Output file name, if not writing to STDOUT
+    pub fn path(&self) -> Option<&Path> {
+        self.output.as_deref()
+    }
+
+    /// Open the output for writing
+    pub fn sink(&self) -> Result<OutputSink, BjtError> {
+        OutputSink::create(self.path(), self.compress, self.compress_level)
+    }
+}
+
+/// Compress everything written to an inner writer
+enum Encoder {
+    Plain(BufWriter<Box<dyn Write>>),
+    Gzip(GzEncoder<BufWriter<Box<dyn Write>>>),
+    Bgzf(BgzfEncoder<BufWriter<Box<dyn Write>>>),
+    Zstd(zstd::Encoder<'static, BufWriter<Box<dyn Write>>>),
+}
+
+impl Encoder {
+    /// Finish compressing and flush everything to the inner writer
+    fn finish(self) -> io::Result<()> {
+        let mut inner = match self {
+            Self::Plain(inner) => inner,
+            Self::Gzip(encoder) => encoder.finish()?,
+            Self::Bgzf(encoder) => encoder.finish()?,
+            Self::Zstd(encoder) => encoder.finish()?,
+        };
+
+        inner.flush()
+    }
+}
+
+/// BGZF compression, as a series of independently compressed gzip blocks
+struct BgzfEncoder<W: Write> {
+    inner: W,
+    block: Block,
+    level: flate2::Compression,
+}
+
+impl<W: Write> BgzfEncoder<W> {
+    fn new(inner: W, level: u32) -> Self {
+        Self {
+            inner,
+            block: Block::new(),
+            level: flate2::Compression::new(level),
+        }
+    }
+
+    /// Compress and write out the current block
+    fn write_block(&mut self) -> io::Result<()> {
+        self.block.compress(self.level)?;
+        self.block.dump(&mut self.inner)?;
+        self.block.reset();
+
+        Ok(())
+    }
+
+    /// Write out any remaining content, followed by the empty block that marks the end of a BGZF file
+    fn finish(mut self) -> io::Result<W> {
+        if self.block.uncompressed_size() > 0 {
+            self.write_block()?;
+        }
+        self.write_block()?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for BgzfEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let space = BGZF_BLOCK_CONTENT - self.block.uncompressed_size() as usize;
+        let n = self.block.extend_contents(&buf[..buf.len().min(space)]);
+        if self.block.uncompressed_size() as usize >= BGZF_BLOCK_CONTENT {
+            self.write_block()?;
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A file (or STDOUT) being written to, that's only put in place once it's finished
+pub struct OutputSink {
+    /// Compressed writer
+    encoder: Option<Encoder>,
+
+    /// Temporary file being written to, and a handle to it for syncing to disk
+    temp: Option<(PathBuf, File)>,
+
+    /// Where the finished output goes
+    path: Option<PathBuf>,
+}
+
+impl OutputSink {
+    /// Open a file (or STDOUT, if no path is given) for writing
+    ///
+    /// Without an explicit compression, it is chosen from the file extension.
+    pub fn create(
+        path: Option<&Path>,
+        compression: Option<Compression>,
+        level: Option<u32>,
+    ) -> Result<Self, BjtError> {
+        let display_path = path.unwrap_or(Path::new("-"));
+        let compression = compression
+            .or_else(|| path.map(Compression::from_path))
+            .unwrap_or(Compression::None);
+        let level = check_level(display_path, compression, level)?;
+
+        let (inner, temp): (Box<dyn Write>, _) = match path {
+            Some(path) => {
+                let temp_path = temp_path(path);
+                let file = File::create(&temp_path)
+                    .and_then(|f| Ok((f.try_clone()?, f)))
+                    .map_err(|source| BjtError::Write {
+                        path: temp_path.clone(),
+                        source,
+                    })?;
+                (Box::new(file.0), Some((temp_path, file.1)))
+            }
+            None => (Box::new(io::stdout()), None),
+        };
+        let inner = BufWriter::new(inner);
+
+        let encoder = match compression {
+            Compression::None => Encoder::Plain(inner),
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(inner, flate2::Compression::new(level)))
+            }
+            Compression::Bgzf => Encoder::Bgzf(BgzfEncoder::new(inner, level)),
+            Compression::Zstd => {
+                Encoder::Zstd(zstd::Encoder::new(inner, level as i32).map_err(|source| {
+                    BjtError::Write {
+                        path: display_path.to_path_buf(),
+                        source,
+                    }
+                })?)
+            }
+            Compression::Bzip2 | Compression::Xz => {
+                return Err(BjtError::UnsupportedCompression {
+                    path: display_path.to_path_buf(),
+                    compression,
+                })
+            }
+        };
+
+        Ok(Self {
+            encoder: Some(encoder),
+            temp,
+            path: path.map(Path::to_path_buf),
+        })
+    }
+
+    /// Finish writing, and move the output into place
+    pub fn finish(mut self) -> Result<(), BjtError> {
+        let path = self.path.clone().unwrap_or_else(|| PathBuf::from("-"));
+        let write_err = |source| BjtError::Write {
+            path: path.clone(),
+            source,
+        };
+
+        if let Some(encoder) = self.encoder.take() {
+            encoder.finish().map_err(write_err)?;
+        }
+        // the temporary file is removed when dropped, unless it's been moved into place
+        if let (Some((temp_path, file)), Some(dest)) = (&self.temp, &self.path) {
+            file.sync_all().map_err(write_err)?;
+            rename(temp_path, dest).map_err(write_err)?;
+            self.temp = None;
+        }
+
+        Ok(())
+    }
+
+    fn encoder(&mut self) -> &mut dyn Write {
+        match self.encoder.as_mut() {
+            Some(Encoder::Plain(w)) => w,
+            Some(Encoder::Gzip(w)) => w,
+            Some(Encoder::Bgzf(w)) => w,
+            Some(Encoder::Zstd(w)) => w,
+            None => unreachable!("output sinks can't be written to after they're finished"),
+        }
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoder().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encoder().flush()
+    }
+}
+
+impl Drop for OutputSink {
+    /// Remove the temporary file of an output that was never finished
+    fn drop(&mut self) {
+        if let Some((temp_path, _)) = self.temp.take() {
+            let _ = remove_file(temp_path);
+        }
+    }
+}
+
+/// Hidden temporary file next to the output, so that it can be renamed into place on the same file system
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.tmp", name, process::id()))
+}
+
+/// Check that a compression level is valid, or get the default level
+fn check_level(path: &Path, compression: Compression, level: Option<u32>) -> Result<u32, BjtError> {
+    let (default, max) = match compression {
+        Compression::Zstd => (DEFAULT_ZSTD_LEVEL, MAX_ZSTD_LEVEL),
+        _ => (DEFAULT_GZIP_LEVEL, MAX_GZIP_LEVEL),
+    };
+
+    match level {
+        None => Ok(default),
+        Some(level) if level <= max => Ok(level),
+        Some(level) => Err(BjtError::InvalidCompressionLevel {
+            path: path.to_path_buf(),
+            compression,
+            level,
+            max,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::sniff::Sniffed;
+    use std::{fs::read, io::Read};
+    use tempfile::tempdir;
+
+    fn write_and_read(name: &str, compression: Option<Compression>) -> Vec<u8> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(name);
+        let mut sink = OutputSink::create(Some(&path), compression, None).unwrap();
+        sink.write_all(b"@r1\nACGT\n+\nIIII\n").unwrap();
+        sink.finish().unwrap();
+
+        read(&path).unwrap()
+    }
+
+    #[test]
+    fn compression_is_chosen_by_extension() {
+        let gzip = write_and_read("reads.fastq.gz", None);
+        let bgzf = write_and_read("reads.fastq.bgz", None);
+        let zstd = write_and_read("reads.fastq.zst", None);
+        let plain = write_and_read("reads.fastq", None);
+
+        assert_eq!(Sniffed::from_head(&gzip).compression, Compression::Gzip);
+        assert_eq!(Sniffed::from_head(&bgzf).compression, Compression::Bgzf);
+        assert_eq!(Sniffed::from_head(&zstd).compression, Compression::Zstd);
+        assert_eq!(plain, b"@r1\nACGT\n+\nIIII\n");
+    }
+
+    #[test]
+    fn flag_overrides_extension() {
+        let obs = write_and_read("flagged.fastq", Some(Compression::Bgzf));
+        let mut content = String::new();
+        flate2::read::MultiGzDecoder::new(obs.as_slice())
+            .read_to_string(&mut content)
+            .unwrap();
+
+        assert_eq!(content, "@r1\nACGT\n+\nIIII\n");
+    }
+
+    #[test]
+    fn bgzf_is_split_into_blocks() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("blocks.txt.bgz");
+        let exp: Vec<u8> = (0..200_000).map(|i| b"ACGT\n"[i % 5]).collect();
+        let mut sink = OutputSink::create(Some(&path), None, None).unwrap();
+        sink.write_all(&exp).unwrap();
+        sink.finish().unwrap();
+
+        let mut obs = vec![];
+        flate2::read::MultiGzDecoder::new(File::open(&path).unwrap())
+            .read_to_end(&mut obs)
+            .unwrap();
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn unfinished_output_is_removed() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("unfinished.fastq");
+        {
+            let mut sink = OutputSink::create(Some(&path), None, None).unwrap();
+            sink.write_all(b"@r1\n").unwrap();
+        }
+
+        assert!(!path.exists());
+        assert!(!temp_path(&path).exists());
+    }
+
+    #[test]
+    fn invalid_level_is_err() {
+        let obs = OutputSink::create(None, Some(Compression::Gzip), Some(10));
+
+        assert!(matches!(
+            obs,
+            Err(BjtError::InvalidCompressionLevel { max: 9, .. })
+        ));
+    }
+}
@@ -0,0 +1,395 @@
+//! # Format detection
+//!
+//! Detect the compression and format of an HTS file from its first bytes, instead of trusting its extension.
+//!
+//! Compression is detected from magic bytes (gzip, BGZF, bzip2, xz, zstd).
+//! The format is then detected from the start of the decompressed content: the `BAM\1`, `BCF\2`, and `CRAM` magics, `##fileformat=VCF` for VCF, `@HD` (or another header tag) for SAM, `@` for FASTQ, and `>` for FASTA.
+//! Formats without a recognizable start, like BED, fall back to their extensions.
+
+use super::{detect_filetype, Align, Fastx, Hts, Variant};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// Number of raw bytes to read from the start of a file, enough for a full BGZF block
+const SNIFF_RAW_BYTES: usize = 65536;
+
+/// Number of decompressed bytes to inspect for the format
+const SNIFF_CONTENT_BYTES: usize = 4096;
+
+/// Magic bytes at the start of a gzip (and BGZF) file
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b\x08";
+
+/// Magic bytes at the start of a bzip2 file
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// Magic bytes at the start of an xz file
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\x00";
+
+/// Magic bytes at the start of a zstd file
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+
+/// Magic bytes at the start of decompressed BAM content
+const BAM_MAGIC: &[u8] = b"BAM\x01";
+
+/// Magic bytes at the start of decompressed BCF content
+const BCF_MAGIC: &[u8] = b"BCF\x02";
+
+/// Magic bytes at the start of a CRAM file
+const CRAM_MAGIC: &[u8] = b"CRAM";
+
+/// First line of a VCF file
+const VCF_MAGIC: &[u8] = b"##fileformat=VCF";
+
+/// Record types that can start a SAM header
+const SAM_HEADER_TAGS: [&[u8]; 5] = [b"@HD\t", b"@SQ\t", b"@RG\t", b"@PG\t", b"@CO\t"];
+
+/// How a file is compressed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bgzf,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression of a file from its first bytes
+    pub fn from_magic(head: &[u8]) -> Self {
+        if head.starts_with(GZIP_MAGIC) {
+            // BGZF is gzip with a `BC` extra subfield in every block header
+            let has_extra = head.get(3).is_some_and(|flags| flags & 0x04 != 0);
+            match (has_extra, head.get(12..14)) {
+                (true, Some(b"BC")) => Self::Bgzf,
+                _ => Self::Gzip,
+            }
+        } else if head.starts_with(BZIP2_MAGIC) {
+            Self::Bzip2
+        } else if head.starts_with(XZ_MAGIC) {
+            Self::Xz
+        } else if head.starts_with(ZSTD_MAGIC) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+
+    /// Detect the compression of a file from its extension
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Self::Gzip,
+            Some("bgz") => Self::Bgzf,
+            Some("bz2") => Self::Bzip2,
+            Some("xz") => Self::Xz,
+            Some("zst") => Self::Zstd,
+            _ => Self::None,
+        }
+    }
+
+    /// Whether two compressions can be read the same way (BGZF files are also valid gzip files)
+    fn is_compatible(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::Gzip | Self::Bgzf, Self::Gzip | Self::Bgzf)
+        ) || self == other
+    }
+
+    /// Decompress the start of some compressed content
+    ///
+    /// The content is usually cut off partway through, so decompression stops at the first error instead of failing.
+    fn decompress_head(&self, head: &[u8]) -> Vec<u8> {
+        let mut decoder: Box<dyn Read + '_> = match self {
+            Self::None => return head.iter().take(SNIFF_CONTENT_BYTES).copied().collect(),
+            Self::Gzip | Self::Bgzf => Box::new(flate2::read::MultiGzDecoder::new(head)),
+            Self::Bzip2 => Box::new(bzip2::read::BzDecoder::new(head)),
+            Self::Xz => Box::new(xz2::read::XzDecoder::new(head)),
+            Self::Zstd => match zstd::stream::read::Decoder::new(head) {
+                Ok(decoder) => Box::new(decoder),
+                Err(_) => return vec![],
+            },
+        };
+
+        let mut content = vec![0; SNIFF_CONTENT_BYTES];
+        let mut n_read = 0;
+        while n_read < content.len() {
+            match decoder.read(&mut content[n_read..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n_read += n,
+            }
+        }
+        content.truncate(n_read);
+
+        content
+    }
+}
+
+impl FromStr for Compression {
+    type Err = SniffError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "bgzf" | "bgz" | "bgzip" => Ok(Self::Bgzf),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            _ => Err(SniffError::UnknownCompression(s.to_string())),
+        }
+    }
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "uncompressed",
+            Self::Gzip => "gzip",
+            Self::Bgzf => "BGZF",
+            Self::Bzip2 => "bzip2",
+            Self::Xz => "xz",
+            Self::Zstd => "zstd",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Errors when detecting the format of a file
+#[derive(Debug, Error)]
+pub enum SniffError {
+    #[error("Cannot read the start of {path} to detect its format. {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{path} has the extension of a {extension} file, but its content looks like a {content} file. Please rename it, or provide the format with `--input-format`.")]
+    FormatMismatch {
+        path: PathBuf,
+        extension: Hts,
+        content: Hts,
+    },
+
+    #[error("The extension of {path} says its content is {extension}, but it is actually {content}. Please rename it to match its compression.")]
+    CompressionMismatch {
+        path: PathBuf,
+        extension: Compression,
+        content: Compression,
+    },
+
+    #[error("Could not detect the format of {path} from its extension or content. Please provide it with `--input-format`.")]
+    Unrecognized { path: PathBuf },
+
+    #[error("Compression {0} not understood. Expected one of `none`, `gzip`, `bgzf`, or `zstd`.")]
+    UnknownCompression(String),
+}
+
+/// Compression and format detected from the start of a file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sniffed {
+    /// How the file is compressed
+    pub compression: Compression,
+
+    /// Format of the decompressed content, if it could be recognized
+    pub format: Option<Hts>,
+}
+
+impl Sniffed {
+    /// Detect the compression and format from the first bytes of a file
+    pub fn from_head(head: &[u8]) -> Self {
+        let compression = Compression::from_magic(head);
+        let content = compression.decompress_head(head);
+
+        Self {
+            compression,
+            format: format_from_content(&content),
+        }
+    }
+
+    /// Detect the compression and format from the first bytes of a file on disk
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let mut head = Vec::with_capacity(SNIFF_RAW_BYTES);
+        File::open(path)?
+            .take(SNIFF_RAW_BYTES as u64)
+            .read_to_end(&mut head)?;
+
+        Ok(Self::from_head(&head))
+    }
+}
+
+/// Detect the format of some decompressed content from how it starts
+fn format_from_content(content: &[u8]) -> Option<Hts> {
+    if content.starts_with(BAM_MAGIC) {
+        Some(Hts::Align(Align::Bam))
+    } else if content.starts_with(CRAM_MAGIC) {
+        Some(Hts::Align(Align::Cram))
+    } else if content.starts_with(BCF_MAGIC) {
+        Some(Hts::Variant(Variant::Bcf))
+    } else if content.starts_with(VCF_MAGIC) {
+        Some(Hts::Variant(Variant::Vcf))
+    } else if SAM_HEADER_TAGS.iter().any(|tag| content.starts_with(tag)) {
+        Some(Hts::Align(Align::Sam))
+    } else if content.starts_with(b"@") {
+        Some(Hts::Fastx(Fastx::Fastq))
+    } else if content.starts_with(b">") {
+        Some(Hts::Fastx(Fastx::Fasta))
+    } else {
+        None
+    }
+}
+
+/// Detect the format of a file from its content, checking that it agrees with its extension
+pub fn detect_format(path: &Path) -> Result<Hts, SniffError> {
+    let sniffed = Sniffed::from_path(path).map_err(|source| SniffError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    reconcile(path, sniffed)
+}
+
+/// Compare the format and compression detected from a file's content to those from its extension
+fn reconcile(path: &Path, sniffed: Sniffed) -> Result<Hts, SniffError> {
+    let extension = detect_filetype(path);
+
+    let format = match (extension, sniffed.format) {
+        (Some(extension), Some(content)) if extension != content => {
+            return Err(SniffError::FormatMismatch {
+                path: path.to_path_buf(),
+                extension,
+                content,
+            })
+        }
+        (_, Some(format)) | (Some(format), None) => format,
+        (None, None) => {
+            return Err(SniffError::Unrecognized {
+                path: path.to_path_buf(),
+            })
+        }
+    };
+
+    // BAM and BCF files are always BGZF-compressed, without an extra extension to say so
+    let ext_compression = match format {
+        Hts::Align(Align::Bam) | Hts::Variant(Variant::Bcf) => Compression::Bgzf,
+        _ => Compression::from_path(path),
+    };
+    if !ext_compression.is_compatible(&sniffed.compression) {
+        return Err(SniffError::CompressionMismatch {
+            path: path.to_path_buf(),
+            extension: ext_compression,
+            content: sniffed.compression,
+        });
+    }
+
+    Ok(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Bed;
+    use flate2::{write::GzEncoder, Compression as GzLevel};
+    use std::io::Write;
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], GzLevel::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn bam_is_bgzf() {
+        let obs = Sniffed::from_path(Path::new("examples/SRR0000001.hg19.bam")).unwrap();
+        let exp = Sniffed {
+            compression: Compression::Bgzf,
+            format: Some(Hts::Align(Align::Bam)),
+        };
+
+        assert_eq!(obs, exp);
+    }
+
+    #[test]
+    fn gzipped_fastq_is_detected() {
+        let obs = detect_format(Path::new("examples/SRR0000001.fastq.gz")).unwrap();
+
+        assert_eq!(obs, Hts::Fastx(Fastx::Fastq));
+    }
+
+    #[test]
+    fn text_formats_are_detected() {
+        let cases: [(&[u8], Option<Hts>); 5] = [
+            (b"@HD\tVN:1.6\n", Some(Hts::Align(Align::Sam))),
+            (b"@read1\nACGT\n+\nIIII\n", Some(Hts::Fastx(Fastx::Fastq))),
+            (b">chr1\nACGT\n", Some(Hts::Fastx(Fastx::Fasta))),
+            (b"##fileformat=VCFv4.2\n", Some(Hts::Variant(Variant::Vcf))),
+            (b"chr1\t0\t100\n", None),
+        ];
+
+        for (content, exp) in cases {
+            assert_eq!(Sniffed::from_head(content).format, exp);
+            assert_eq!(Sniffed::from_head(&gzip(content)).format, exp);
+        }
+    }
+
+    #[test]
+    fn compression_magic_is_detected() {
+        assert_eq!(Compression::from_magic(b"BZh91AY&SY"), Compression::Bzip2);
+        assert_eq!(
+            Compression::from_magic(b"\xfd7zXZ\x00\x00"),
+            Compression::Xz
+        );
+        assert_eq!(
+            Compression::from_magic(b"\x28\xb5\x2f\xfd\x00"),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::from_magic(&gzip(b"@r\n")), Compression::Gzip);
+    }
+
+    #[test]
+    fn format_mismatch_is_err() {
+        let sniffed = Sniffed {
+            compression: Compression::Bgzf,
+            format: Some(Hts::Align(Align::Bam)),
+        };
+        let obs = reconcile(Path::new("reads.fastq.gz"), sniffed);
+
+        assert!(matches!(
+            obs,
+            Err(SniffError::FormatMismatch {
+                content: Hts::Align(Align::Bam),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn compression_mismatch_is_err() {
+        let sniffed = Sniffed {
+            compression: Compression::Gzip,
+            format: Some(Hts::Fastx(Fastx::Fastq)),
+        };
+        let obs = reconcile(Path::new("reads.fastq"), sniffed);
+
+        assert!(matches!(obs, Err(SniffError::CompressionMismatch { .. })));
+    }
+
+    #[test]
+    fn content_without_extension_is_ok() {
+        let sniffed = Sniffed {
+            compression: Compression::None,
+            format: Some(Hts::Fastx(Fastx::Fasta)),
+        };
+        let obs = reconcile(Path::new("genome.txt"), sniffed).unwrap();
+
+        assert_eq!(obs, Hts::Fastx(Fastx::Fasta));
+    }
+
+    #[test]
+    fn extension_is_used_for_unrecognized_content() {
+        let sniffed = Sniffed::from_head(b"chr1\t0\t100\n");
+        let obs = reconcile(Path::new("peaks.bed"), sniffed).unwrap();
+
+        assert_eq!(obs, Hts::Bed(Bed::Bed));
+    }
+}
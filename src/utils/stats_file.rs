@@ -0,0 +1,359 @@
+//! # Stats files
+//!
+//! Save the raw statistics of a file, and combine the statistics of many files into a single report.
+//!
+//! Huge files are often split into chunks that are processed separately, like on a cluster.
+//! `bjt info fastq --save-stats` and `bjt info bam --save-stats` save the tallies from each chunk to a stats file,
+//! and `bjt info merge` combines them into the same report as processing the whole file at once.
+//!
+//! Stats files are JSON, with a version number so that files from incompatible versions of `bjt` are rejected instead of misread.
+
+use super::{
+    batch::expand_paths, formats::OutputFormat, is_stdin, sink::OutputSink, sniff::Compression,
+};
+use crate::{
+    align::info_stats::{SamBamCramStats, SamBamCramStatsState},
+    cli::CliOpt,
+    error::BjtError,
+    fastq::info_stats::{FastqStats, FastqStatsState},
+    record::{
+        barcodes::{SampleSheet, DEFAULT_TOP_INDEXES},
+        stats::MergeStats,
+    },
+};
+use clap::Parser;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use thiserror::Error;
+
+/// Version of the stats file format written by this version of `bjt`
+const STATS_FILE_VERSION: u64 = 1;
+
+#[derive(Debug, Error)]
+pub enum StatsFileError {
+    #[error("Cannot read stats file {path}. {source}")]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{path} is not a valid stats file. {source}")]
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("{path} is a version {version} stats file, but only version {STATS_FILE_VERSION} is supported. Please save the statistics again with this version of bjt.")]
+    UnsupportedVersion { path: PathBuf, version: u64 },
+
+    #[error("Statistics in {path} are inconsistent. {reason}")]
+    Inconsistent { path: PathBuf, reason: &'static str },
+
+    #[error("Cannot merge {path} with {first}. {reason}")]
+    Incompatible {
+        path: PathBuf,
+        first: PathBuf,
+        reason: &'static str,
+    },
+}
+
+/// Raw statistics of a single kind of file
+#[derive(Debug)]
+pub(crate) enum PartialStats {
+    Fastq(Box<FastqStatsState>),
+    Bam(Box<SamBamCramStatsState>),
+}
+
+/// Contents of a stats file
+#[derive(Serialize)]
+struct StatsFile<'a, T> {
+    /// Version of the stats file format
+    version: u64,
+
+    /// Kind of file the statistics came from
+    kind: &'static str,
+
+    stats: &'a T,
+}
+
+/// Kind of file in a stats file with FASTQ statistics
+const FASTQ_KIND: &str = "fastq";
+
+/// Kind of file in a stats file with SAM/BAM/CRAM statistics
+const BAM_KIND: &str = "bam";
+
+impl PartialStats {
+    /// Save raw statistics to a stats file
+    pub fn save(&self, path: &Path) -> Result<(), BjtError> {
+        // stats files are always plain JSON, whatever their extension
+        let mut sink = OutputSink::create(Some(path), Some(Compression::None), None)?;
+        let written = match self {
+            Self::Fastq(stats) => write_stats(&mut sink, FASTQ_KIND, stats),
+            Self::Bam(stats) => write_stats(&mut sink, BAM_KIND, stats),
+        };
+        written.map_err(|e| BjtError::Write {
+            path: path.to_path_buf(),
+            source: e.into(),
+        })?;
+
+        sink.finish()
+    }
+
+    /// Load raw statistics from a stats file (or standard input)
+    pub fn load(path: &Path) -> Result<Self, StatsFileError> {
+        let parse_err = |source| StatsFileError::Parse {
+            path: path.to_path_buf(),
+            source,
+        };
+        let inconsistent = |reason| StatsFileError::Inconsistent {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let reader: Box<dyn Read> = if is_stdin(path) {
+            Box::new(io::stdin())
+        } else {
+            Box::new(File::open(path).map_err(|source| StatsFileError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?)
+        };
+        let mut file: Value = serde_json::from_reader(BufReader::new(reader)).map_err(parse_err)?;
+
+        // check the version before anything else, since other versions may have a different layout
+        match file.get("version").and_then(Value::as_u64) {
+            Some(STATS_FILE_VERSION) => {}
+            Some(version) => {
+                return Err(StatsFileError::UnsupportedVersion {
+                    path: path.to_path_buf(),
+                    version,
+                })
+            }
+            None => return Err(inconsistent("It has no stats file version.")),
+        }
+
+        let stats = file["stats"].take();
+        match file.get("kind").and_then(Value::as_str) {
+            Some(FASTQ_KIND) => Ok(Self::Fastq(
+                serde_json::from_value(stats).map_err(parse_err)?,
+            )),
+            Some(BAM_KIND) => Ok(Self::Bam(serde_json::from_value(stats).map_err(parse_err)?)),
+            _ => Err(inconsistent(
+                "It doesn't say what kind of file its statistics are from.",
+            )),
+        }
+    }
+}
+
+/// Write statistics of a given kind of file as a stats file
+fn write_stats<W: Write, T: Serialize>(
+    writer: W,
+    kind: &'static str,
+    stats: &T,
+) -> serde_json::Result<()> {
+    let file = StatsFile {
+        version: STATS_FILE_VERSION,
+        kind,
+        stats,
+    };
+
+    serde_json::to_writer(writer, &file)
+}
+
+/// Statistics combined from one or more stats files
+enum MergedStats {
+    Fastq(Box<FastqStats>),
+    Bam(Box<SamBamCramStats>),
+}
+
+impl MergedStats {
+    /// Load the statistics from a stats file
+    fn load(path: &Path) -> Result<Self, StatsFileError> {
+        let inconsistent = |reason| StatsFileError::Inconsistent {
+            path: path.to_path_buf(),
+            reason,
+        };
+
+        let stats = match PartialStats::load(path)? {
+            PartialStats::Fastq(state) => Self::Fastq(Box::new(
+                FastqStats::try_from(*state).map_err(inconsistent)?,
+            )),
+            PartialStats::Bam(state) => Self::Bam(Box::new((*state).into())),
+        };
+
+        Ok(stats)
+    }
+
+    /// Add the statistics from `path` to the ones combined so far, which started with `first`
+    fn merge(&mut self, path: &Path, first: &Path) -> Result<(), StatsFileError> {
+        let incompatible = |reason| StatsFileError::Incompatible {
+            path: path.to_path_buf(),
+            first: first.to_path_buf(),
+            reason,
+        };
+
+        match (self, Self::load(path)?) {
+            (Self::Fastq(stats), Self::Fastq(other)) => {
+                if let (Some(a), Some(b)) =
+                    (stats.nanopore_bin_minutes(), other.nanopore_bin_minutes())
+                {
+                    if a != b {
+                        return Err(incompatible(
+                            "Their Oxford Nanopore timelines have bins of different widths.",
+                        ));
+                    }
+                }
+                stats.merge(&other);
+            }
+            (Self::Bam(stats), Self::Bam(other)) => stats.merge(&other),
+            _ => {
+                return Err(incompatible(
+                    "They have statistics from different kinds of files.",
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Raw statistics, to save to a stats file
+    fn state(&self) -> PartialStats {
+        match self {
+            Self::Fastq(stats) => PartialStats::Fastq(Box::new(stats.as_ref().into())),
+            Self::Bam(stats) => PartialStats::Bam(Box::new(stats.as_ref().into())),
+        }
+    }
+}
+
+/// CLI options for combining stats files
+#[derive(Debug, Parser)]
+pub(crate) struct MergeStatsOpts {
+    /// Stats files saved by `bjt info fastq --save-stats` or `bjt info bam --save-stats` (glob patterns are expanded).
+    /// All files must have statistics from the same kind of file.
+    #[clap(name = "STATS", required = true)]
+    stats_paths: Vec<PathBuf>,
+
+    /// Output format to return statistics in
+    #[clap(short = 'f', long, default_value = "human")]
+    format: OutputFormat,
+
+    /// Also save the combined raw statistics to this file, so that they can be merged again
+    #[clap(long, value_name = "FILE")]
+    save_stats: Option<PathBuf>,
+
+    /// Number of the most common index sequences to report
+    #[clap(
+        long = "top-indexes",
+        value_name = "N",
+        default_value_t = DEFAULT_TOP_INDEXES
+    )]
+    n_top_indexes: usize,
+
+    /// Check index sequences against the samples in this sample sheet.
+    /// Either an Illumina sample sheet, or a CSV/TSV file with `sample,i7[,i5]` on each line.
+    #[clap(long, value_name = "FILE", value_parser = SampleSheet::from_path_arg)]
+    sample_sheet: Option<Arc<SampleSheet>>,
+}
+
+impl CliOpt for MergeStatsOpts {
+    fn exec(&self) -> anyhow::Result<()> {
+        let paths = expand_paths(&self.stats_paths)?;
+        let (first, rest) = paths
+            .split_first()
+            .expect("at least one stats file is required by clap");
+
+        let mut merged = MergedStats::load(first)?;
+        for path in rest {
+            merged.merge(path, first)?;
+        }
+
+        if let Some(stats_path) = &self.save_stats {
+            merged.state().save(stats_path)?;
+        }
+
+        let rendered = match &mut merged {
+            MergedStats::Fastq(stats) => {
+                stats.set_index_report(self.n_top_indexes, self.sample_sheet.clone());
+                self.format.render(stats)?
+            }
+            MergedStats::Bam(stats) => self.format.render(stats)?,
+        };
+        print!("{}", rendered);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::record::stats::RecordStats;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    const BAM_STATS: &str = r#"{
+        "version": 1,
+        "kind": "bam",
+        "stats": {
+            "valid_records": 2,
+            "invalid_records": 0,
+            "bases": 20,
+            "lengths": {"10": 2},
+            "instruments": {},
+            "flow_cell_ids": {},
+            "lanes": {},
+            "sra_runs": null,
+            "unrecognized_names": 0
+        }
+    }"#;
+
+    #[test]
+    fn stats_are_merged() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("merged.bam.json");
+        write(&path, BAM_STATS).unwrap();
+        let mut merged = MergedStats::load(&path).unwrap();
+        merged.merge(&path, &path).unwrap();
+
+        let MergedStats::Bam(stats) = merged else {
+            panic!("BAM statistics were loaded as another kind");
+        };
+        assert_eq!(stats.n_valid(), 4);
+        assert_eq!(stats.lengths().get(&10), Some(&4));
+    }
+
+    #[test]
+    fn different_kinds_are_incompatible() {
+        let dir = tempdir().unwrap();
+        let bam = dir.path().join("kinds.bam.json");
+        let fastq = dir.path().join("kinds.fastq.json");
+        write(&bam, BAM_STATS).unwrap();
+        PartialStats::Fastq(Box::new((&FastqStats::new()).into()))
+            .save(&fastq)
+            .unwrap();
+
+        let obs = MergedStats::load(&fastq).unwrap().merge(&bam, &fastq);
+
+        assert!(matches!(obs, Err(StatsFileError::Incompatible { .. })));
+    }
+
+    #[test]
+    fn newer_version_is_err() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("newer.json");
+        write(
+            &path,
+            BAM_STATS.replace(r#""version": 1"#, r#""version": 99"#),
+        )
+        .unwrap();
+        let obs = PartialStats::load(&path);
+
+        assert!(matches!(
+            obs,
+            Err(StatsFileError::UnsupportedVersion { version: 99, .. })
+        ));
+    }
+}